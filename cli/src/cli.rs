@@ -1,55 +1,379 @@
-use clap::{App, Arg, SubCommand};
+use crate::query::OutputFormat;
+use clap::{App, AppSettings, Arg, SubCommand};
 
 pub fn build_cli() -> App<'static, 'static> {
-    use clap::{crate_authors, crate_version};
+    use clap::crate_version;
     App::new("knowgraf-cli")
         .version(crate_version!())
-        .author(crate_authors!(","))
+        .author(env!("CARGO_PKG_AUTHORS"))
         .about("Command-line interface to knowgraf.")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
         .arg(
             Arg::with_name("file")
                 .short("f")
                 .long("file")
                 .value_name("PATH")
-                .help("Specify the db")
-                .required(true),
+                .help("Specify the db; needed by every subcommand but convert, unless --endpoint is given"),
+        )
+        .arg(
+            Arg::with_name("endpoint")
+                .long("endpoint")
+                .value_name("URL")
+                .conflicts_with("file")
+                .help(
+                    "Work on a running knowgraf server instead of a db, e.g. http://localhost:8080",
+                ),
         )
         .subcommand(
-            SubCommand::with_name("query").about("query the graf").arg(
-                Arg::with_name("query")
-                    .short("q")
-                    .long("query")
-                    .value_name("INPUT"),
-            ),
+            SubCommand::with_name("query")
+                .about("query the graf")
+                .arg(
+                    Arg::with_name("query")
+                        .short("q")
+                        .long("query")
+                        .value_name("INPUT")
+                        .help("The SPARQL query [default: read from stdin]"),
+                )
+                .arg(
+                    Arg::with_name("query-file")
+                        .long("query-file")
+                        .value_name("PATH")
+                        .conflicts_with("query")
+                        .help("Read the query from a file, or stdin if '-'"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .possible_values(OutputFormat::NAMES)
+                        .default_value("table")
+                        .help("How to print the results"),
+                ),
         )
-        .arg(
-            Arg::with_name("update")
-                .short("u")
-                .long("update")
-                .value_name("INPUT"),
+        .subcommand(
+            SubCommand::with_name("update")
+                .about("update the graf")
+                .arg(
+                    Arg::with_name("update")
+                        .short("u")
+                        .long("update")
+                        .value_name("INPUT")
+                        .help("The SPARQL update [default: read from stdin]"),
+                )
+                .arg(
+                    Arg::with_name("update-file")
+                        .long("update-file")
+                        .value_name("PATH")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .conflicts_with("update")
+                        .help("Read an update from a file, or stdin if '-'; files run in order"),
+                )
+                .arg(
+                    Arg::with_name("using-graph-uri")
+                        .long("using-graph-uri")
+                        .value_name("IRI")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Use the graph as default graph of the update's WHERE clauses"),
+                )
+                .arg(
+                    Arg::with_name("using-named-graph-uri")
+                        .long("using-named-graph-uri")
+                        .value_name("IRI")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Make the graph available to GRAPH in the update's WHERE clauses"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("load")
+                .about("load RDF data into the graf")
+                .arg(
+                    Arg::with_name("data")
+                        .short("d")
+                        .long("data")
+                        .value_name("INPUT")
+                        .required(true)
+                        .multiple(true)
+                        .help(
+                            "Files, directories or glob patterns to load, or '-' for stdin; \
+                             directories are searched for files with an RDF extension",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help(
+                            "One of ntriples, turtle, rdfxml, nquads, trig or jsonld \
+                             [default: guessed from the file extension]",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("graph")
+                        .long("graph")
+                        .value_name("IRI")
+                        .help("Load triples into this named graph instead of the default graph"),
+                )
+                .arg(
+                    Arg::with_name("base")
+                        .long("base")
+                        .value_name("IRI")
+                        .help("Base IRI to resolve relative IRIs against"),
+                )
+                .arg(
+                    Arg::with_name("jobs")
+                        .short("j")
+                        .long("jobs")
+                        .value_name("N")
+                        .default_value("4")
+                        .help("How many files to load in parallel"),
+                )
+                .arg(
+                    Arg::with_name("keep-going")
+                        .long("keep-going")
+                        .help("Go on loading the other files after one fails"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("dump")
+                .about("write the graf out as RDF")
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("PATH")
+                        .help("The file to write, or '-' for stdout [default: -]"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help(
+                            "One of ntriples, turtle, rdfxml, nquads, trig or jsonld \
+                             [default: guessed from the output file, else nquads]",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("graph")
+                        .long("graph")
+                        .value_name("IRI")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Only dump this named graph"),
+                )
+                .arg(
+                    Arg::with_name("default-graph")
+                        .long("default-graph")
+                        .help("Only dump the default graph, along with any --graph"),
+                )
+                .arg(
+                    Arg::with_name("context")
+                        .long("context")
+                        .value_name("PATH")
+                        .help("JSON-LD context to compact the output against"),
+                )
+                .arg(
+                    Arg::with_name("split")
+                        .long("split")
+                        .value_name("DIR")
+                        .conflicts_with("output")
+                        .help("Write one file per graph into this directory, listed in manifest.tsv"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("shell")
+                .about("query and update the graf interactively")
+                .arg(
+                    Arg::with_name("history")
+                        .long("history")
+                        .value_name("PATH")
+                        .help("Keep the shell history in this file [default: ~/.kg-cli_history]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("stats")
+                .about("show statistics of the graf")
+                .arg(
+                    Arg::with_name("top")
+                        .long("top")
+                        .value_name("N")
+                        .default_value("10")
+                        .help("How many of the most used predicates and classes to list"),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Print the statistics as JSON"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("graphs")
+                .about("list and manage the named graphs; 'default' names the default graph")
+                .subcommand(SubCommand::with_name("list").about("list the named graphs"))
+                .subcommand(
+                    SubCommand::with_name("rm")
+                        .about("remove graphs, or empty the default graph")
+                        .arg(
+                            Arg::with_name("graph")
+                                .value_name("GRAPH")
+                                .required(true)
+                                .multiple(true),
+                        ),
+                )
+                .subcommand(graph_pair("copy", "replace a graph with a copy of another"))
+                .subcommand(graph_pair("move", "replace a graph with another one"))
+                .subcommand(graph_pair("rename", "move a graph to a name not taken yet")),
         )
         .subcommand(
-            SubCommand::with_name("load").arg(
-                Arg::with_name("data")
-                    .short("d")
-                    .long("data")
-                    .value_name("INPUT"),
-            ),
+            SubCommand::with_name("convert")
+                .about("convert RDF between formats, without a db")
+                .arg(
+                    Arg::with_name("input")
+                        .short("i")
+                        .long("input")
+                        .value_name("PATH")
+                        .default_value("-")
+                        .help("The file to convert, or '-' for stdin"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("PATH")
+                        .default_value("-")
+                        .help("The file to write, or '-' for stdout"),
+                )
+                .arg(
+                    Arg::with_name("from")
+                        .long("from")
+                        .value_name("FORMAT")
+                        .help("Format of the input [default: guessed from the input file]"),
+                )
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .value_name("FORMAT")
+                        .help(
+                            "Format of the output [default: guessed from the output file, \
+                             else ntriples, or nquads for quad formats]",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("base")
+                        .long("base")
+                        .value_name("IRI")
+                        .help("Base IRI to resolve relative IRIs against"),
+                )
+                .arg(
+                    Arg::with_name("graph")
+                        .long("graph")
+                        .value_name("IRI")
+                        .help("Put the triples in this named graph when writing quads"),
+                ),
+        )
+}
+
+/// A `graphs` action taking a source and a target graph.
+fn graph_pair(name: &'static str, about: &'static str) -> App<'static, 'static> {
+    SubCommand::with_name(name)
+        .about(about)
+        .arg(
+            Arg::with_name("from")
+                .value_name("FROM")
+                .required(true)
+                .help("The source graph"),
+        )
+        .arg(
+            Arg::with_name("to")
+                .value_name("TO")
+                .required(true)
+                .help("The target graph"),
         )
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::target::Target;
+
+    #[test]
+    fn file_or_endpoint_is_required() {
+        let args = vec!["kg-cli", "-f", "db", "query"];
+        assert!(build_cli().get_matches_from_safe(args).is_ok());
+        let args = vec!["kg-cli", "--endpoint", "http://localhost:8080", "query"];
+        let matches = build_cli().get_matches_from_safe(args).unwrap();
+        assert!(Target::open(matches.value_of("file"), matches.value_of("endpoint")).is_ok());
+
+        let args = vec!["kg-cli", "query"];
+        let matches = build_cli().get_matches_from_safe(args).unwrap();
+        let err = Target::open(matches.value_of("file"), matches.value_of("endpoint"))
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "--file or --endpoint is required");
+    }
+
+    #[test]
+    fn query_file_conflicts_with_query() {
+        let args = vec![
+            "kg-cli",
+            "-f",
+            "db",
+            "query",
+            "-q",
+            "ASK {}",
+            "--query-file",
+            "q.rq",
+        ];
+        assert!(build_cli().get_matches_from_safe(args).is_err());
+    }
+
+    #[test]
+    fn load_takes_several_inputs() {
+        let args = vec!["kg-cli", "-f", "db", "load", "-d", "a.nt", "data/", "*.ttl"];
+        let matches = build_cli().get_matches_from(args);
+        let matches = matches.subcommand_matches("load").unwrap();
+        assert_eq!(matches.values_of("data").unwrap().count(), 3);
+        assert_eq!(matches.value_of("jobs"), Some("4"));
+    }
+
+    #[test]
+    fn endpoint_replaces_file() {
+        let args = vec!["kg-cli", "--endpoint", "http://localhost:8080", "query"];
+        assert!(build_cli().get_matches_from_safe(args).is_ok());
+        let args = vec!["kg-cli", "-f", "db", "--endpoint", "http://localhost:8080"];
+        assert!(build_cli().get_matches_from_safe(args).is_err());
+    }
+
+    #[test]
+    fn convert_needs_no_file() {
+        let args = vec!["kg-cli", "convert", "-i", "data.ttl", "--to", "nt"];
+        assert!(build_cli().get_matches_from_safe(args).is_ok());
+    }
 
     #[test]
-    fn file_is_required() {
-        let args = vec!["kg-cli"];
-        let m = build_cli().get_matches_from_safe(args);
-
-        match m {
-            Err(_) => {}
-            _ => panic!("should not be here."),
-        }
+    fn graphs_actions() {
+        let args = vec!["kg-cli", "-f", "db", "graphs"];
+        let matches = build_cli().get_matches_from(args);
+        assert_eq!(
+            matches
+                .subcommand_matches("graphs")
+                .unwrap()
+                .subcommand_name(),
+            None
+        );
+        let args = vec!["kg-cli", "-f", "db", "graphs", "rename", "http://e/a"];
+        assert!(build_cli().get_matches_from_safe(args).is_err());
+        let args = vec![
+            "kg-cli",
+            "-f",
+            "db",
+            "graphs",
+            "rm",
+            "default",
+            "http://e/a",
+        ];
+        assert!(build_cli().get_matches_from_safe(args).is_ok());
     }
 }
@@ -0,0 +1,259 @@
+use crate::format::{QuadWriter, RdfFormat};
+use anyhow::{anyhow, bail, Error};
+use knowgraf::jsonld;
+use oxigraph::io::{DatasetFormat, DatasetParser, GraphFormat, GraphParser, GraphSerializer};
+use oxigraph::model::{GraphName, NamedNode, Quad, Triple};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+/// Picks the format of `path` for `convert`: `format` if given, else from
+/// the extension. Standard input needs `format`, standard output defaults to
+/// `default`.
+pub fn format(
+    path: &str,
+    format: Option<&str>,
+    flag: &str,
+    default: Option<RdfFormat>,
+) -> Result<RdfFormat, Error> {
+    match (format, default) {
+        (Some(name), _) => name.parse(),
+        (None, Some(default)) if path == "-" => Ok(default),
+        (None, None) if path == "-" => bail!("{} is required to read from stdin", flag),
+        (None, _) => RdfFormat::from_path(Path::new(path)).ok_or_else(|| {
+            anyhow!(
+                "can't tell the RDF format of {} from its extension, use {}",
+                path,
+                flag
+            )
+        }),
+    }
+}
+
+/// The output format used when writing to stdout without `--to`: the
+/// N-Triples or N-Quads counterpart of `from`.
+pub fn default_output_format(from: RdfFormat) -> RdfFormat {
+    match from {
+        RdfFormat::Graph(_) => RdfFormat::Graph(GraphFormat::NTriples),
+        RdfFormat::Dataset(_) | RdfFormat::JsonLd => RdfFormat::Dataset(DatasetFormat::NQuads),
+    }
+}
+
+/// Streams `reader` from format `from` to `out` in format `to`.
+///
+/// Triples written as quads go to `graph`, or to the default graph when it
+/// is `None`. Quads can only be written as triples if they are all in the
+/// default graph.
+pub fn convert(
+    reader: impl BufRead,
+    from: RdfFormat,
+    to: RdfFormat,
+    base_iri: Option<&str>,
+    graph: Option<&NamedNode>,
+    mut out: impl Write,
+) -> Result<(), Error> {
+    match (from, to) {
+        (RdfFormat::Graph(from), RdfFormat::Graph(to)) => {
+            if graph.is_some() {
+                bail!("--graph only applies when converting triples to quads");
+            }
+            let mut writer = GraphSerializer::from_format(to).triple_writer(&mut out)?;
+            for triple in triples(reader, from, base_iri)? {
+                writer.write(&triple?)?;
+            }
+            writer.finish()?;
+        }
+        (RdfFormat::Graph(from), to) => {
+            let graph = graph.map_or(GraphName::DefaultGraph, |graph| graph.clone().into());
+            let mut writer = QuadWriter::new(to, &mut out, None)?;
+            for triple in triples(reader, from, base_iri)? {
+                writer.write(&triple?.in_graph(graph.clone()))?;
+            }
+            writer.finish()?;
+        }
+        (_, _) if graph.is_some() => {
+            bail!("--graph can't be used with quad formats, their quads have a graph already")
+        }
+        (from, RdfFormat::Graph(to)) => {
+            let mut writer = GraphSerializer::from_format(to).triple_writer(&mut out)?;
+            for quad in quads(reader, from, base_iri)? {
+                let quad = quad?;
+                if !quad.graph_name.is_default_graph() {
+                    bail!(
+                        "the input has quads in the graph {}, which a triple format can't hold",
+                        quad.graph_name
+                    );
+                }
+                writer.write(&Triple::from(quad))?;
+            }
+            writer.finish()?;
+        }
+        (from, to) => {
+            let mut writer = QuadWriter::new(to, &mut out, None)?;
+            for quad in quads(reader, from, base_iri)? {
+                writer.write(&quad?)?;
+            }
+            writer.finish()?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn triples<R: BufRead>(
+    reader: R,
+    format: GraphFormat,
+    base_iri: Option<&str>,
+) -> Result<impl Iterator<Item = Result<Triple, io::Error>>, Error> {
+    let mut parser = GraphParser::from_format(format);
+    if let Some(base_iri) = base_iri {
+        parser = parser.with_base_iri(base_iri)?;
+    }
+    Ok(parser.read_triples(reader)?)
+}
+
+/// Reads the quads of a dataset format or, all at once, of JSON-LD.
+fn quads<'a, R: BufRead + 'a>(
+    reader: R,
+    format: RdfFormat,
+    base_iri: Option<&str>,
+) -> Result<Box<dyn Iterator<Item = Result<Quad, io::Error>> + 'a>, Error> {
+    match format {
+        RdfFormat::Dataset(format) => {
+            let mut parser = DatasetParser::from_format(format);
+            if let Some(base_iri) = base_iri {
+                parser = parser.with_base_iri(base_iri)?;
+            }
+            Ok(Box::new(parser.read_quads(reader)?))
+        }
+        RdfFormat::JsonLd => Ok(Box::new(
+            jsonld::parse(reader, base_iri)?.into_iter().map(Ok),
+        )),
+        RdfFormat::Graph(_) => bail!("{:?} doesn't hold quads", format),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn converted(
+        input: &str,
+        from: RdfFormat,
+        to: RdfFormat,
+        base_iri: Option<&str>,
+        graph: Option<&str>,
+    ) -> Result<String, Error> {
+        let graph = graph.map(|iri| NamedNode::new(iri).unwrap());
+        let mut out = Vec::new();
+        convert(
+            input.as_bytes(),
+            from,
+            to,
+            base_iri,
+            graph.as_ref(),
+            &mut out,
+        )?;
+        Ok(String::from_utf8(out)?)
+    }
+
+    #[test]
+    fn turtle_to_ntriples_with_base() {
+        let out = converted(
+            "@prefix ex: <http://e/> . <s> ex:p ex:o .",
+            RdfFormat::Graph(GraphFormat::Turtle),
+            RdfFormat::Graph(GraphFormat::NTriples),
+            Some("http://b/"),
+            None,
+        )
+        .unwrap();
+        assert_eq!(out, "<http://b/s> <http://e/p> <http://e/o> .\n");
+    }
+
+    #[test]
+    fn triples_into_a_graph() {
+        let out = converted(
+            "<http://e/s> <http://e/p> <http://e/o> .",
+            RdfFormat::Graph(GraphFormat::NTriples),
+            RdfFormat::Dataset(DatasetFormat::NQuads),
+            None,
+            Some("http://e/g"),
+        )
+        .unwrap();
+        assert_eq!(
+            out,
+            "<http://e/s> <http://e/p> <http://e/o> <http://e/g> .\n"
+        );
+    }
+
+    #[test]
+    fn quads_to_triples() {
+        let nquads = RdfFormat::Dataset(DatasetFormat::NQuads);
+        let ntriples = RdfFormat::Graph(GraphFormat::NTriples);
+        let out = converted(
+            "<http://e/s> <http://e/p> <http://e/o> .",
+            nquads,
+            ntriples,
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(out, "<http://e/s> <http://e/p> <http://e/o> .\n");
+        assert!(converted(
+            "<http://e/s> <http://e/p> <http://e/o> <http://e/g> .",
+            nquads,
+            ntriples,
+            None,
+            None
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn json_ld() {
+        let out = converted(
+            r#"{ "@context": { "@vocab": "http://e/" }, "@id": "s", "p": "v" }"#,
+            RdfFormat::JsonLd,
+            RdfFormat::Dataset(DatasetFormat::NQuads),
+            Some("http://b/"),
+            None,
+        )
+        .unwrap();
+        assert_eq!(out, "<http://b/s> <http://e/p> \"v\" .\n");
+        let out = converted(
+            "<http://e/s> <http://e/p> <http://e/o> .",
+            RdfFormat::Graph(GraphFormat::NTriples),
+            RdfFormat::JsonLd,
+            None,
+            Some("http://e/g"),
+        )
+        .unwrap();
+        assert_eq!(
+            out,
+            "[\n{\"@id\": \"http://e/g\", \"@graph\": [\n\
+             {\"@id\":\"http://e/s\",\"http://e/p\":[{\"@id\":\"http://e/o\"}]}\n]}\n]\n"
+        );
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!(
+            format("data.trig", None, "--from", None).unwrap(),
+            RdfFormat::Dataset(DatasetFormat::TriG)
+        );
+        assert!(format("-", None, "--from", None).is_err());
+        assert_eq!(
+            format(
+                "-",
+                None,
+                "--to",
+                Some(RdfFormat::Graph(GraphFormat::NTriples))
+            )
+            .unwrap(),
+            RdfFormat::Graph(GraphFormat::NTriples)
+        );
+        assert_eq!(
+            format("out.nq", Some("turtle"), "--to", None).unwrap(),
+            RdfFormat::Graph(GraphFormat::Turtle)
+        );
+    }
+}
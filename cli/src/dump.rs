@@ -0,0 +1,435 @@
+use crate::format::{QuadWriter, RdfFormat};
+use crate::remote::Client;
+use anyhow::{anyhow, bail, Error};
+use knowgraf::jsonld::Context;
+use oxigraph::io::{DatasetFormat, GraphFormat};
+use oxigraph::model::{GraphName, GraphNameRef, NamedOrBlankNode};
+use oxigraph::SledStore;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// The longest name [`split`] gives a file, extension included, well under
+/// the 255 bytes most file systems allow.
+const MAX_FILE_NAME: usize = 200;
+
+/// The file [`split`] lists the graph of each file in, one per line: the
+/// file name, a tab, and the graph name in N-Quads syntax, or `DEFAULT`.
+pub const MANIFEST: &str = "manifest.tsv";
+
+/// Picks the format to dump to: `format` if given, else from the extension
+/// of `output`, else Turtle for `--split` and N-Quads otherwise.
+pub fn output_format(
+    output: Option<&str>,
+    format: Option<&str>,
+    split: bool,
+) -> Result<RdfFormat, Error> {
+    if let Some(name) = format {
+        return name.parse();
+    }
+    match output.filter(|path| *path != "-") {
+        Some(path) => RdfFormat::from_path(Path::new(path)).ok_or_else(|| {
+            anyhow!(
+                "can't tell the RDF format of {} from its extension, use --format",
+                path
+            )
+        }),
+        None if split => Ok(RdfFormat::Graph(GraphFormat::Turtle)),
+        None => Ok(RdfFormat::Dataset(DatasetFormat::NQuads)),
+    }
+}
+
+/// Reads the JSON-LD context to compact the dump against from `path`.
+pub fn read_context(path: Option<&str>, format: RdfFormat) -> Result<Option<Context>, Error> {
+    match path {
+        Some(_) if format != RdfFormat::JsonLd => bail!("--context only applies to JSON-LD"),
+        Some(path) => Ok(Some(Context::from_reader(io::BufReader::new(
+            fs::File::open(path)?,
+        ))?)),
+        None => Ok(None),
+    }
+}
+
+/// Creates the file at `path`, or writes to stdout if `path` is `-`.
+pub fn create_output(path: &str) -> io::Result<Box<dyn Write>> {
+    if path == "-" {
+        Ok(Box::new(io::BufWriter::new(io::stdout())))
+    } else {
+        Ok(Box::new(io::BufWriter::new(fs::File::create(path)?)))
+    }
+}
+
+/// Writes `graphs`, or the whole dataset if empty, to `out`. JSON-LD is
+/// compacted against `context` if given.
+///
+/// A triple format can only hold a single graph.
+pub fn dump(
+    store: &SledStore,
+    graphs: &[GraphName],
+    format: RdfFormat,
+    context: Option<&Context>,
+    mut out: impl Write,
+) -> Result<(), Error> {
+    check_graphs_exist(store, graphs)?;
+    match (format, graphs) {
+        (RdfFormat::Dataset(format), []) => store.dump_dataset(&mut out, format)?,
+        (RdfFormat::Graph(format), [graph]) => store.dump_graph(&mut out, format, graph)?,
+        (RdfFormat::Graph(_), _) => bail!(
+            "a triple format holds a single graph, select one with --graph, \
+             use --split or a quad format"
+        ),
+        (format, []) => {
+            let mut writer = QuadWriter::new(format, &mut out, context)?;
+            for quad in store.iter() {
+                writer.write(&quad?)?;
+            }
+            writer.finish()?;
+        }
+        (format, graphs) => {
+            let mut writer = QuadWriter::new(format, &mut out, context)?;
+            for graph in graphs {
+                for quad in store.quads_for_pattern(None, None, None, Some(graph.as_ref())) {
+                    writer.write(&quad?)?;
+                }
+            }
+            writer.finish()?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Fetches `graphs`, or the whole dataset if empty, from the server behind
+/// `client` and writes them to `out`.
+///
+/// The Graph Store serves either the whole dataset or a single graph, so
+/// those are the only selections possible. The server compacts JSON-LD.
+pub fn dump_remote(
+    client: &Client,
+    graphs: &[GraphName],
+    format: RdfFormat,
+    context: Option<&Context>,
+    mut out: impl Write,
+) -> Result<(), Error> {
+    let context = context.map(|context| context.as_json().to_string());
+    let context = context.as_deref();
+    let mut body = match (format, graphs) {
+        (RdfFormat::Dataset(_), []) | (RdfFormat::JsonLd, []) => {
+            client.get_store(None, format.media_type(), context)?
+        }
+        (RdfFormat::Graph(_), [graph]) | (RdfFormat::JsonLd, [graph]) => {
+            client.get_store(Some(graph), format.media_type(), context)?
+        }
+        _ => bail!(
+            "with --endpoint, dump either the whole dataset in a quad format \
+             or a single --graph in a triple format"
+        ),
+    };
+    io::copy(&mut body, &mut out)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Writes each of `graphs`, or every non-empty graph if empty, to its own
+/// file in `dir`, along with their [`MANIFEST`], and returns the paths of
+/// the graph files.
+pub fn split(
+    store: &SledStore,
+    graphs: &[GraphName],
+    format: RdfFormat,
+    dir: &Path,
+) -> Result<Vec<PathBuf>, Error> {
+    let format = match format {
+        RdfFormat::Graph(format) => format,
+        _ => bail!("--split needs a triple format"),
+    };
+    check_graphs_exist(store, graphs)?;
+    let graphs = if graphs.is_empty() {
+        all_graphs(store)?
+    } else {
+        graphs.to_vec()
+    };
+    let names = file_names(&graphs, format);
+    fs::create_dir_all(dir)?;
+    let mut paths = Vec::new();
+    let mut manifest = String::new();
+    for (graph, name) in graphs.iter().zip(names) {
+        let path = dir.join(&name);
+        let mut out = io::BufWriter::new(fs::File::create(&path)?);
+        store.dump_graph(&mut out, format, graph)?;
+        out.flush()?;
+        manifest.push_str(&format!("{}\t{}\n", name, graph));
+        paths.push(path);
+    }
+    fs::write(dir.join(MANIFEST), manifest)?;
+    Ok(paths)
+}
+
+fn all_graphs(store: &SledStore) -> Result<Vec<GraphName>, Error> {
+    let mut graphs = Vec::new();
+    let mut default_graph =
+        store.quads_for_pattern(None, None, None, Some(GraphNameRef::DefaultGraph));
+    if default_graph.next().is_some() {
+        graphs.push(GraphName::DefaultGraph);
+    }
+    for graph in store.named_graphs() {
+        graphs.push(match graph? {
+            NamedOrBlankNode::NamedNode(node) => node.into(),
+            NamedOrBlankNode::BlankNode(node) => node.into(),
+        });
+    }
+    Ok(graphs)
+}
+
+fn check_graphs_exist(store: &SledStore, graphs: &[GraphName]) -> Result<(), Error> {
+    for graph in graphs {
+        let exists = match graph {
+            GraphName::DefaultGraph => true,
+            GraphName::NamedNode(node) => store.contains_named_graph(node)?,
+            GraphName::BlankNode(node) => store.contains_named_graph(node)?,
+        };
+        if !exists {
+            bail!("the graph {} does not exist", graph);
+        }
+    }
+    Ok(())
+}
+
+/// The file `graph` is written to by [`split`]: the graph name with every
+/// byte outside `[A-Za-z0-9._-]` percent-encoded. The default graph goes to
+/// `default`, which can't clash with an absolute IRI as its `:` would be
+/// encoded. Names longer than [`MAX_FILE_NAME`] are replaced by a
+/// [`hashed_file_name`].
+pub fn file_name(graph: &GraphName, format: GraphFormat) -> String {
+    let name = match graph {
+        GraphName::DefaultGraph => "default".to_string(),
+        GraphName::NamedNode(node) => percent_encode(node.as_str()),
+        GraphName::BlankNode(node) => percent_encode(&node.to_string()),
+    };
+    let name = format!("{}.{}", name, format.file_extension());
+    if name.len() > MAX_FILE_NAME {
+        hashed_file_name(graph, format, 0)
+    } else {
+        name
+    }
+}
+
+/// `graph-` and a hash of the graph name, followed by `-attempt` past the
+/// first attempt. These names have no `%`, so they can't clash with the
+/// encoded ones.
+fn hashed_file_name(graph: &GraphName, format: GraphFormat, attempt: usize) -> String {
+    let hash = fnv1a(graph.to_string().as_bytes());
+    match attempt {
+        0 => format!("graph-{:016x}.{}", hash, format.file_extension()),
+        n => format!("graph-{:016x}-{}.{}", hash, n, format.file_extension()),
+    }
+}
+
+/// The [`file_name`]s of `graphs`. A name that only differs in case from an
+/// earlier one, which would overwrite it on case-insensitive file systems
+/// such as the macOS and Windows defaults, is replaced by a hashed one.
+fn file_names(graphs: &[GraphName], format: GraphFormat) -> Vec<String> {
+    let mut taken = HashSet::new();
+    graphs
+        .iter()
+        .map(|graph| {
+            let mut name = file_name(graph, format);
+            let mut attempt = 0;
+            while !taken.insert(name.to_lowercase()) {
+                name = hashed_file_name(graph, format, attempt);
+                attempt += 1;
+            }
+            name
+        })
+        .collect()
+}
+
+/// The 64-bit FNV-1a hash of `bytes`. Unlike the hasher of the standard
+/// library it doesn't change between Rust versions, so the files keep their
+/// names from one dump to the next.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"._-".contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxigraph::model::NamedNode;
+    use tempfile::tempdir;
+
+    const DATA: &str = "\
+<http://e/s> <http://e/p> <http://e/o> .
+<http://e/s> <http://e/p> <http://e/o> <http://e/g1> .
+<http://e/s> <http://e/p> <http://e/o2> <http://e/g2> .
+";
+
+    fn store() -> (tempfile::TempDir, SledStore) {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path().join("db")).unwrap();
+        store
+            .load_dataset(DATA.as_bytes(), DatasetFormat::NQuads, None)
+            .unwrap();
+        (dir, store)
+    }
+
+    fn graph(iri: &str) -> GraphName {
+        NamedNode::new(iri).unwrap().into()
+    }
+
+    fn dumped(graphs: &[GraphName], format: RdfFormat) -> Result<String, Error> {
+        let (_dir, store) = store();
+        let mut out = Vec::new();
+        dump(&store, graphs, format, None, &mut out)?;
+        Ok(String::from_utf8(out)?)
+    }
+
+    #[test]
+    fn whole_dataset() {
+        let out = dumped(&[], RdfFormat::Dataset(DatasetFormat::NQuads)).unwrap();
+        assert_eq!(out.lines().count(), 3);
+    }
+
+    #[test]
+    fn selected_graphs() {
+        let out = dumped(
+            &[graph("http://e/g2")],
+            RdfFormat::Dataset(DatasetFormat::NQuads),
+        )
+        .unwrap();
+        assert_eq!(
+            out,
+            "<http://e/s> <http://e/p> <http://e/o2> <http://e/g2> .\n"
+        );
+        let out = dumped(
+            &[graph("http://e/g1")],
+            RdfFormat::Graph(GraphFormat::NTriples),
+        )
+        .unwrap();
+        assert_eq!(out, "<http://e/s> <http://e/p> <http://e/o> .\n");
+    }
+
+    #[test]
+    fn json_ld() {
+        let (_dir, store) = store();
+        let context = Context::parse(&serde_json::json!({ "e": "http://e/" })).unwrap();
+        let mut out = Vec::new();
+        dump(
+            &store,
+            &[graph("http://e/g2")],
+            RdfFormat::JsonLd,
+            Some(&context),
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"@context\": {\"e\":\"http://e/\"},\n\"@graph\": [\n\
+             {\"@id\": \"e:g2\", \"@graph\": [\n\
+             {\"@id\":\"e:s\",\"e:p\":{\"@id\":\"e:o2\"}}\n]}\n]}\n"
+        );
+        let out = dumped(&[], RdfFormat::JsonLd).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&out)
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .len(),
+            3
+        );
+        assert!(read_context(
+            Some("context.jsonld"),
+            RdfFormat::Graph(GraphFormat::Turtle)
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn triple_format_needs_one_graph() {
+        assert!(dumped(&[], RdfFormat::Graph(GraphFormat::Turtle)).is_err());
+        assert!(dumped(
+            &[graph("http://e/missing")],
+            RdfFormat::Graph(GraphFormat::Turtle)
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn one_file_per_graph() {
+        let (dir, store) = store();
+        let out = dir.path().join("split");
+        let mut paths = split(&store, &[], RdfFormat::Graph(GraphFormat::NTriples), &out).unwrap();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                out.join("default.nt"),
+                out.join("http%3A%2F%2Fe%2Fg1.nt"),
+                out.join("http%3A%2F%2Fe%2Fg2.nt"),
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(&paths[2]).unwrap(),
+            "<http://e/s> <http://e/p> <http://e/o2> .\n"
+        );
+        let manifest = fs::read_to_string(out.join(MANIFEST)).unwrap();
+        assert!(manifest.contains("default.nt\tDEFAULT\n"));
+        assert!(manifest.contains("http%3A%2F%2Fe%2Fg1.nt\t<http://e/g1>\n"));
+    }
+
+    #[test]
+    fn file_names_fit_and_differ() {
+        let long = graph(&format!("http://e/{}", "é".repeat(100)));
+        let names = file_names(
+            &[
+                graph("http://e/G"),
+                graph("http://e/g"),
+                long.clone(),
+                GraphName::DefaultGraph,
+            ],
+            GraphFormat::Turtle,
+        );
+        assert_eq!(names[0], "http%3A%2F%2Fe%2FG.ttl");
+        assert_eq!(
+            names[1],
+            hashed_file_name(&graph("http://e/g"), GraphFormat::Turtle, 0)
+        );
+        assert_eq!(names[2], hashed_file_name(&long, GraphFormat::Turtle, 0));
+        assert!(names[2].len() <= MAX_FILE_NAME);
+        assert_eq!(names[3], "default.ttl");
+        assert_eq!(
+            hashed_file_name(&graph("http://e/g"), GraphFormat::Turtle, 0),
+            "graph-2268c8cc90e3e08c.ttl"
+        );
+    }
+
+    #[test]
+    fn default_output_format() {
+        assert_eq!(
+            output_format(None, None, false).unwrap(),
+            RdfFormat::Dataset(DatasetFormat::NQuads)
+        );
+        assert_eq!(
+            output_format(Some("backup.trig"), None, false).unwrap(),
+            RdfFormat::Dataset(DatasetFormat::TriG)
+        );
+        assert_eq!(
+            output_format(None, None, true).unwrap(),
+            RdfFormat::Graph(GraphFormat::Turtle)
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use anyhow::{anyhow, bail, Error};
+use knowgraf::jsonld::{self, JsonLdWriter};
+use oxigraph::io::{write, DatasetFormat, DatasetSerializer, GraphFormat};
+use oxigraph::model::Quad;
+use std::io::{self, Write};
+use std::path;
+use std::str::FromStr;
+
+/// An RDF serialization, either of triples or of quads.
+///
+/// JSON-LD documents hold quads, like the dataset formats.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RdfFormat {
+    Graph(GraphFormat),
+    Dataset(DatasetFormat),
+    JsonLd,
+}
+
+impl RdfFormat {
+    /// Guesses the format from the extension of `path`.
+    pub fn from_path(path: &path::Path) -> Option<Self> {
+        if matches!(path.extension(), Some(ext) if ext == "jsonld") {
+            return Some(RdfFormat::JsonLd);
+        }
+        dataset_format_from_path(path)
+            .map(RdfFormat::Dataset)
+            .or_else(|| graph_format_from_path(path).map(RdfFormat::Graph))
+    }
+
+    pub fn media_type(self) -> &'static str {
+        match self {
+            RdfFormat::Graph(format) => format.media_type(),
+            RdfFormat::Dataset(format) => format.media_type(),
+            RdfFormat::JsonLd => jsonld::MEDIA_TYPE,
+        }
+    }
+}
+
+impl FromStr for RdfFormat {
+    type Err = Error;
+
+    /// Parses a format name, a file extension or a media type.
+    fn from_str(name: &str) -> Result<Self, Error> {
+        let format = match name {
+            "ntriples" | "nt" => RdfFormat::Graph(GraphFormat::NTriples),
+            "turtle" | "ttl" => RdfFormat::Graph(GraphFormat::Turtle),
+            "rdfxml" | "rdf" => RdfFormat::Graph(GraphFormat::RdfXml),
+            "nquads" | "nq" => RdfFormat::Dataset(DatasetFormat::NQuads),
+            "trig" => RdfFormat::Dataset(DatasetFormat::TriG),
+            "jsonld" | "json-ld" | jsonld::MEDIA_TYPE => RdfFormat::JsonLd,
+            _ => GraphFormat::from_media_type(name)
+                .map(RdfFormat::Graph)
+                .or_else(|| DatasetFormat::from_media_type(name).map(RdfFormat::Dataset))
+                .ok_or_else(|| anyhow!("unknown RDF format: {}", name))?,
+        };
+        Ok(format)
+    }
+}
+
+/// Writes quads in a dataset format or in JSON-LD.
+pub enum QuadWriter<W: Write> {
+    Dataset(write::QuadWriter<W>),
+    JsonLd(Box<JsonLdWriter<W>>),
+}
+
+impl<W: Write> QuadWriter<W> {
+    /// Starts writing to `out`, JSON-LD being compacted against `context` if
+    /// given.
+    pub fn new(
+        format: RdfFormat,
+        out: W,
+        context: Option<&jsonld::Context>,
+    ) -> Result<Self, Error> {
+        Ok(match format {
+            RdfFormat::Dataset(format) => {
+                QuadWriter::Dataset(DatasetSerializer::from_format(format).quad_writer(out)?)
+            }
+            RdfFormat::JsonLd => QuadWriter::JsonLd(Box::new(JsonLdWriter::new(out, context)?)),
+            RdfFormat::Graph(_) => bail!("{:?} can't hold quads", format),
+        })
+    }
+
+    pub fn write(&mut self, quad: &Quad) -> io::Result<()> {
+        match self {
+            QuadWriter::Dataset(writer) => writer.write(quad),
+            QuadWriter::JsonLd(writer) => writer.write_quad(quad),
+        }
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            QuadWriter::Dataset(writer) => writer.finish(),
+            QuadWriter::JsonLd(writer) => writer.finish().map(drop),
+        }
+    }
+}
+
+fn dataset_format_from_path(path: &path::Path) -> Option<DatasetFormat> {
+    match path.extension() {
+        None => None,
+        Some(ext) => {
+            if ext == DatasetFormat::TriG.file_extension() {
+                Some(DatasetFormat::TriG)
+            } else if ext == DatasetFormat::NQuads.file_extension() {
+                Some(DatasetFormat::NQuads)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+fn graph_format_from_path(path: &path::Path) -> Option<GraphFormat> {
+    let ext = path.extension()?;
+    [
+        GraphFormat::NTriples,
+        GraphFormat::Turtle,
+        GraphFormat::RdfXml,
+    ]
+    .iter()
+    .copied()
+    .find(|format| ext == format.file_extension())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod dataset_format {
+        use super::*;
+
+        #[test]
+        fn no_extension() {
+            assert_eq!(dataset_format_from_path(path::Path::new("test/test")), None);
+        }
+
+        #[test]
+        fn unknown_extension() {
+            assert_eq!(
+                dataset_format_from_path(path::Path::new("test/test.foo")),
+                None
+            );
+        }
+
+        #[test]
+        fn trig() {
+            assert_eq!(
+                dataset_format_from_path(path::Path::new("test/test.trig")),
+                Some(DatasetFormat::TriG)
+            );
+        }
+
+        #[test]
+        fn nquads() {
+            assert_eq!(
+                dataset_format_from_path(path::Path::new("test/test.nq")),
+                Some(DatasetFormat::NQuads)
+            );
+        }
+    }
+
+    mod rdf_format {
+        use super::*;
+
+        #[test]
+        fn from_path() {
+            assert_eq!(
+                RdfFormat::from_path(path::Path::new("test/test.ttl")),
+                Some(RdfFormat::Graph(GraphFormat::Turtle))
+            );
+            assert_eq!(
+                RdfFormat::from_path(path::Path::new("test/test.nq")),
+                Some(RdfFormat::Dataset(DatasetFormat::NQuads))
+            );
+            assert_eq!(
+                RdfFormat::from_path(path::Path::new("test/test.jsonld")),
+                Some(RdfFormat::JsonLd)
+            );
+            assert_eq!(RdfFormat::from_path(path::Path::new("test/test.foo")), None);
+        }
+
+        #[test]
+        fn from_str() {
+            for name in &["ntriples", "turtle", "rdfxml", "nquads", "trig", "jsonld"] {
+                assert!(name.parse::<RdfFormat>().is_ok());
+            }
+            assert_eq!(
+                "application/rdf+xml".parse::<RdfFormat>().unwrap(),
+                RdfFormat::Graph(GraphFormat::RdfXml)
+            );
+            assert_eq!(
+                "application/ld+json".parse::<RdfFormat>().unwrap(),
+                RdfFormat::JsonLd
+            );
+            assert!("yaml".parse::<RdfFormat>().is_err());
+        }
+    }
+}
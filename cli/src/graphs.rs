@@ -0,0 +1,168 @@
+use crate::table::Table;
+use anyhow::{bail, Error};
+use knowgraf::GraphCount;
+use oxigraph::model::{GraphName, NamedNode, Quad};
+use oxigraph::SledStore;
+use std::io::{self, Write};
+
+/// Parses a graph given on the command line: `default` for the default
+/// graph, else the IRI of a named graph.
+pub fn parse_graph(name: &str) -> Result<GraphName, Error> {
+    if name == "default" {
+        Ok(GraphName::DefaultGraph)
+    } else {
+        Ok(NamedNode::new(name)?.into())
+    }
+}
+
+/// Prints `graphs` as a table of graph names and quad counts.
+pub fn write(graphs: &[GraphCount], out: &mut impl Write) -> io::Result<()> {
+    let mut table = Table::new(vec!["graph".to_string(), "quads".to_string()]);
+    for graph in graphs {
+        table.push(vec![graph.graph.clone(), graph.quads.to_string()]);
+    }
+    table.write(out)?;
+    match graphs.len() {
+        1 => writeln!(out, "1 graph"),
+        n => writeln!(out, "{} graphs", n),
+    }
+}
+
+/// Whether `graph` is in `store`. The default graph always is.
+pub fn exists(store: &SledStore, graph: &GraphName) -> io::Result<bool> {
+    match graph {
+        GraphName::DefaultGraph => Ok(true),
+        GraphName::NamedNode(graph) => store.contains_named_graph(graph),
+        GraphName::BlankNode(graph) => store.contains_named_graph(graph),
+    }
+}
+
+/// Removes the named `graph` from `store`, or empties the default graph.
+pub fn remove(store: &SledStore, graph: &GraphName) -> Result<(), Error> {
+    match graph {
+        GraphName::DefaultGraph => store.clear_graph(graph)?,
+        GraphName::NamedNode(node) => {
+            if !store.contains_named_graph(node)? {
+                bail!("there is no graph {}", graph);
+            }
+            store.remove_named_graph(node)?
+        }
+        GraphName::BlankNode(_) => bail!("the graph {} can't be named", graph),
+    }
+    Ok(())
+}
+
+/// Replaces the content of `to` with that of `from`, like SPARQL `COPY`,
+/// and returns how many quads were copied.
+pub fn copy(store: &SledStore, from: &GraphName, to: &GraphName) -> Result<usize, Error> {
+    if !exists(store, from)? {
+        bail!("there is no graph {}", from);
+    }
+    if from == to {
+        return Ok(0);
+    }
+    let quads = store
+        .quads_for_pattern(None, None, None, Some(from.as_ref()))
+        .collect::<Result<Vec<_>, _>>()?;
+    match to {
+        GraphName::NamedNode(node) if !store.contains_named_graph(node)? => {
+            store.insert_named_graph(node)?
+        }
+        _ => store.clear_graph(to)?,
+    }
+    for quad in &quads {
+        store.insert(&Quad::new(
+            quad.subject.clone(),
+            quad.predicate.clone(),
+            quad.object.clone(),
+            to.clone(),
+        ))?;
+    }
+    Ok(quads.len())
+}
+
+/// Copies `from` to `to` and then removes `from`, like SPARQL `MOVE`.
+pub fn move_graph(store: &SledStore, from: &GraphName, to: &GraphName) -> Result<usize, Error> {
+    let moved = copy(store, from, to)?;
+    if from != to {
+        remove(store, from)?;
+    }
+    Ok(moved)
+}
+
+/// The SPARQL Update to `COPY` or `MOVE` (`operation`) `from` to `to`.
+pub fn copy_update(operation: &str, from: &GraphName, to: &GraphName) -> String {
+    format!("{} {} TO {}", operation, sparql(from), sparql(to))
+}
+
+/// The SPARQL Update to `DROP` `graph`.
+pub fn drop_update(graph: &GraphName) -> String {
+    format!("DROP {}", sparql(graph))
+}
+
+fn sparql(graph: &GraphName) -> String {
+    match graph {
+        GraphName::DefaultGraph => "DEFAULT".to_string(),
+        graph => format!("GRAPH {}", graph),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxigraph::io::DatasetFormat;
+    use tempfile::tempdir;
+
+    fn graph(name: &str) -> GraphName {
+        parse_graph(name).unwrap()
+    }
+
+    #[test]
+    fn manage_graphs() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        store
+            .load_dataset(
+                "<http://e/s> <http://e/p> \"1\" <http://e/a> .\n\
+                 <http://e/s> <http://e/p> \"2\" <http://e/a> .\n\
+                 <http://e/s> <http://e/p> \"3\" .\n"
+                    .as_bytes(),
+                DatasetFormat::NQuads,
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(
+            copy(&store, &graph("http://e/a"), &graph("http://e/b")).unwrap(),
+            2
+        );
+        assert_eq!(store.len(), 5);
+        assert_eq!(
+            move_graph(&store, &graph("default"), &graph("http://e/b")).unwrap(),
+            1
+        );
+        assert_eq!(store.len(), 3);
+        assert!(exists(&store, &graph("http://e/b")).unwrap());
+        remove(&store, &graph("http://e/b")).unwrap();
+        assert!(!exists(&store, &graph("http://e/b")).unwrap());
+        assert!(remove(&store, &graph("http://e/b")).is_err());
+        assert!(copy(&store, &graph("http://e/c"), &graph("http://e/a")).is_err());
+        assert_eq!(
+            GraphCount::list(&store).unwrap(),
+            vec![GraphCount {
+                graph: "http://e/a".into(),
+                quads: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn sparql_updates() {
+        assert_eq!(
+            copy_update("MOVE", &graph("default"), &graph("http://e/g")),
+            "MOVE DEFAULT TO GRAPH <http://e/g>"
+        );
+        assert_eq!(drop_update(&graph("http://e/g")), "DROP GRAPH <http://e/g>");
+        assert!(parse_graph("not an iri").is_err());
+    }
+}
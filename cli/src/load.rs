@@ -0,0 +1,557 @@
+use crate::format::RdfFormat;
+use crate::remote::Client;
+use crate::target::Target;
+use anyhow::{anyhow, bail, Error};
+use indicatif::{ProgressBar, ProgressStyle};
+use knowgraf::jsonld;
+use oxigraph::io::{DatasetParser, GraphParser};
+use oxigraph::model::{GraphName, GraphNameRef, NamedNode, QuadRef};
+use oxigraph::SledStore;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+/// Picks the format of `path`: `format` if given, else from the extension.
+pub fn input_format(path: &str, format: Option<&str>) -> Result<RdfFormat, Error> {
+    match format {
+        Some(name) => name.parse(),
+        None if path == "-" => bail!("--format is required to load from stdin"),
+        None => RdfFormat::from_path(Path::new(path)).ok_or_else(|| {
+            anyhow!(
+                "can't tell the RDF format of {} from its extension, use --format",
+                path
+            )
+        }),
+    }
+}
+
+/// Expands `inputs` into the files to load: glob patterns are matched,
+/// directories are walked recursively and anything else is taken as is.
+///
+/// Files found in directories are kept if their extension is a known RDF
+/// format, or all of them if `any_extension` is set because `--format` was
+/// given.
+pub fn expand_inputs(inputs: &[&str], any_extension: bool) -> Result<Vec<String>, Error> {
+    let mut files = Vec::new();
+    for input in inputs {
+        if *input == "-" {
+            files.push(input.to_string());
+        } else if input.contains(['*', '?', '[']) {
+            let before = files.len();
+            for path in glob::glob(input)? {
+                walk(&path?, any_extension, &mut files)?;
+            }
+            if files.len() == before {
+                bail!("no files match {}", input);
+            }
+        } else {
+            walk(Path::new(input), any_extension, &mut files)?;
+        }
+    }
+    Ok(files)
+}
+
+fn walk(path: &Path, any_extension: bool, files: &mut Vec<String>) -> Result<(), Error> {
+    if !path.is_dir() {
+        files.push(path.display().to_string());
+        return Ok(());
+    }
+    let mut entries = fs::read_dir(path)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<io::Result<Vec<PathBuf>>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            walk(&entry, any_extension, files)?;
+        } else if any_extension || RdfFormat::from_path(&entry).is_some() {
+            files.push(entry.display().to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Opens the file at `path`, or stdin if `path` is `-`.
+pub fn open_input(path: &str) -> io::Result<Box<dyn Read + Send>> {
+    if path == "-" {
+        Ok(Box::new(io::stdin()))
+    } else {
+        Ok(Box::new(fs::File::open(path)?))
+    }
+}
+
+/// The size in bytes of `files`, leaving out stdin and unreadable files.
+pub fn total_size(files: &[String]) -> u64 {
+    files
+        .iter()
+        .filter_map(|path| fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// How [`load_all`] loads each file.
+#[derive(Default)]
+pub struct Options {
+    pub format: Option<String>,
+    pub graph: Option<NamedNode>,
+    pub base_iri: Option<String>,
+    pub jobs: usize,
+    pub keep_going: bool,
+}
+
+/// The outcome of [`load_all`].
+///
+/// Servers don't tell how many quads a load added, so `quads_added` is only
+/// known for local stores, as the difference of the store size before and
+/// after the load.
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub loaded: usize,
+    pub quads_added: Option<usize>,
+    pub failed: Vec<(String, Error)>,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plural = |n| if n == 1 { "" } else { "s" };
+        write!(f, "loaded {} file{}", self.loaded, plural(self.loaded))?;
+        if let Some(quads) = self.quads_added {
+            write!(f, ", added {} quad{}", quads, plural(quads))?;
+        }
+        if !self.failed.is_empty() {
+            write!(f, ", {} failed:", self.failed.len())?;
+            for (path, err) in &self.failed {
+                write!(f, "\n  {}: {}", path, err)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Progress of a load, shared by the worker threads: a bar over the bytes
+/// read with the number of triples parsed and their rate as message.
+pub struct Progress {
+    bar: ProgressBar,
+    triples: AtomicU64,
+    started: Instant,
+}
+
+impl Progress {
+    /// Number of triples between two updates of the message.
+    const STEP: u64 = 10_000;
+
+    /// A progress bar on stderr for `bytes` bytes, drawn only if stderr is
+    /// a terminal.
+    pub fn new(bytes: u64) -> Self {
+        let bar = ProgressBar::new(bytes).with_style(
+            ProgressStyle::default_bar()
+                .template("{bar:40} {bytes}/{total_bytes} {elapsed_precise} {msg}"),
+        );
+        Self::with_bar(bar)
+    }
+
+    #[cfg(test)]
+    pub fn hidden() -> Self {
+        Self::with_bar(ProgressBar::hidden())
+    }
+
+    fn with_bar(bar: ProgressBar) -> Self {
+        Self {
+            bar,
+            triples: AtomicU64::new(0),
+            started: Instant::now(),
+        }
+    }
+
+    fn add_triple(&self) {
+        let before = self.triples.fetch_add(1, Ordering::Relaxed);
+        let triples = before + 1;
+        if before / Self::STEP < triples / Self::STEP {
+            let seconds = self.started.elapsed().as_secs_f64().max(0.001);
+            self.bar.set_message(&format!(
+                "{} triples, {:.0} triples/s",
+                triples,
+                triples as f64 / seconds
+            ));
+        }
+    }
+
+    pub fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+/// Counts the bytes read from a file into the progress bar.
+struct Counted<'a, R> {
+    inner: R,
+    progress: &'a Progress,
+}
+
+impl<R: Read> Read for Counted<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.progress.bar.inc(read as u64);
+        Ok(read)
+    }
+}
+
+/// Loads `files` into `target` with `options.jobs` threads, each taking the
+/// next file off a shared queue.
+///
+/// A failed file stops the other threads from taking new files unless
+/// `options.keep_going` is set. For a local store, the number of quads added
+/// is the difference of the store size before and after, which takes two
+/// full scans but stays exact however many threads insert the same quads.
+pub fn load_all(
+    target: &Target,
+    files: Vec<String>,
+    options: &Options,
+    progress: &Progress,
+) -> Result<Summary, Error> {
+    let quads_before = target.local_len();
+    let queue = Mutex::new(files.into_iter());
+    let stop = AtomicBool::new(false);
+    let summary = Mutex::new(Summary::default());
+    thread::scope(|scope| {
+        for _ in 0..options.jobs.max(1) {
+            scope.spawn(|| {
+                while !stop.load(Ordering::Relaxed) {
+                    let path = match queue.lock().unwrap().next() {
+                        Some(path) => path,
+                        None => break,
+                    };
+                    let result = load_file(target, &path, options, progress);
+                    let mut summary = summary.lock().unwrap();
+                    match result {
+                        Ok(()) => summary.loaded += 1,
+                        Err(err) => {
+                            progress
+                                .bar
+                                .println(format!("failed to load {}: {}", path, err));
+                            summary.failed.push((path, err));
+                            if !options.keep_going {
+                                stop.store(true, Ordering::Relaxed);
+                            }
+                        }
+                    }
+                }
+            });
+        }
+    });
+    let mut summary = summary.into_inner().unwrap();
+    summary.quads_added = quads_before
+        .zip(target.local_len())
+        .map(|(before, after)| after.saturating_sub(before));
+    Ok(summary)
+}
+
+fn load_file(
+    target: &Target,
+    path: &str,
+    options: &Options,
+    progress: &Progress,
+) -> Result<(), Error> {
+    let format = input_format(path, options.format.as_deref())?;
+    let reader = io::BufReader::new(Counted {
+        inner: open_input(path)?,
+        progress,
+    });
+    target.load(
+        reader,
+        format,
+        options.graph.as_ref(),
+        options.base_iri.as_deref(),
+        progress,
+    )
+}
+
+/// Loads `reader` into `store`. Triples go to `graph`, or to the default
+/// graph when it is `None`; quads keep their own graph.
+///
+/// Like the loaders of the store, this is not atomic: a parse error leaves
+/// the quads before it in the store.
+pub fn load(
+    store: &SledStore,
+    reader: impl BufRead,
+    format: RdfFormat,
+    graph: Option<&NamedNode>,
+    base_iri: Option<&str>,
+    progress: &Progress,
+) -> Result<(), Error> {
+    match format {
+        RdfFormat::Graph(format) => {
+            let graph = graph.map_or(GraphNameRef::DefaultGraph, |graph| graph.as_ref().into());
+            let mut parser = GraphParser::from_format(format);
+            if let Some(base_iri) = base_iri {
+                parser = parser.with_base_iri(base_iri)?;
+            }
+            for triple in parser.read_triples(reader)? {
+                store.insert(triple?.as_ref().in_graph(graph))?;
+                progress.add_triple();
+            }
+        }
+        RdfFormat::Dataset(_) if graph.is_some() => bail!(GRAPH_WITH_QUADS),
+        RdfFormat::Dataset(format) => {
+            let mut parser = DatasetParser::from_format(format);
+            if let Some(base_iri) = base_iri {
+                parser = parser.with_base_iri(base_iri)?;
+            }
+            for quad in parser.read_quads(reader)? {
+                store.insert(&quad?)?;
+                progress.add_triple();
+            }
+        }
+        RdfFormat::JsonLd => {
+            let quads = jsonld::parse(reader, base_iri)?;
+            if graph.is_some() && quads.iter().any(|quad| !quad.graph_name.is_default_graph()) {
+                bail!(GRAPH_WITH_QUADS);
+            }
+            let graph = graph.map_or(GraphNameRef::DefaultGraph, |graph| graph.as_ref().into());
+            for quad in &quads {
+                if quad.graph_name.is_default_graph() {
+                    store.insert(QuadRef::new(
+                        quad.subject.as_ref(),
+                        quad.predicate.as_ref(),
+                        quad.object.as_ref(),
+                        graph,
+                    ))?;
+                } else {
+                    store.insert(quad)?;
+                }
+                progress.add_triple();
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Sends `reader` to the Graph Store of the server behind `client`, into
+/// the same graphs as [`load`] would.
+pub fn load_remote(
+    client: &Client,
+    reader: impl Read,
+    format: RdfFormat,
+    graph: Option<&NamedNode>,
+    base_iri: Option<&str>,
+) -> Result<(), Error> {
+    if base_iri.is_some() {
+        bail!("--base can't be used with --endpoint");
+    }
+    match format {
+        RdfFormat::Graph(format) => {
+            let graph = graph.map_or(GraphName::DefaultGraph, |graph| graph.clone().into());
+            client.post_store(Some(&graph), format.media_type(), reader)
+        }
+        RdfFormat::Dataset(_) if graph.is_some() => bail!(GRAPH_WITH_QUADS),
+        RdfFormat::Dataset(_) => client.post_store(None, format.media_type(), reader),
+        // Loaded into a graph, documents with named graphs are rejected by
+        // the server.
+        RdfFormat::JsonLd => {
+            let graph = graph.map(|graph| GraphName::from(graph.clone()));
+            client.post_store(graph.as_ref(), format.media_type(), reader)
+        }
+    }
+}
+
+const GRAPH_WITH_QUADS: &str =
+    "--graph can't be used with quad formats, their quads have a graph already";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxigraph::io::{DatasetFormat, GraphFormat};
+    use tempfile::tempdir;
+
+    #[test]
+    fn into_named_graph_with_base() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        let graph = NamedNode::new("http://example.com/g").unwrap();
+        load(
+            &store,
+            "<s> <p> <o> .".as_bytes(),
+            RdfFormat::Graph(GraphFormat::Turtle),
+            Some(&graph),
+            Some("http://example.com/"),
+            &Progress::hidden(),
+        )
+        .unwrap();
+        assert!(store.contains_named_graph(&graph).unwrap());
+        let subject = NamedNode::new("http://example.com/s").unwrap();
+        assert_eq!(
+            store
+                .quads_for_pattern(Some(subject.as_ref().into()), None, None, None)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn graph_with_quads() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        let graph = NamedNode::new("http://example.com/g").unwrap();
+        assert!(load(
+            &store,
+            "".as_bytes(),
+            RdfFormat::Dataset(DatasetFormat::NQuads),
+            Some(&graph),
+            None,
+            &Progress::hidden(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn json_ld() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        let graph = NamedNode::new("http://example.com/g").unwrap();
+        let document = r#"{ "@id": "http://example.com/s", "http://example.com/p": 1 }"#;
+        load(
+            &store,
+            document.as_bytes(),
+            RdfFormat::JsonLd,
+            Some(&graph),
+            None,
+            &Progress::hidden(),
+        )
+        .unwrap();
+        assert_eq!(
+            store
+                .quads_for_pattern(None, None, None, Some(graph.as_ref().into()))
+                .count(),
+            1
+        );
+        let document =
+            r#"{ "@id": "http://example.com/h", "@graph": { "http://example.com/p": 1 } }"#;
+        assert!(load(
+            &store,
+            document.as_bytes(),
+            RdfFormat::JsonLd,
+            Some(&graph),
+            None,
+            &Progress::hidden(),
+        )
+        .is_err());
+        load(
+            &store,
+            document.as_bytes(),
+            RdfFormat::JsonLd,
+            None,
+            None,
+            &Progress::hidden(),
+        )
+        .unwrap();
+        assert!(store
+            .contains_named_graph(NamedNode::new("http://example.com/h").unwrap().as_ref())
+            .unwrap());
+    }
+
+    #[test]
+    fn unknown_format() {
+        assert!(input_format("data.foo", None).is_err());
+        assert!(input_format("-", None).is_err());
+        assert_eq!(
+            input_format("-", Some("ttl")).unwrap(),
+            RdfFormat::Graph(GraphFormat::Turtle)
+        );
+    }
+
+    fn write(dir: &Path, name: &str, data: &str) -> String {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, data).unwrap();
+        path.display().to_string()
+    }
+
+    #[test]
+    fn expands_directories_and_globs() {
+        let dir = tempdir().unwrap();
+        let a = write(dir.path(), "a.nt", "");
+        let b = write(dir.path(), "sub/b.ttl", "");
+        let c = write(dir.path(), "sub/c.nq", "");
+        write(dir.path(), "sub/notes.txt", "");
+        let root = dir.path().display().to_string();
+        assert_eq!(
+            expand_inputs(&[&root], false).unwrap(),
+            vec![a.clone(), b.clone(), c.clone()]
+        );
+        assert_eq!(expand_inputs(&[&root], true).unwrap().len(), 4);
+        let pattern = format!("{}/sub/*.ttl", root);
+        assert_eq!(
+            expand_inputs(&[&pattern, "-"], false).unwrap(),
+            vec![b, "-".to_string()]
+        );
+        let pattern = format!("{}/*.trig", root);
+        assert!(expand_inputs(&[&pattern], false).is_err());
+    }
+
+    #[test]
+    fn loads_in_parallel() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path().join("db")).unwrap();
+        let files = (0..8)
+            .map(|i| {
+                write(
+                    dir.path(),
+                    &format!("data/{}.nt", i),
+                    // Each file also holds the quad all of them share.
+                    &format!(
+                        "<http://e/s> <http://e/p> \"{}\" .\n<http://e/s> <http://e/p> <http://e/o> .\n",
+                        i
+                    ),
+                )
+            })
+            .collect();
+        let options = Options {
+            jobs: 3,
+            ..Options::default()
+        };
+        let summary =
+            load_all(&Target::Local(store), files, &options, &Progress::hidden()).unwrap();
+        assert_eq!(summary.loaded, 8);
+        assert_eq!(summary.quads_added, Some(9));
+        assert!(summary.failed.is_empty());
+        assert_eq!(summary.to_string(), "loaded 8 files, added 9 quads");
+    }
+
+    #[test]
+    fn keep_going() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path().join("db")).unwrap();
+        let files = vec![
+            write(dir.path(), "bad.nt", "not ntriples"),
+            write(
+                dir.path(),
+                "good.nt",
+                "<http://e/s> <http://e/p> <http://e/o> .\n",
+            ),
+        ];
+        let options = Options {
+            jobs: 1,
+            ..Options::default()
+        };
+        let target = Target::Local(store);
+        let summary = load_all(&target, files.clone(), &options, &Progress::hidden()).unwrap();
+        assert_eq!(summary.loaded, 0);
+        assert_eq!(summary.failed.len(), 1);
+
+        let options = Options {
+            jobs: 1,
+            keep_going: true,
+            ..Options::default()
+        };
+        let summary = load_all(&target, files, &options, &Progress::hidden()).unwrap();
+        assert_eq!(summary.loaded, 1);
+        assert_eq!(summary.quads_added, Some(1));
+        assert_eq!(
+            summary.failed[0].0,
+            dir.path().join("bad.nt").display().to_string()
+        );
+    }
+}
@@ -1,86 +1,201 @@
 use anyhow::Error;
-use oxigraph::io::DatasetFormat;
 use std::path;
 
 mod cli;
+mod convert;
+mod dump;
+mod format;
+mod graphs;
+mod load;
+mod query;
+mod remote;
+mod shell;
+mod stats;
+mod table;
+mod target;
+mod update;
 
 fn main() {
     match run_cli() {
         Ok(_) => {}
-        Err(err) => eprintln!("Error: {}", err),
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
     }
 }
 
 fn run_cli() -> Result<(), Error> {
-    use oxigraph::SledStore;
-    use std::fs;
+    use anyhow::bail;
     use std::io;
+    use target::Target;
 
     let matches = cli::build_cli().get_matches();
 
-    let db_path = matches.value_of("file").unwrap();
-    println!("Value of file: {:?}", db_path);
-
-    let store = SledStore::open(db_path)?;
+    if let Some(matches) = matches.subcommand_matches("convert") {
+        use oxigraph::model::NamedNode;
 
-    if let Some(matches) = matches.subcommand_matches("load") {
-        let data = path::Path::new(matches.value_of("data").unwrap());
-        if let Some(format) = dataset_format_from_path(data) {
-            store.load_dataset(io::BufReader::new(fs::File::open(data)?), format, None)?;
-        }
+        let input = matches.value_of("input").unwrap();
+        let output = matches.value_of("output").unwrap();
+        let from = convert::format(input, matches.value_of("from"), "--from", None)?;
+        let to = convert::format(
+            output,
+            matches.value_of("to"),
+            "--to",
+            Some(convert::default_output_format(from)),
+        )?;
+        let graph = matches.value_of("graph").map(NamedNode::new).transpose()?;
+        return convert::convert(
+            io::BufReader::new(load::open_input(input)?),
+            from,
+            to,
+            matches.value_of("base"),
+            graph.as_ref(),
+            dump::create_output(output)?,
+        );
     }
-    Ok(())
-}
 
-fn dataset_format_from_path(path: &path::Path) -> Option<DatasetFormat> {
-    match path.extension() {
-        None => None,
-        Some(ext) => {
-            if ext == DatasetFormat::TriG.file_extension() {
-                Some(DatasetFormat::TriG)
-            } else if ext == DatasetFormat::NQuads.file_extension() {
-                Some(DatasetFormat::NQuads)
-            } else {
-                None
-            }
-        }
-    }
-}
+    let file = matches.value_of("file");
+    let target = Target::open(file, matches.value_of("endpoint"))?;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    if let Some(matches) = matches.subcommand_matches("load") {
+        use oxigraph::model::NamedNode;
 
-    mod dataset_format {
-        use super::*;
+        let inputs = matches.values_of("data").unwrap().collect::<Vec<_>>();
+        let options = load::Options {
+            format: matches.value_of("format").map(String::from),
+            graph: matches.value_of("graph").map(NamedNode::new).transpose()?,
+            base_iri: matches.value_of("base").map(String::from),
+            jobs: matches.value_of("jobs").unwrap().parse()?,
+            keep_going: matches.is_present("keep-going"),
+        };
+        let files = load::expand_inputs(&inputs, options.format.is_some())?;
+        let progress = load::Progress::new(load::total_size(&files));
+        let summary = load::load_all(&target, files, &options, &progress)?;
+        progress.finish();
+        println!("{}", summary);
+        if !summary.failed.is_empty() {
+            bail!("{} of the files failed to load", summary.failed.len());
+        }
+    } else if let Some(matches) = matches.subcommand_matches("query") {
+        let text = query::read_query(matches.value_of("query"), matches.value_of("query-file"))?;
+        let format = matches.value_of("format").unwrap().parse()?;
+        let stdout = io::stdout();
+        let mut out = io::BufWriter::new(stdout.lock());
+        target.query(&text, format, &mut out)?;
+        io::Write::flush(&mut out)?;
+    } else if let Some(matches) = matches.subcommand_matches("update") {
+        use oxigraph::model::NamedNode;
 
-        #[test]
-        fn no_extension() {
-            assert_eq!(dataset_format_from_path(path::Path::new("test/test")), None);
+        let texts = match (matches.value_of("update"), matches.values_of("update-file")) {
+            (Some(text), _) => vec![text.to_string()],
+            (None, Some(paths)) => paths.map(read_input).collect::<Result<_, _>>()?,
+            (None, None) => vec![read_input("-")?],
+        };
+        let iris = |name| -> Result<Vec<NamedNode>, Error> {
+            Ok(matches
+                .values_of(name)
+                .into_iter()
+                .flatten()
+                .map(NamedNode::new)
+                .collect::<Result<_, _>>()?)
+        };
+        let using_graphs = iris("using-graph-uri")?;
+        let using_named_graphs = iris("using-named-graph-uri")?;
+        match target.update(&texts, &using_graphs, &using_named_graphs)? {
+            Some(changes) => println!("{}", changes),
+            None => println!("done"),
         }
+    } else if let Some(matches) = matches.subcommand_matches("dump") {
+        use oxigraph::model::{GraphName, NamedNode};
 
-        #[test]
-        fn unknown_extension() {
-            assert_eq!(
-                dataset_format_from_path(path::Path::new("test/test.foo")),
-                None
-            );
+        let mut graphs = Vec::new();
+        if matches.is_present("default-graph") {
+            graphs.push(GraphName::DefaultGraph);
+        }
+        for iri in matches.values_of("graph").into_iter().flatten() {
+            graphs.push(NamedNode::new(iri)?.into());
         }
+        let output = matches.value_of("output");
+        let split = matches.value_of("split");
+        let format = dump::output_format(output, matches.value_of("format"), split.is_some())?;
+        let context = dump::read_context(matches.value_of("context"), format)?;
+        match (split, &target) {
+            (Some(dir), Target::Local(store)) => {
+                let paths = dump::split(store, &graphs, format, path::Path::new(dir))?;
+                eprintln!("wrote {} files to {}", paths.len(), dir);
+            }
+            (Some(_), Target::Remote(_)) => bail!("--split can't be used with --endpoint"),
+            (None, _) => {
+                let out = dump::create_output(output.unwrap_or("-"))?;
+                target.dump(&graphs, format, context.as_ref(), out)?
+            }
+        }
+    } else if let Some(matches) = matches.subcommand_matches("stats") {
+        use knowgraf::Stats;
+        use std::io::Write;
 
-        #[test]
-        fn trig() {
-            assert_eq!(
-                dataset_format_from_path(path::Path::new("test/test.trig")),
-                Some(DatasetFormat::TriG)
-            );
+        let top = matches.value_of("top").unwrap().parse()?;
+        let stats = match &target {
+            Target::Local(store) => Stats::compute(store, file.map(path::Path::new), top)?,
+            Target::Remote(client) => client.stats(top)?,
+        };
+        let stdout = io::stdout();
+        let mut out = io::BufWriter::new(stdout.lock());
+        if matches.is_present("json") {
+            serde_json::to_writer_pretty(&mut out, &stats)?;
+            writeln!(out)?;
+        } else {
+            stats::write(&stats, &mut out)?;
         }
+        out.flush()?;
+    } else if let Some(matches) = matches.subcommand_matches("graphs") {
+        use std::io::Write;
 
-        #[test]
-        fn nquads() {
-            assert_eq!(
-                dataset_format_from_path(path::Path::new("test/test.nq")),
-                Some(DatasetFormat::NQuads)
-            );
+        match matches.subcommand() {
+            ("rm", Some(matches)) => {
+                for name in matches.values_of("graph").unwrap() {
+                    target.remove_graph(&graphs::parse_graph(name)?)?;
+                }
+            }
+            (action @ ("copy" | "move" | "rename"), Some(matches)) => {
+                let from = graphs::parse_graph(matches.value_of("from").unwrap())?;
+                let to = graphs::parse_graph(matches.value_of("to").unwrap())?;
+                match action {
+                    "copy" => target.copy_graph(&from, &to)?,
+                    "move" => target.move_graph(&from, &to)?,
+                    _ => target.rename_graph(&from, &to)?,
+                }
+            }
+            _ => {
+                let stdout = io::stdout();
+                let mut out = io::BufWriter::new(stdout.lock());
+                graphs::write(&target.graphs()?, &mut out)?;
+                out.flush()?;
+            }
         }
+    } else if let Some(matches) = matches.subcommand_matches("shell") {
+        let history = match matches.value_of("history") {
+            Some(path) => Some(path::PathBuf::from(path)),
+            None => shell::default_history(),
+        };
+        match &target {
+            Target::Local(store) => shell::run(store, history.as_deref())?,
+            Target::Remote(_) => bail!("the shell can't be used with --endpoint yet"),
+        }
+    }
+    Ok(())
+}
+
+/// Reads the file at `path`, or stdin if `path` is `-`.
+fn read_input(path: &str) -> std::io::Result<String> {
+    use std::io::Read;
+
+    if path == "-" {
+        let mut input = String::new();
+        std::io::stdin().read_to_string(&mut input)?;
+        Ok(input)
+    } else {
+        std::fs::read_to_string(path)
     }
 }
@@ -0,0 +1,299 @@
+use crate::remote::Client;
+use crate::table::Table;
+use anyhow::{anyhow, bail, Error};
+use knowgraf::jsonld::{self, JsonLdWriter};
+use oxigraph::io::{GraphFormat, GraphParser};
+use oxigraph::model::Triple;
+use oxigraph::sparql::{Query, QueryResults, QueryResultsFormat};
+use oxigraph::SledStore;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+/// The ways `kg-cli query` can print results.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+    Tsv,
+    Xml,
+    Turtle,
+    NTriples,
+    JsonLd,
+}
+
+impl OutputFormat {
+    pub const NAMES: &'static [&'static str] = &[
+        "table", "json", "csv", "tsv", "xml", "turtle", "ntriples", "jsonld",
+    ];
+
+    fn results_format(self) -> Option<QueryResultsFormat> {
+        match self {
+            OutputFormat::Json => Some(QueryResultsFormat::Json),
+            OutputFormat::Csv => Some(QueryResultsFormat::Csv),
+            OutputFormat::Tsv => Some(QueryResultsFormat::Tsv),
+            OutputFormat::Xml => Some(QueryResultsFormat::Xml),
+            _ => None,
+        }
+    }
+
+    fn graph_format(self) -> Option<GraphFormat> {
+        match self {
+            OutputFormat::Turtle => Some(GraphFormat::Turtle),
+            OutputFormat::NTriples => Some(GraphFormat::NTriples),
+            OutputFormat::Xml => Some(GraphFormat::RdfXml),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Error> {
+        match name {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            "xml" => Ok(OutputFormat::Xml),
+            "turtle" => Ok(OutputFormat::Turtle),
+            "ntriples" => Ok(OutputFormat::NTriples),
+            "jsonld" => Ok(OutputFormat::JsonLd),
+            _ => Err(anyhow!("unknown output format: {}", name)),
+        }
+    }
+}
+
+/// Reads the query from `query`, else from the file `query_file` (`-` for
+/// stdin), else from stdin.
+pub fn read_query(query: Option<&str>, query_file: Option<&str>) -> Result<String, Error> {
+    match (query, query_file) {
+        (Some(query), _) => Ok(query.to_string()),
+        (None, path) => Ok(crate::read_input(path.unwrap_or("-"))?),
+    }
+}
+
+/// Evaluates `query` against `store` and prints the results to `out`.
+pub fn run(
+    store: &SledStore,
+    query: &str,
+    format: OutputFormat,
+    out: &mut impl Write,
+) -> Result<(), Error> {
+    write_results(store.query(query)?, format, out)
+}
+
+/// Sends `query` to the server behind `client` and prints the results to
+/// `out`.
+///
+/// The server serializes the results itself, except for tables which are
+/// built from JSON results or N-Triples.
+pub fn run_remote(
+    client: &Client,
+    query: &str,
+    format: OutputFormat,
+    out: &mut impl Write,
+) -> Result<(), Error> {
+    if format != OutputFormat::Table {
+        let accept = format
+            .results_format()
+            .map(QueryResultsFormat::media_type)
+            .into_iter()
+            .chain(format.graph_format().map(GraphFormat::media_type))
+            .chain(Some(jsonld::MEDIA_TYPE).filter(|_| format == OutputFormat::JsonLd))
+            .collect::<Vec<_>>()
+            .join(", ");
+        io::copy(&mut client.query(query, &accept)?, out)?;
+        return Ok(());
+    }
+    match Query::parse(query, None)? {
+        Query::Construct { .. } | Query::Describe { .. } => {
+            let body = client.query(query, GraphFormat::NTriples.media_type())?;
+            let triples = GraphParser::from_format(GraphFormat::NTriples)
+                .read_triples(io::BufReader::new(body))?;
+            write_triples_table(triples, out)
+        }
+        Query::Select { .. } | Query::Ask { .. } => {
+            let body = client.query(query, QueryResultsFormat::Json.media_type())?;
+            let results = QueryResults::read(io::BufReader::new(body), QueryResultsFormat::Json)?;
+            write_results(results, format, out)
+        }
+    }
+}
+
+/// Prints `results` to `out` in `format`.
+fn write_results(
+    results: QueryResults,
+    format: OutputFormat,
+    out: &mut impl Write,
+) -> Result<(), Error> {
+    match (results, format) {
+        (QueryResults::Graph(triples), OutputFormat::Table) => write_triples_table(triples, out)?,
+        (QueryResults::Graph(triples), OutputFormat::JsonLd) => {
+            let mut writer = JsonLdWriter::new(out, None)?;
+            for triple in triples {
+                writer.write_triple(&triple?)?;
+            }
+            writer.finish()?;
+        }
+        (QueryResults::Graph(triples), format) => {
+            let graph_format = format
+                .graph_format()
+                .ok_or_else(|| anyhow!("{:?} output is not available for graph results", format))?;
+            QueryResults::Graph(triples).write_graph(out, graph_format)?;
+        }
+        (QueryResults::Solutions(solutions), OutputFormat::Table) => {
+            let variables = solutions.variables().to_vec();
+            let mut table = Table::new(variables.iter().map(|v| v.as_str().to_string()).collect());
+            for solution in solutions {
+                let solution = solution?;
+                table.push(
+                    variables
+                        .iter()
+                        .map(|v| solution.get(v).map_or(String::new(), |t| t.to_string()))
+                        .collect(),
+                );
+            }
+            write_table(&table, "row", out)?;
+        }
+        (QueryResults::Boolean(value), OutputFormat::Table) => writeln!(out, "{}", value)?,
+        (results, format) => match format.results_format() {
+            Some(results_format) => results.write(out, results_format)?,
+            None => bail!("{:?} output is only available for graph results", format),
+        },
+    }
+    Ok(())
+}
+
+fn write_triples_table<E>(
+    triples: impl Iterator<Item = Result<Triple, E>>,
+    out: &mut impl Write,
+) -> Result<(), Error>
+where
+    Error: From<E>,
+{
+    let mut table = Table::new(vec![
+        "subject".to_string(),
+        "predicate".to_string(),
+        "object".to_string(),
+    ]);
+    for triple in triples {
+        let triple = triple?;
+        table.push(vec![
+            triple.subject.to_string(),
+            triple.predicate.to_string(),
+            triple.object.to_string(),
+        ]);
+    }
+    write_table(&table, "triple", out)?;
+    Ok(())
+}
+
+fn write_table(table: &Table, noun: &str, out: &mut impl Write) -> io::Result<()> {
+    table.write(out)?;
+    let plural = if table.len() == 1 { "" } else { "s" };
+    writeln!(out, "{} {}{}", table.len(), noun, plural)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxigraph::model::GraphNameRef;
+    use tempfile::tempdir;
+
+    fn store() -> (tempfile::TempDir, SledStore) {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        store
+            .load_graph(
+                "<http://example.com/s> <http://example.com/p> \"o\" .\n".as_bytes(),
+                GraphFormat::NTriples,
+                GraphNameRef::DefaultGraph,
+                None,
+            )
+            .unwrap();
+        (dir, store)
+    }
+
+    fn output(query: &str, format: OutputFormat) -> Result<String, Error> {
+        let (_dir, store) = store();
+        let mut out = Vec::new();
+        run(&store, query, format, &mut out)?;
+        Ok(String::from_utf8(out)?)
+    }
+
+    #[test]
+    fn select_as_table() {
+        assert_eq!(
+            output("SELECT ?s ?o WHERE { ?s ?p ?o }", OutputFormat::Table).unwrap(),
+            "\
++------------------------+-----+
+| s                      | o   |
++------------------------+-----+
+| <http://example.com/s> | \"o\" |
++------------------------+-----+
+1 row
+"
+        );
+    }
+
+    #[test]
+    fn select_as_csv() {
+        assert_eq!(
+            output("SELECT ?o WHERE { ?s ?p ?o }", OutputFormat::Csv).unwrap(),
+            "o\r\no"
+        );
+    }
+
+    #[test]
+    fn ask_as_table() {
+        assert_eq!(
+            output("ASK { ?s ?p ?o }", OutputFormat::Table).unwrap(),
+            "true\n"
+        );
+    }
+
+    #[test]
+    fn construct_as_ntriples() {
+        assert_eq!(
+            output(
+                "CONSTRUCT { ?s ?p ?o } WHERE { ?s ?p ?o }",
+                OutputFormat::NTriples
+            )
+            .unwrap(),
+            "<http://example.com/s> <http://example.com/p> \"o\" .\n"
+        );
+    }
+
+    #[test]
+    fn construct_as_json_ld() {
+        assert_eq!(
+            output(
+                "CONSTRUCT { ?s ?p ?o } WHERE { ?s ?p ?o }",
+                OutputFormat::JsonLd
+            )
+            .unwrap(),
+            "[\n{\"@id\":\"http://example.com/s\",\"http://example.com/p\":[{\"@value\":\"o\"}]}\n]\n"
+        );
+    }
+
+    #[test]
+    fn mismatched_format() {
+        assert!(output("SELECT * WHERE { ?s ?p ?o }", OutputFormat::Turtle).is_err());
+        assert!(output("SELECT * WHERE { ?s ?p ?o }", OutputFormat::JsonLd).is_err());
+        assert!(output(
+            "CONSTRUCT { ?s ?p ?o } WHERE { ?s ?p ?o }",
+            OutputFormat::Csv
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn format_names() {
+        for name in OutputFormat::NAMES {
+            assert!(name.parse::<OutputFormat>().is_ok());
+        }
+        assert!("yaml".parse::<OutputFormat>().is_err());
+    }
+}
@@ -0,0 +1,204 @@
+use anyhow::{anyhow, bail, Error};
+use knowgraf::{GraphCount, Stats};
+use oxigraph::model::{GraphName, NamedNode};
+use std::io::Read;
+
+/// A client of the SPARQL 1.1 Protocol and Graph Store endpoints of a
+/// knowgraf server.
+pub struct Client {
+    endpoint: String,
+    agent: ureq::Agent,
+}
+
+impl Client {
+    /// A client of the server at `endpoint`, e.g. `http://localhost:8080`.
+    pub fn new(endpoint: &str) -> Self {
+        Client {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            agent: ureq::agent(),
+        }
+    }
+
+    /// Sends `query` to `/query` and returns the body of the results.
+    pub fn query(&self, query: &str, accept: &str) -> Result<Box<dyn Read + Send>, Error> {
+        let response = self
+            .agent
+            .post(&self.url("query"))
+            .set("Content-Type", "application/sparql-query")
+            .set("Accept", accept)
+            .send_string(query);
+        Ok(Box::new(check(response)?.into_reader()))
+    }
+
+    /// Sends `update` to `/update`, with `using_graphs` and
+    /// `using_named_graphs` as its dataset.
+    pub fn update(
+        &self,
+        update: &str,
+        using_graphs: &[NamedNode],
+        using_named_graphs: &[NamedNode],
+    ) -> Result<(), Error> {
+        let mut request = self
+            .agent
+            .post(&self.url("update"))
+            .set("Content-Type", "application/sparql-update");
+        for graph in using_graphs {
+            request = request.query("using-graph-uri", graph.as_str());
+        }
+        for graph in using_named_graphs {
+            request = request.query("using-named-graph-uri", graph.as_str());
+        }
+        check(request.send_string(update))?;
+        Ok(())
+    }
+
+    /// Fetches the statistics of the dataset from `/stats`.
+    pub fn stats(&self, top: usize) -> Result<Stats, Error> {
+        let response = self
+            .agent
+            .get(&self.url("stats"))
+            .query("top", &top.to_string())
+            .call();
+        Ok(serde_json::from_reader(check(response)?.into_reader())?)
+    }
+
+    /// Fetches the named graphs and their quad counts from `/graphs`.
+    pub fn graphs(&self) -> Result<Vec<GraphCount>, Error> {
+        let response = self
+            .agent
+            .get(&self.url("graphs"))
+            .set("Accept", "application/json")
+            .call();
+        Ok(serde_json::from_reader(check(response)?.into_reader())?)
+    }
+
+    /// Adds `body` to `graph` with a Graph Store POST, or to the dataset if
+    /// `graph` is `None`.
+    pub fn post_store(
+        &self,
+        graph: Option<&GraphName>,
+        content_type: &str,
+        body: impl Read,
+    ) -> Result<(), Error> {
+        let request = self.store_request("POST", graph)?;
+        check(request.set("Content-Type", content_type).send(body))?;
+        Ok(())
+    }
+
+    /// Fetches `graph` with a Graph Store GET, or the dataset if `graph` is
+    /// `None`. JSON-LD is compacted against the JSON `context` if given.
+    pub fn get_store(
+        &self,
+        graph: Option<&GraphName>,
+        accept: &str,
+        context: Option<&str>,
+    ) -> Result<Box<dyn Read + Send>, Error> {
+        let mut request = self.store_request("GET", graph)?;
+        if let Some(context) = context {
+            request = request.query("context", context);
+        }
+        Ok(Box::new(
+            check(request.set("Accept", accept).call())?.into_reader(),
+        ))
+    }
+
+    fn store_request(
+        &self,
+        method: &str,
+        graph: Option<&GraphName>,
+    ) -> Result<ureq::Request, Error> {
+        let request = self.agent.request(method, &self.url("store"));
+        Ok(match graph {
+            None => request,
+            Some(GraphName::DefaultGraph) => request.query("default", ""),
+            Some(GraphName::NamedNode(graph)) => request.query("graph", graph.as_str()),
+            Some(GraphName::BlankNode(graph)) => {
+                bail!(
+                    "the blank node graph {} can't be addressed on a server",
+                    graph
+                )
+            }
+        })
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.endpoint, path)
+    }
+}
+
+/// Turns an error status into an error carrying the server's explanation,
+/// the `detail` of a problem document or else the body.
+fn check(response: Result<ureq::Response, ureq::Error>) -> Result<ureq::Response, Error> {
+    match response {
+        Ok(response) => Ok(response),
+        Err(ureq::Error::Status(status, response)) => {
+            let is_problem = response.content_type() == "application/problem+json";
+            let body = response.into_string()?;
+            let detail = if is_problem {
+                serde_json::from_str::<serde_json::Value>(&body)
+                    .ok()
+                    .and_then(|problem| problem["detail"].as_str().map(String::from))
+            } else {
+                None
+            };
+            Err(anyhow!(
+                "the server answered {}: {}",
+                status,
+                detail.unwrap_or(body).trim()
+            ))
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use actix_web::{web, App, HttpServer};
+    use knowgraf::{config_app, AppState};
+    use oxigraph::SledStore;
+    use std::sync::mpsc;
+    use std::thread;
+
+    /// Serves `store` from a server running in the background and returns
+    /// its URL.
+    pub(crate) fn serve(store: SledStore) -> String {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let system = actix_rt::System::new("kg-cli-test");
+            let state = web::Data::new(AppState::new(store));
+            let server = HttpServer::new(move || App::new().configure(config_app(state.clone())))
+                .workers(1)
+                .bind("127.0.0.1:0")
+                .unwrap();
+            sender.send(server.addrs()[0]).unwrap();
+            server.run();
+            system.run().unwrap();
+        });
+        format!("http://{}", receiver.recv().unwrap())
+    }
+
+    #[test]
+    fn errors_carry_the_detail() {
+        let dir = tempfile::tempdir().unwrap();
+        let client = Client::new(&serve(SledStore::open(dir.path()).unwrap()));
+        let err = client
+            .update("INSERT DATA { <http://e/s> <http://e/p> }", &[], &[])
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("the server answered 400: query parse error: error at 1:41"));
+    }
+
+    #[test]
+    fn stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        store
+            .update("INSERT DATA { <http://e/s> <http://e/p> 1, 2 }")
+            .unwrap();
+        let stats = Client::new(&serve(store)).stats(5).unwrap();
+        assert_eq!(stats.quads, 2);
+        assert_eq!(stats.top_predicates[0].term, "http://e/p");
+    }
+}
@@ -0,0 +1,462 @@
+use crate::load;
+use crate::query::{self, OutputFormat};
+use crate::target::Target;
+use crate::update;
+use anyhow::{anyhow, bail, Error};
+use oxigraph::model::{GraphNameRef, NamedNode, NamedOrBlankNode};
+use oxigraph::SledStore;
+use rustyline::error::ReadlineError;
+use rustyline::Editor;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::Path;
+
+const HELP: &str = "\
+Enter SPARQL queries and updates, ending them with ';' or an empty line.
+PREFIX and BASE declarations are remembered for the rest of the session.
+
+.count [IRI]        count the quads in the store, or in the named graph IRI
+.format [NAME]      show or set the output format of queries
+.graphs             list the named graphs
+.help               show this help
+.load PATH [IRI]    load files, directories or globs, into the graph IRI if given
+.prefixes           list the remembered prefixes
+.quit               leave the shell";
+
+const COMMANDS: &[&str] = &[
+    ".count",
+    ".exit",
+    ".format",
+    ".graphs",
+    ".help",
+    ".load",
+    ".prefixes",
+    ".quit",
+];
+
+/// Keywords an update operation can start with.
+const UPDATE_KEYWORDS: &[&str] = &[
+    "INSERT", "DELETE", "LOAD", "CLEAR", "CREATE", "DROP", "COPY", "MOVE", "ADD", "WITH",
+];
+
+/// The state of a shell session: the statement being typed and what is
+/// remembered between statements.
+pub struct Session<'a> {
+    store: &'a SledStore,
+    format: OutputFormat,
+    base_iri: Option<String>,
+    prefixes: BTreeMap<String, String>,
+    buffer: String,
+}
+
+impl<'a> Session<'a> {
+    pub fn new(store: &'a SledStore) -> Self {
+        Session {
+            store,
+            format: OutputFormat::Table,
+            base_iri: None,
+            prefixes: BTreeMap::new(),
+            buffer: String::new(),
+        }
+    }
+
+    /// Whether a statement has been started but not ended yet.
+    pub fn is_pending(&self) -> bool {
+        !self.buffer.trim().is_empty()
+    }
+
+    /// Drops the statement being typed.
+    pub fn cancel(&mut self) {
+        self.buffer.clear();
+    }
+
+    /// Feeds a line of input, running the statement or dot-command it
+    /// completes. Returns `false` once the session should end.
+    pub fn line(&mut self, line: &str, out: &mut impl Write) -> Result<bool, Error> {
+        if !self.is_pending() && line.trim_start().starts_with('.') {
+            return self.command(line.trim(), out);
+        }
+        self.buffer.push_str(line);
+        self.buffer.push('\n');
+        let (declarations, rest) = prologue(&self.buffer);
+        let statement = if rest.trim().is_empty() && !declarations.is_empty() {
+            // Only declarations so far: remember them right away.
+            Some(String::new())
+        } else if line.trim().is_empty() || is_complete(&self.buffer) {
+            Some(rest.trim().trim_end_matches(';').to_string())
+        } else {
+            None
+        };
+        if let Some(statement) = statement {
+            self.buffer.clear();
+            for declaration in declarations {
+                match declaration {
+                    Declaration::Base(iri) => self.base_iri = Some(iri),
+                    Declaration::Prefix(name, iri) => {
+                        self.prefixes.insert(name, iri);
+                    }
+                }
+            }
+            if !statement.is_empty() {
+                self.run(&statement, out)?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Runs `statement` with the remembered declarations, through the same
+    /// paths as `kg-cli query` and `kg-cli update`.
+    fn run(&self, statement: &str, out: &mut impl Write) -> Result<(), Error> {
+        let mut text = String::new();
+        if let Some(iri) = &self.base_iri {
+            text.push_str(&format!("BASE <{}>\n", iri));
+        }
+        for (name, iri) in &self.prefixes {
+            text.push_str(&format!("PREFIX {}: <{}>\n", name, iri));
+        }
+        text.push_str(statement);
+        if is_update(statement) {
+            let update = update::parse(&text, &[], &[])?;
+            match update::run(self.store, vec![update])? {
+                Some(changes) => writeln!(out, "{}", changes)?,
+                None => writeln!(out, "done")?,
+            }
+        } else {
+            query::run(self.store, &text, self.format, out)?;
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, line: &str, out: &mut impl Write) -> Result<bool, Error> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default();
+        let args = words.collect::<Vec<_>>();
+        match (command, args.as_slice()) {
+            (".quit", []) | (".exit", []) => return Ok(false),
+            (".help", []) => writeln!(out, "{}", HELP)?,
+            (".format", []) => writeln!(out, "{}", format_name(self.format))?,
+            (".format", [name]) => self.format = name.parse()?,
+            (".prefixes", []) => {
+                for (name, iri) in &self.prefixes {
+                    writeln!(out, "PREFIX {}: <{}>", name, iri)?;
+                }
+            }
+            (".graphs", []) => {
+                for graph in self.store.named_graphs() {
+                    match graph? {
+                        NamedOrBlankNode::NamedNode(node) => writeln!(out, "{}", node)?,
+                        NamedOrBlankNode::BlankNode(node) => writeln!(out, "{}", node)?,
+                    }
+                }
+            }
+            (".count", []) => writeln!(out, "{}", self.store.len())?,
+            (".count", [iri]) => {
+                let graph = named_node(iri)?;
+                let count = self
+                    .store
+                    .quads_for_pattern(None, None, None, Some(GraphNameRef::from(&graph)))
+                    .count();
+                writeln!(out, "{}", count)?;
+            }
+            (".load", [path]) | (".load", [path, _]) => {
+                let options = load::Options {
+                    graph: args.get(1).map(|iri| named_node(iri)).transpose()?,
+                    base_iri: self.base_iri.clone(),
+                    jobs: 1,
+                    ..load::Options::default()
+                };
+                let files = load::expand_inputs(&[path], false)?;
+                let progress = load::Progress::new(load::total_size(&files));
+                let target = Target::Local(self.store.clone());
+                let summary = load::load_all(&target, files, &options, &progress)?;
+                progress.finish();
+                writeln!(out, "{}", summary)?;
+            }
+            _ if COMMANDS.contains(&command) => {
+                bail!("wrong arguments to {}, see .help", command)
+            }
+            _ => bail!("unknown command {}, see .help", command),
+        }
+        Ok(true)
+    }
+}
+
+/// Runs the shell on the terminal, keeping its history in `history` if
+/// given.
+pub fn run(store: &SledStore, history: Option<&Path>) -> Result<(), Error> {
+    let mut editor = Editor::<()>::new();
+    if let Some(path) = history {
+        // A missing history file just means a first session.
+        let _ = editor.load_history(path);
+    }
+    let mut session = Session::new(store);
+    loop {
+        let prompt = if session.is_pending() {
+            "  -> "
+        } else {
+            "kg> "
+        };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => {
+                session.cancel();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err.into()),
+        };
+        if !line.trim().is_empty() {
+            editor.add_history_entry(line.as_str());
+        }
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        match session.line(&line, &mut out) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(err) => eprintln!("Error: {}", err),
+        }
+        out.flush()?;
+    }
+    if let Some(path) = history {
+        editor.save_history(path)?;
+    }
+    Ok(())
+}
+
+/// The default history file, `~/.kg-cli_history`.
+pub fn default_history() -> Option<std::path::PathBuf> {
+    std::env::var_os("HOME").map(|home| Path::new(&home).join(".kg-cli_history"))
+}
+
+fn format_name(format: OutputFormat) -> &'static str {
+    OutputFormat::NAMES
+        .iter()
+        .find(|name| name.parse::<OutputFormat>().ok() == Some(format))
+        .unwrap()
+}
+
+/// Parses an IRI given to a dot-command, with or without angle brackets.
+fn named_node(iri: &str) -> Result<NamedNode, Error> {
+    let iri = iri.strip_prefix('<').unwrap_or(iri);
+    let iri = iri.strip_suffix('>').unwrap_or(iri);
+    NamedNode::new(iri).map_err(|err| anyhow!("invalid IRI {}: {}", iri, err))
+}
+
+#[derive(Debug, PartialEq)]
+enum Declaration {
+    Base(String),
+    Prefix(String, String),
+}
+
+/// Splits the complete BASE and PREFIX declarations off the start of `text`.
+fn prologue(text: &str) -> (Vec<Declaration>, &str) {
+    let mut declarations = Vec::new();
+    let mut rest = text;
+    loop {
+        let start = skip_blank(rest);
+        let (keyword, after) = split_word(start);
+        let declaration = if keyword.eq_ignore_ascii_case("BASE") {
+            iri(skip_blank(after)).map(|(iri, after)| (Declaration::Base(iri.into()), after))
+        } else if keyword.eq_ignore_ascii_case("PREFIX") {
+            let (name, after) = split_word(skip_blank(after));
+            match (name.strip_suffix(':'), iri(skip_blank(after))) {
+                (Some(name), Some((iri, after))) => {
+                    Some((Declaration::Prefix(name.into(), iri.into()), after))
+                }
+                _ => None,
+            }
+        } else {
+            None
+        };
+        match declaration {
+            Some((declaration, after)) => {
+                declarations.push(declaration);
+                rest = after;
+            }
+            None => return (declarations, rest),
+        }
+    }
+}
+
+/// Skips whitespace and comments.
+fn skip_blank(mut text: &str) -> &str {
+    loop {
+        text = text.trim_start();
+        match text.strip_prefix('#') {
+            Some(comment) => text = comment.find('\n').map_or("", |end| &comment[end..]),
+            None => return text,
+        }
+    }
+}
+
+fn split_word(text: &str) -> (&str, &str) {
+    let end = text
+        .find(|c: char| c.is_whitespace() || c == '<')
+        .unwrap_or(text.len());
+    text.split_at(end)
+}
+
+fn iri(text: &str) -> Option<(&str, &str)> {
+    let text = text.strip_prefix('<')?;
+    let end = text.find('>')?;
+    Some((&text[..end], &text[end + 1..]))
+}
+
+/// Whether `statement`, without its prologue, is an update.
+fn is_update(statement: &str) -> bool {
+    let (keyword, _) = split_word(skip_blank(statement));
+    UPDATE_KEYWORDS
+        .iter()
+        .any(|update| keyword.eq_ignore_ascii_case(update))
+}
+
+/// Whether `text` ends with a `;` outside of braces, strings, IRIs and
+/// comments.
+fn is_complete(text: &str) -> bool {
+    let mut depth = 0;
+    let mut last = None;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '#' => {
+                while matches!(chars.peek(), Some(c) if *c != '\n') {
+                    chars.next();
+                }
+                continue;
+            }
+            '"' | '\'' => {
+                while let Some(next) = chars.next() {
+                    match next {
+                        '\\' => {
+                            chars.next();
+                        }
+                        next if next == c => break,
+                        _ => {}
+                    }
+                }
+            }
+            '<' => {
+                // An IRI, or a less than if followed by a character no IRI has.
+                while matches!(chars.peek(), Some(c) if !c.is_whitespace() && !"<\"{}|^`\\".contains(*c))
+                {
+                    if chars.next() == Some('>') {
+                        break;
+                    }
+                }
+            }
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            c if c.is_whitespace() => continue,
+            _ => {}
+        }
+        last = Some(c);
+    }
+    depth <= 0 && last == Some(';')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn input(session: &mut Session<'_>, lines: &[&str]) -> String {
+        let mut out = Vec::new();
+        for line in lines {
+            session.line(line, &mut out).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn statements_end_with_semicolon_or_empty_line() {
+        assert!(is_complete("SELECT * WHERE { ?s ?p ?o };"));
+        assert!(!is_complete("SELECT * WHERE {\n ?s ?p ?o ;"));
+        assert!(!is_complete("SELECT * WHERE { ?s ?p \"};\""));
+        assert!(!is_complete("SELECT * WHERE { ?s ?p ?o } # done;"));
+        assert!(!is_complete("SELECT * WHERE { <http://e/{x}> ?p ?o }"));
+        assert!(is_complete("SELECT * WHERE { ?s ?p ?o FILTER(?o<3) };"));
+    }
+
+    #[test]
+    fn splits_prologue() {
+        let (declarations, rest) =
+            prologue("BASE <http://e/>\n# comment\nprefix ex: <http://e/ns#> SELECT");
+        assert_eq!(
+            declarations,
+            vec![
+                Declaration::Base("http://e/".into()),
+                Declaration::Prefix("ex".into(), "http://e/ns#".into())
+            ]
+        );
+        assert_eq!(rest, " SELECT");
+        assert!(prologue("PREFIX ex:").0.is_empty());
+        assert!(is_update(" insert data {}"));
+        assert!(!is_update("SELECT * {}"));
+    }
+
+    #[test]
+    fn remembers_prefixes() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        let mut session = Session::new(&store);
+        let out = input(
+            &mut session,
+            &[
+                "PREFIX ex: <http://e/>",
+                "INSERT DATA {",
+                "  ex:s ex:p 1 ;",
+                "       ex:p 2 }",
+                "",
+                ".format csv",
+                "SELECT ?o WHERE { ex:s ex:p ?o } ORDER BY ?o;",
+            ],
+        );
+        assert_eq!(out, "inserted 2 quads, deleted 0 quads\no\r\n1\r\n2");
+        assert!(!session.is_pending());
+        assert_eq!(
+            input(&mut session, &[".prefixes"]),
+            "PREFIX ex: <http://e/>\n"
+        );
+    }
+
+    #[test]
+    fn reports_update_changes() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        let mut session = Session::new(&store);
+        let out = input(
+            &mut session,
+            &[
+                "INSERT DATA { <http://e/s> <http://e/p> 1, 2 };",
+                "DELETE { ?s ?p 1 } INSERT { ?s ?p 3 } WHERE { ?s ?p 1 };",
+                "LOAD SILENT <http://localhost:1/missing.nt>;",
+            ],
+        );
+        assert_eq!(
+            out,
+            "inserted 2 quads, deleted 0 quads\n\
+             inserted 1 quad, deleted 1 quad\n\
+             done\n"
+        );
+    }
+
+    #[test]
+    fn dot_commands() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path().join("db")).unwrap();
+        let data = dir.path().join("data.nt");
+        std::fs::write(&data, "<http://e/s> <http://e/p> <http://e/o> .\n").unwrap();
+        let mut session = Session::new(&store);
+        let load = format!(".load {} <http://e/g>", data.display());
+        let out = input(
+            &mut session,
+            &[&load, ".graphs", ".count", ".count http://e/g"],
+        );
+        assert_eq!(out, "loaded 1 file, added 1 quad\n<http://e/g>\n1\n1\n");
+        assert_eq!(input(&mut session, &[".format"]), "table\n");
+        let mut out = Vec::new();
+        assert!(session.line(".format yaml", &mut out).is_err());
+        assert!(session.line(".nope", &mut out).is_err());
+        assert!(!session.line(".quit", &mut out).unwrap());
+    }
+}
@@ -0,0 +1,160 @@
+use crate::table::Table;
+use knowgraf::{Stats, TermCount};
+use std::io::{self, Write};
+
+/// Prints `stats` as a summary followed by tables of the named graphs, the
+/// top predicates and the top classes. Estimated counts are marked with `~`.
+pub fn write(stats: &Stats, out: &mut impl Write) -> io::Result<()> {
+    let mark = |field: &str| {
+        if stats.estimated.iter().any(|estimated| estimated == field) {
+            "~"
+        } else {
+            ""
+        }
+    };
+    let mut summary = vec![
+        ("quads", stats.quads.to_string()),
+        ("default graph", stats.default_graph.to_string()),
+        ("named graphs", stats.graphs.len().to_string()),
+        (
+            "distinct subjects",
+            format!("{}{}", mark("distinct_subjects"), stats.distinct_subjects),
+        ),
+        (
+            "distinct predicates",
+            format!(
+                "{}{}",
+                mark("distinct_predicates"),
+                stats.distinct_predicates
+            ),
+        ),
+        (
+            "distinct objects",
+            format!("{}{}", mark("distinct_objects"), stats.distinct_objects),
+        ),
+    ];
+    if let Some(size) = stats.disk_size {
+        summary.push(("disk size", human_size(size)));
+    }
+    for (name, value) in summary {
+        writeln!(out, "{:<20} {}", name, value)?;
+    }
+    if !stats.graphs.is_empty() {
+        let mut table = Table::new(vec!["graph".to_string(), "quads".to_string()]);
+        for graph in &stats.graphs {
+            table.push(vec![graph.graph.clone(), graph.quads.to_string()]);
+        }
+        writeln!(out)?;
+        table.write(out)?;
+    }
+    write_counts(
+        "predicate",
+        &stats.top_predicates,
+        mark("top_predicates"),
+        out,
+    )?;
+    write_counts("class", &stats.top_classes, mark("top_classes"), out)
+}
+
+/// Writes `counts` as a table, each count prefixed with `mark`.
+fn write_counts(
+    header: &str,
+    counts: &[TermCount],
+    mark: &str,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    if counts.is_empty() {
+        return Ok(());
+    }
+    let mut table = Table::new(vec![header.to_string(), "count".to_string()]);
+    for count in counts {
+        table.push(vec![count.term.clone(), format!("{}{}", mark, count.count)]);
+    }
+    writeln!(out)?;
+    table.write(out)
+}
+
+/// `size` bytes in the largest binary unit that keeps it at least 1.
+fn human_size(size: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", size)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use knowgraf::GraphCount;
+
+    #[test]
+    fn report() {
+        let stats = Stats {
+            quads: 3,
+            default_graph: 1,
+            graphs: vec![GraphCount {
+                graph: "http://e/g".into(),
+                quads: 2,
+            }],
+            distinct_subjects: 1,
+            distinct_predicates: 1,
+            distinct_objects: 3,
+            top_predicates: vec![TermCount {
+                term: "http://e/p".into(),
+                count: 3,
+            }],
+            top_classes: vec![TermCount {
+                term: "http://e/C".into(),
+                count: 1,
+            }],
+            disk_size: Some(1536),
+            estimated: vec!["distinct_subjects".into(), "top_classes".into()],
+        };
+        let mut out = Vec::new();
+        write(&stats, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\
+quads                3
+default graph        1
+named graphs         1
+distinct subjects    ~1
+distinct predicates  1
+distinct objects     3
+disk size            1.5 KiB
+
++------------+-------+
+| graph      | quads |
++------------+-------+
+| http://e/g | 2     |
++------------+-------+
+
++------------+-------+
+| predicate  | count |
++------------+-------+
+| http://e/p | 3     |
++------------+-------+
+
++------------+-------+
+| class      | count |
++------------+-------+
+| http://e/C | ~1    |
++------------+-------+
+"
+        );
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(human_size(10), "10 B");
+        assert_eq!(human_size(5 * 1024 * 1024), "5.0 MiB");
+    }
+}
@@ -0,0 +1,100 @@
+use std::io::{self, Write};
+
+/// A table of text cells printed with ASCII borders, sized to its contents.
+pub struct Table {
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(header: Vec<String>) -> Self {
+        Table {
+            header,
+            rows: Vec::new(),
+        }
+    }
+
+    /// Adds a row; missing cells are left empty.
+    pub fn push(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        let widths: Vec<usize> = (0..self.header.len())
+            .map(|i| {
+                self.rows
+                    .iter()
+                    .filter_map(|row| row.get(i))
+                    .chain(Some(&self.header[i]))
+                    .map(|cell| cell.chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        write_separator(out, &widths)?;
+        write_row(out, &widths, &self.header)?;
+        write_separator(out, &widths)?;
+        for row in &self.rows {
+            write_row(out, &widths, row)?;
+        }
+        if !self.rows.is_empty() {
+            write_separator(out, &widths)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_separator(out: &mut impl Write, widths: &[usize]) -> io::Result<()> {
+    for width in widths {
+        write!(out, "+{}", "-".repeat(width + 2))?;
+    }
+    writeln!(out, "+")
+}
+
+fn write_row(out: &mut impl Write, widths: &[usize], row: &[String]) -> io::Result<()> {
+    for (i, width) in widths.iter().enumerate() {
+        let cell = row.get(i).map(String::as_str).unwrap_or("");
+        let padding = width - cell.chars().count();
+        write!(out, "| {}{} ", cell, " ".repeat(padding))?;
+    }
+    writeln!(out, "|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(table: &Table) -> String {
+        let mut out = Vec::new();
+        table.write(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn sized_to_contents() {
+        let mut table = Table::new(vec!["s".to_string(), "name".to_string()]);
+        table.push(vec!["<http://a>".to_string(), "\"Åsa\"".to_string()]);
+        table.push(vec!["_:b".to_string()]);
+        assert_eq!(
+            render(&table),
+            "\
++------------+-------+
+| s          | name  |
++------------+-------+
+| <http://a> | \"Åsa\" |
+| _:b        |       |
++------------+-------+
+"
+        );
+    }
+
+    #[test]
+    fn empty() {
+        let table = Table::new(vec!["s".to_string()]);
+        assert_eq!(render(&table), "+---+\n| s |\n+---+\n");
+    }
+}
@@ -0,0 +1,292 @@
+use crate::dump;
+use crate::format::RdfFormat;
+use crate::graphs;
+use crate::load::{self, Progress};
+use crate::query::{self, OutputFormat};
+use crate::remote::Client;
+use crate::update::{self, Changes};
+use anyhow::{bail, Error};
+use knowgraf::jsonld::Context;
+use knowgraf::GraphCount;
+use oxigraph::model::{GraphName, NamedNode};
+use oxigraph::SledStore;
+use std::io::{BufRead, Write};
+
+/// What the subcommands work on: the database opened with `--file`, or a
+/// knowgraf server given with `--endpoint`.
+pub enum Target {
+    Local(SledStore),
+    Remote(Client),
+}
+
+impl Target {
+    /// Opens the server at `endpoint` if given, else the database at `file`.
+    pub fn open(file: Option<&str>, endpoint: Option<&str>) -> Result<Target, Error> {
+        match (endpoint, file) {
+            (Some(endpoint), _) => Ok(Target::Remote(Client::new(endpoint))),
+            (None, Some(path)) => Ok(Target::Local(SledStore::open(path)?)),
+            (None, None) => bail!("--file or --endpoint is required"),
+        }
+    }
+
+    pub fn query(
+        &self,
+        query: &str,
+        format: OutputFormat,
+        out: &mut impl Write,
+    ) -> Result<(), Error> {
+        match self {
+            Target::Local(store) => query::run(store, query, format, out),
+            Target::Remote(client) => query::run_remote(client, query, format, out),
+        }
+    }
+
+    /// Runs `updates` in order. The changes are only counted locally, the
+    /// protocol doesn't report them, and only for updates without a `LOAD`.
+    pub fn update(
+        &self,
+        updates: &[String],
+        using_graphs: &[NamedNode],
+        using_named_graphs: &[NamedNode],
+    ) -> Result<Option<Changes>, Error> {
+        match self {
+            Target::Local(store) => {
+                let updates = updates
+                    .iter()
+                    .map(|text| update::parse(text, using_graphs, using_named_graphs))
+                    .collect::<Result<_, _>>()?;
+                update::run(store, updates)
+            }
+            Target::Remote(client) => {
+                for text in updates {
+                    client.update(text, using_graphs, using_named_graphs)?;
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// Counts the quads of a local store, or `None` through a server.
+    pub fn local_len(&self) -> Option<usize> {
+        match self {
+            Target::Local(store) => Some(store.len()),
+            Target::Remote(_) => None,
+        }
+    }
+
+    pub fn load(
+        &self,
+        reader: impl BufRead,
+        format: RdfFormat,
+        graph: Option<&NamedNode>,
+        base_iri: Option<&str>,
+        progress: &Progress,
+    ) -> Result<(), Error> {
+        match self {
+            Target::Local(store) => load::load(store, reader, format, graph, base_iri, progress),
+            Target::Remote(client) => load::load_remote(client, reader, format, graph, base_iri),
+        }
+    }
+
+    pub fn dump(
+        &self,
+        graphs: &[GraphName],
+        format: RdfFormat,
+        context: Option<&Context>,
+        out: impl Write,
+    ) -> Result<(), Error> {
+        match self {
+            Target::Local(store) => dump::dump(store, graphs, format, context, out),
+            Target::Remote(client) => dump::dump_remote(client, graphs, format, context, out),
+        }
+    }
+
+    /// The named graphs with their quad counts, by name.
+    pub fn graphs(&self) -> Result<Vec<GraphCount>, Error> {
+        match self {
+            Target::Local(store) => Ok(GraphCount::list(store)?),
+            Target::Remote(client) => client.graphs(),
+        }
+    }
+
+    /// Whether `graph` exists. The default graph always does.
+    pub fn contains_graph(&self, graph: &GraphName) -> Result<bool, Error> {
+        match (self, graph) {
+            (Target::Local(store), graph) => Ok(graphs::exists(store, graph)?),
+            (Target::Remote(_), GraphName::NamedNode(node)) => Ok(self
+                .graphs()?
+                .iter()
+                .any(|listed| listed.graph == node.as_str())),
+            (Target::Remote(_), graph) => Ok(graph.is_default_graph()),
+        }
+    }
+
+    /// Removes the named `graph`, or empties the default graph.
+    pub fn remove_graph(&self, graph: &GraphName) -> Result<(), Error> {
+        match self {
+            Target::Local(store) => graphs::remove(store, graph),
+            Target::Remote(client) => {
+                if !self.contains_graph(graph)? {
+                    bail!("there is no graph {}", graph);
+                }
+                client.update(&graphs::drop_update(graph), &[], &[])
+            }
+        }
+    }
+
+    /// Replaces the content of `to` with that of `from`.
+    pub fn copy_graph(&self, from: &GraphName, to: &GraphName) -> Result<(), Error> {
+        match self {
+            Target::Local(store) => graphs::copy(store, from, to).map(drop),
+            Target::Remote(client) => self.remote_copy(client, "COPY", from, to),
+        }
+    }
+
+    /// Replaces the content of `to` with that of `from` and removes `from`.
+    pub fn move_graph(&self, from: &GraphName, to: &GraphName) -> Result<(), Error> {
+        match self {
+            Target::Local(store) => graphs::move_graph(store, from, to).map(drop),
+            Target::Remote(client) => self.remote_copy(client, "MOVE", from, to),
+        }
+    }
+
+    /// Moves `from` to `to`, which must not exist yet.
+    pub fn rename_graph(&self, from: &GraphName, to: &GraphName) -> Result<(), Error> {
+        if to.is_default_graph() {
+            bail!("the default graph always exists, move to it instead");
+        }
+        if self.contains_graph(to)? {
+            bail!("the graph {} exists already", to);
+        }
+        self.move_graph(from, to)
+    }
+
+    fn remote_copy(
+        &self,
+        client: &Client,
+        operation: &str,
+        from: &GraphName,
+        to: &GraphName,
+    ) -> Result<(), Error> {
+        if !self.contains_graph(from)? {
+            bail!("there is no graph {}", from);
+        }
+        client.update(&graphs::copy_update(operation, from, to), &[], &[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::remote::tests::serve;
+    use oxigraph::io::{DatasetFormat, GraphFormat};
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn against_a_server() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path().join("db")).unwrap();
+        let target = Target::Remote(Client::new(&serve(store.clone())));
+
+        let data = dir.path().join("data.ttl");
+        fs::write(&data, "<http://e/s> <http://e/p> \"o\" .\n").unwrap();
+        let options = load::Options {
+            graph: Some(NamedNode::new("http://e/g").unwrap()),
+            jobs: 1,
+            ..load::Options::default()
+        };
+        let files = vec![data.display().to_string()];
+        let summary = load::load_all(&target, files, &options, &Progress::hidden()).unwrap();
+        assert_eq!(summary.to_string(), "loaded 1 file");
+
+        let changes = target
+            .update(
+                &["INSERT { ?s ?p \"copy\" } WHERE { ?s ?p ?o }".to_string()],
+                &[NamedNode::new("http://e/g").unwrap()],
+                &[],
+            )
+            .unwrap();
+        assert_eq!(changes, None);
+        assert_eq!(store.len(), 2);
+
+        let mut out = Vec::new();
+        target
+            .query(
+                "SELECT ?o WHERE { ?s ?p ?o } ORDER BY ?o",
+                OutputFormat::Table,
+                &mut out,
+            )
+            .unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .ends_with("| \"copy\" |\n+--------+\n1 row\n"));
+        let mut out = Vec::new();
+        target
+            .query(
+                "CONSTRUCT { ?s ?p ?o } WHERE { GRAPH ?g { ?s ?p ?o } }",
+                OutputFormat::Table,
+                &mut out,
+            )
+            .unwrap();
+        assert!(String::from_utf8(out).unwrap().ends_with("1 triple\n"));
+
+        let mut out = Vec::new();
+        target
+            .dump(
+                &[GraphName::DefaultGraph],
+                RdfFormat::Graph(GraphFormat::NTriples),
+                None,
+                &mut out,
+            )
+            .unwrap();
+        assert_eq!(out, b"<http://e/s> <http://e/p> \"copy\" .\n");
+        let mut out = Vec::new();
+        target
+            .dump(
+                &[],
+                RdfFormat::Dataset(DatasetFormat::NQuads),
+                None,
+                &mut out,
+            )
+            .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 2);
+        assert!(target
+            .dump(
+                &[],
+                RdfFormat::Graph(GraphFormat::NTriples),
+                None,
+                Vec::new()
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn graphs_on_a_server() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        store
+            .update("INSERT DATA { GRAPH <http://e/a> { <http://e/s> <http://e/p> 1, 2 } }")
+            .unwrap();
+        let target = Target::Remote(Client::new(&serve(store.clone())));
+        let a = GraphName::from(NamedNode::new("http://e/a").unwrap());
+        let b = GraphName::from(NamedNode::new("http://e/b").unwrap());
+
+        target.copy_graph(&a, &b).unwrap();
+        assert_eq!(store.len(), 4);
+        assert!(target.rename_graph(&a, &b).is_err());
+        target.remove_graph(&b).unwrap();
+        target.rename_graph(&a, &b).unwrap();
+        assert_eq!(
+            target.graphs().unwrap(),
+            vec![GraphCount {
+                graph: "http://e/b".into(),
+                quads: 2
+            }]
+        );
+        assert!(target.remove_graph(&a).is_err());
+        target.move_graph(&b, &GraphName::DefaultGraph).unwrap();
+        assert!(target.graphs().unwrap().is_empty());
+        assert_eq!(store.len(), 2);
+    }
+}
@@ -0,0 +1,354 @@
+use anyhow::{bail, Error};
+use oxigraph::model::{
+    BlankNode, GraphName, GraphNameRef, NamedNode, NamedOrBlankNode, Quad, Term,
+};
+use oxigraph::sparql::algebra::{
+    GraphTarget, GraphUpdateOperation, NamedNodeOrVariable, QuadPattern, TermOrVariable,
+};
+use oxigraph::sparql::{Query, QueryResults, QuerySolution, Update};
+use oxigraph::SledStore;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// The quads an update run added to and removed from the store.
+#[derive(Debug, Default, PartialEq)]
+pub struct Changes {
+    pub inserted: usize,
+    pub deleted: usize,
+}
+
+impl fmt::Display for Changes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plural = |n| if n == 1 { "" } else { "s" };
+        write!(
+            f,
+            "inserted {} quad{}, deleted {} quad{}",
+            self.inserted,
+            plural(self.inserted),
+            self.deleted,
+            plural(self.deleted)
+        )
+    }
+}
+
+/// Parses `update`, making `using_graphs` and `using_named_graphs` the
+/// dataset of its operations like the protocol parameters of the same name.
+pub fn parse(
+    update: &str,
+    using_graphs: &[NamedNode],
+    using_named_graphs: &[NamedNode],
+) -> Result<Update, Error> {
+    let mut update = Update::parse(update, None)?;
+    if !using_graphs.is_empty() || !using_named_graphs.is_empty() {
+        for operation in &mut update.operations {
+            if let GraphUpdateOperation::DeleteInsert { using, .. } = operation {
+                if !using.is_default_dataset() {
+                    bail!("--using-graph-uri and --using-named-graph-uri must not be used with an update containing USING");
+                }
+                using
+                    .set_default_graph(using_graphs.iter().cloned().map(GraphName::from).collect());
+                using.set_available_named_graphs(
+                    using_named_graphs
+                        .iter()
+                        .cloned()
+                        .map(NamedOrBlankNode::from)
+                        .collect(),
+                );
+            }
+        }
+    }
+    Ok(update)
+}
+
+/// Runs `updates` in order and counts the quads they changed, if it can.
+///
+/// Each operation is counted from the quads it touches rather than from the
+/// whole store. `DELETE`/`INSERT` operations are run by [`delete_insert`],
+/// which counts as it goes. The others are counted just before they run:
+/// the quads of `INSERT DATA` and `DELETE DATA` are looked up and the graphs
+/// emptied by `CLEAR` and `DROP` are counted. What `LOAD` adds isn't known
+/// before it runs, so updates with a `LOAD` aren't counted.
+pub fn run(store: &SledStore, updates: Vec<Update>) -> Result<Option<Changes>, Error> {
+    let mut changes = Some(Changes::default());
+    for update in updates {
+        for operation in update.operations {
+            let counted = match operation {
+                GraphUpdateOperation::DeleteInsert {
+                    delete,
+                    insert,
+                    using,
+                    pattern,
+                } => {
+                    let query = Query::Select {
+                        dataset: using,
+                        pattern: *pattern,
+                        base_iri: update.base_iri.clone(),
+                    };
+                    Some(delete_insert(store, &delete, &insert, query)?)
+                }
+                operation => {
+                    let update = Update {
+                        base_iri: update.base_iri.clone(),
+                        operations: vec![operation],
+                    };
+                    let counted = count(store, &update)?;
+                    store.update(update)?;
+                    counted
+                }
+            };
+            match (&mut changes, counted) {
+                (Some(total), Some(counted)) => {
+                    total.inserted += counted.inserted;
+                    total.deleted += counted.deleted;
+                }
+                _ => changes = None,
+            }
+        }
+    }
+    Ok(changes)
+}
+
+/// Runs a `DELETE`/`INSERT` operation, whose `WHERE` clause is `query`, the
+/// way Oxigraph does: the templates are applied to each solution as it
+/// comes. The quads actually removed and added are counted on the way, so
+/// that the `WHERE` clause is evaluated once and its solutions aren't held.
+/// A quad removed for a solution and added back for a later one counts as
+/// both deleted and inserted.
+fn delete_insert(
+    store: &SledStore,
+    delete: &[QuadPattern],
+    insert: &[QuadPattern],
+    query: Query,
+) -> Result<Changes, Error> {
+    let mut changes = Changes::default();
+    if let QueryResults::Solutions(solutions) = store.query(query)? {
+        for solution in solutions {
+            let solution = solution?;
+            for quad in delete.iter().filter_map(|q| instantiate(q, &solution)) {
+                if store.contains(&quad)? {
+                    store.remove(&quad)?;
+                    changes.deleted += 1;
+                }
+            }
+            // Blank nodes in the template are fresh for each solution.
+            let mut bnodes = HashMap::new();
+            for quad in insert
+                .iter()
+                .filter_map(|q| instantiate(&with_fresh_bnodes(q, &mut bnodes), &solution))
+            {
+                if !store.contains(&quad)? {
+                    store.insert(&quad)?;
+                    changes.inserted += 1;
+                }
+            }
+        }
+    }
+    Ok(changes)
+}
+
+/// The quads the operation of `update`, which only has one, would change in
+/// `store`, or `None` if that can't be told before running it.
+fn count(store: &SledStore, update: &Update) -> Result<Option<Changes>, Error> {
+    let mut changes = Changes::default();
+    for operation in &update.operations {
+        match operation {
+            GraphUpdateOperation::InsertData { data } => {
+                for quad in data.iter().collect::<HashSet<_>>() {
+                    if !store.contains(quad)? {
+                        changes.inserted += 1;
+                    }
+                }
+            }
+            GraphUpdateOperation::DeleteData { data } => {
+                for quad in data.iter().collect::<HashSet<_>>() {
+                    if store.contains(quad)? {
+                        changes.deleted += 1;
+                    }
+                }
+            }
+            GraphUpdateOperation::Clear { graph, .. }
+            | GraphUpdateOperation::Drop { graph, .. } => {
+                let in_graph = |graph: GraphNameRef<'_>| {
+                    store
+                        .quads_for_pattern(None, None, None, Some(graph))
+                        .count()
+                };
+                changes.deleted += match graph {
+                    GraphTarget::NamedNode(node) => in_graph(node.into()),
+                    GraphTarget::DefaultGraph => in_graph(GraphNameRef::DefaultGraph),
+                    GraphTarget::NamedGraphs => store.len() - in_graph(GraphNameRef::DefaultGraph),
+                    GraphTarget::AllGraphs => store.len(),
+                };
+            }
+            GraphUpdateOperation::Create { .. } => (),
+            GraphUpdateOperation::Load { .. } => return Ok(None),
+            GraphUpdateOperation::DeleteInsert { .. } => unreachable!("run by delete_insert"),
+        }
+    }
+    Ok(Some(changes))
+}
+
+/// `pattern` with its blank nodes replaced by the ones in `bnodes`, creating
+/// those not seen yet.
+fn with_fresh_bnodes(pattern: &QuadPattern, bnodes: &mut HashMap<BlankNode, Term>) -> QuadPattern {
+    let mut fresh = |term: &TermOrVariable| match term {
+        TermOrVariable::Term(Term::BlankNode(node)) => TermOrVariable::Term(
+            bnodes
+                .entry(node.clone())
+                .or_insert_with(|| BlankNode::default().into())
+                .clone(),
+        ),
+        term => term.clone(),
+    };
+    QuadPattern {
+        subject: fresh(&pattern.subject),
+        predicate: pattern.predicate.clone(),
+        object: fresh(&pattern.object),
+        graph_name: pattern.graph_name.clone(),
+    }
+}
+
+/// The quad `pattern` gives for `solution`, if its variables are bound to
+/// terms that fit their positions.
+fn instantiate(pattern: &QuadPattern, solution: &QuerySolution) -> Option<Quad> {
+    let term = |term: &TermOrVariable| match term {
+        TermOrVariable::Term(term) => Some(term.clone()),
+        TermOrVariable::Variable(variable) => solution.get(variable).cloned(),
+    };
+    let named_node = |node: &NamedNodeOrVariable| match node {
+        NamedNodeOrVariable::NamedNode(node) => Some(node.clone()),
+        NamedNodeOrVariable::Variable(variable) => match solution.get(variable)? {
+            Term::NamedNode(node) => Some(node.clone()),
+            _ => None,
+        },
+    };
+    let subject = match term(&pattern.subject)? {
+        Term::NamedNode(node) => NamedOrBlankNode::from(node),
+        Term::BlankNode(node) => node.into(),
+        Term::Literal(_) => return None,
+    };
+    let graph_name = match &pattern.graph_name {
+        Some(graph) => named_node(graph)?.into(),
+        None => GraphName::DefaultGraph,
+    };
+    Some(Quad::new(
+        subject,
+        named_node(&pattern.predicate)?,
+        term(&pattern.object)?,
+        graph_name,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn run_updates(store: &SledStore, updates: &[&str]) -> Option<Changes> {
+        let updates = updates
+            .iter()
+            .map(|update| parse(update, &[], &[]).unwrap())
+            .collect();
+        run(store, updates).unwrap()
+    }
+
+    #[test]
+    fn counts_changes() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        let changes = run_updates(
+            &store,
+            &[
+                "INSERT DATA { <http://e/a> <http://e/p> 1, 2, 3 }",
+                "DELETE DATA { <http://e/a> <http://e/p> 1, 4 }",
+            ],
+        );
+        assert_eq!(
+            changes,
+            Some(Changes {
+                inserted: 3,
+                deleted: 1
+            })
+        );
+        let changes = run_updates(&store, &["DELETE WHERE { ?s ?p 2 }"]).unwrap();
+        assert_eq!(changes.to_string(), "inserted 0 quads, deleted 1 quad");
+    }
+
+    #[test]
+    fn counts_templates() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        run_updates(
+            &store,
+            &["INSERT DATA { <http://e/a> <http://e/p> 1, 2, 3 }"],
+        );
+        let changes = run_updates(
+            &store,
+            &[
+                "DELETE { ?s ?p ?o } INSERT { ?s ?p 3 . ?s <http://e/q> [ <http://e/r> ?o ] } \
+               WHERE { ?s ?p ?o }",
+            ],
+        );
+        // `?s ?p 3` is deleted for the last solution, then added back.
+        assert_eq!(
+            changes,
+            Some(Changes {
+                inserted: 7,
+                deleted: 3
+            })
+        );
+        assert_eq!(store.len(), 7);
+    }
+
+    #[test]
+    fn counts_cleared_graphs() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        run_updates(
+            &store,
+            &["INSERT DATA { <http://e/a> <http://e/p> 1 . GRAPH <http://e/g> { <http://e/a> <http://e/p> 1, 2 } }"],
+        );
+        let changes = run_updates(&store, &["CLEAR NAMED", "DROP DEFAULT"]).unwrap();
+        assert_eq!(changes.deleted, 3);
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn load_is_not_counted() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        let updates = vec![
+            parse("INSERT DATA { <http://e/a> <http://e/p> 1 }", &[], &[]).unwrap(),
+            parse("LOAD SILENT <http://localhost:1/missing.nt>", &[], &[]).unwrap(),
+        ];
+        assert_eq!(run(&store, updates).unwrap(), None);
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn using_graph() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        run_updates(
+            &store,
+            &["INSERT DATA { GRAPH <http://e/g> { <http://e/a> <http://e/p> 1 } }"],
+        );
+        let update = parse(
+            "INSERT { ?s ?p 2 } WHERE { ?s ?p 1 }",
+            &[NamedNode::new("http://e/g").unwrap()],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(run(&store, vec![update]).unwrap().unwrap().inserted, 1);
+    }
+
+    #[test]
+    fn using_conflicts_with_using_graph() {
+        assert!(parse(
+            "INSERT { ?s ?p 2 } USING <http://e/h> WHERE { ?s ?p 1 }",
+            &[NamedNode::new("http://e/g").unwrap()],
+            &[],
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use actix_web::error::BlockingError;
+use futures::channel::oneshot;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use threadpool::ThreadPool;
+
+/// The threads store operations run on, so that they don't hold up the HTTP
+/// workers.
+///
+/// Each [`AppState`](crate::AppState) has its own, started on first use.
+pub(crate) struct Blocking {
+    threads: usize,
+    pool: Mutex<Option<ThreadPool>>,
+    /// The queries and dumps running, see [`Blocking::start_read`].
+    reads: Running,
+}
+
+impl Blocking {
+    /// A pool of `threads` threads, 5 per CPU if `None`.
+    pub(crate) fn new(threads: Option<usize>) -> Self {
+        let threads = threads
+            .unwrap_or_else(|| 5 * thread::available_parallelism().map_or(1, |cpus| cpus.get()));
+        Blocking {
+            threads: threads.max(1),
+            pool: Mutex::new(None),
+            reads: Running::default(),
+        }
+    }
+
+    /// How many threads queries and dumps may take at once: all but a quarter
+    /// of the pool, and at least one thread, is left to the other operations.
+    ///
+    /// Queries can't be stopped while Oxigraph sorts, groups or aggregates,
+    /// so one that timed out may keep its thread until it is done, and both
+    /// keep theirs while the client is slow to read the response. Writes
+    /// still get threads meanwhile.
+    pub(crate) fn read_threads(&self) -> usize {
+        (self.threads - (self.threads / 4).max(1)).max(1)
+    }
+
+    /// Counts one more of the reads counted by `running`, queries or dumps,
+    /// until the permit is dropped. Returns `None` if `max` of them are
+    /// running already, or if the reads of all kinds took
+    /// [`Blocking::read_threads`].
+    pub(crate) fn start_read(&self, running: &Running, max: usize) -> Option<ReadPermit> {
+        let own = running.start(max)?;
+        let all = self.reads.start(self.read_threads())?;
+        Some(ReadPermit {
+            _own: own,
+            _all: all,
+        })
+    }
+
+    /// Runs `f` on the pool, without waiting for it.
+    pub(crate) fn execute(&self, f: impl FnOnce() + Send + 'static) {
+        self.pool().execute(f)
+    }
+
+    /// Runs `f` on the pool and waits for its result, like `web::block` but
+    /// on this pool.
+    pub(crate) async fn run<F, T, E>(&self, f: F) -> Result<T, BlockingError<E>>
+    where
+        F: FnOnce() -> Result<T, E> + Send + 'static,
+        T: Send + 'static,
+        E: fmt::Debug + Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        self.execute(move || {
+            let _ = sender.send(f());
+        });
+        match receiver.await {
+            Ok(result) => result.map_err(BlockingError::Error),
+            Err(oneshot::Canceled) => Err(BlockingError::Canceled),
+        }
+    }
+
+    fn pool(&self) -> ThreadPool {
+        let mut pool = self.pool.lock().unwrap_or_else(PoisonError::into_inner);
+        pool.get_or_insert_with(|| {
+            threadpool::Builder::new()
+                .num_threads(self.threads)
+                .thread_name("knowgraf-blocking".into())
+                .build()
+        })
+        .clone()
+    }
+}
+
+/// Counts the operations of a kind being run, to bound how many run at once.
+#[derive(Default)]
+pub(crate) struct Running(Arc<AtomicUsize>);
+
+impl Running {
+    /// Counts one more operation until the permit is dropped, unless `max`
+    /// are running already.
+    pub(crate) fn start(&self, max: usize) -> Option<Permit> {
+        self.0
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |running| {
+                (running < max).then_some(running + 1)
+            })
+            .ok()
+            .map(|_| Permit(self.0.clone()))
+    }
+}
+
+pub(crate) struct Permit(Arc<AtomicUsize>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Counts a query or a dump, among those of its kind and among all reads.
+pub(crate) struct ReadPermit {
+    _own: Permit,
+    _all: Permit,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Barrier};
+
+    #[actix_rt::test]
+    async fn runs_on_its_own_threads() {
+        let blocking = Blocking::new(Some(2));
+        let name = blocking
+            .run(|| Ok::<_, ()>(thread::current().name().map(String::from)))
+            .await
+            .unwrap();
+        assert_eq!(name.as_deref(), Some("knowgraf-blocking"));
+
+        // Both threads are needed to get past the barrier.
+        let barrier = Arc::new(Barrier::new(2));
+        let other = barrier.clone();
+        blocking.execute(move || {
+            other.wait();
+        });
+        blocking
+            .run(move || Ok::<_, ()>(barrier.wait()))
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn reads_leave_threads() {
+        assert_eq!(Blocking::new(Some(1)).read_threads(), 1);
+        assert_eq!(Blocking::new(Some(2)).read_threads(), 1);
+        assert_eq!(Blocking::new(Some(8)).read_threads(), 6);
+
+        // Two threads for reads, one for the rest.
+        let blocking = Blocking::new(Some(3));
+        let (queries, dumps) = (Running::default(), Running::default());
+        let query = blocking.start_read(&queries, 2).unwrap();
+        let dump = blocking.start_read(&dumps, 2).unwrap();
+        assert!(blocking.start_read(&dumps, 2).is_none());
+        drop(dump);
+        assert!(blocking.start_read(&queries, 1).is_none());
+        let _dump = blocking.start_read(&dumps, 2).unwrap();
+        drop(query);
+        assert!(blocking.start_read(&queries, 2).is_some());
+    }
+
+    #[actix_rt::test]
+    async fn passes_errors_on() {
+        let blocking = Blocking::new(Some(1));
+        match blocking.run(|| Err::<(), _>("failed")).await {
+            Err(BlockingError::Error(err)) => assert_eq!(err, "failed"),
+            _ => panic!("expected the error of the closure"),
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use clap::{App, Arg};
+
+pub fn build_cli() -> App<'static, 'static> {
+    use clap::crate_version;
+    App::new("knowgraf")
+        .version(crate_version!())
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .about("SPARQL and Graph Store server for knowgraf.")
+        .after_help(
+            "Every option can also be set with an environment variable named after it, \
+             such as KNOWGRAF_MAX_UPLOAD_SIZE for --max-upload-size.",
+        )
+        .arg(
+            Arg::with_name("config")
+                .short("c")
+                .long("config")
+                .value_name("PATH")
+                .help("Read settings from a TOML file"),
+        )
+        .arg(
+            Arg::with_name("bind")
+                .short("b")
+                .long("bind")
+                .value_name("ADDRESS")
+                .help("Address to listen on [default: 127.0.0.1]"),
+        )
+        .arg(
+            Arg::with_name("port")
+                .short("p")
+                .long("port")
+                .value_name("PORT")
+                .help("Port to listen on [default: 8080]"),
+        )
+        .arg(
+            Arg::with_name("file")
+                .short("f")
+                .long("file")
+                .value_name("PATH")
+                .help("Specify the db [default: example.db]"),
+        )
+        .arg(
+            Arg::with_name("workers")
+                .short("w")
+                .long("workers")
+                .value_name("N")
+                .help("Number of HTTP workers [default: number of CPUs]"),
+        )
+        .arg(
+            Arg::with_name("blocking_threads")
+                .long("blocking-threads")
+                .value_name("N")
+                .help("Number of threads running store operations [default: 5 per CPU]"),
+        )
+        .arg(
+            Arg::with_name("max_upload_size")
+                .long("max-upload-size")
+                .value_name("BYTES")
+                .help("Largest accepted graph store upload [default: 4 GiB]"),
+        )
+        .arg(
+            Arg::with_name("max_jsonld_upload_size")
+                .long("max-jsonld-upload-size")
+                .value_name("BYTES")
+                .help("Largest accepted JSON-LD upload, parsed in memory [default: 64 MiB]"),
+        )
+        .arg(
+            Arg::with_name("upload_idle_timeout")
+                .long("upload-idle-timeout")
+                .value_name("SECS")
+                .help("Time an upload may go without data before it is rejected [default: 30]"),
+        )
+        .arg(
+            Arg::with_name("write_idle_timeout")
+                .long("write-idle-timeout")
+                .value_name("SECS")
+                .help("Time a response may wait for the client to read before it is cut short [default: 30]"),
+        )
+        .arg(
+            Arg::with_name("query_timeout")
+                .long("query-timeout")
+                .value_name("SECS")
+                .help("Time a query may run unless it asks for less [default: 60]"),
+        )
+        .arg(
+            Arg::with_name("max_query_timeout")
+                .long("max-query-timeout")
+                .value_name("SECS")
+                .help("Cap on the timeout parameter of queries [default: 600]"),
+        )
+        .arg(
+            Arg::with_name("max_concurrent_queries")
+                .long("max-concurrent-queries")
+                .value_name("N")
+                .help("Number of queries evaluated at once [default: 16]"),
+        )
+        .arg(
+            Arg::with_name("max_concurrent_dumps")
+                .long("max-concurrent-dumps")
+                .value_name("N")
+                .help("Number of graph store dumps sent at once [default: 4]"),
+        )
+        .arg(
+            Arg::with_name("jsonld_context")
+                .long("jsonld-context")
+                .value_name("PATH")
+                .help("JSON-LD context to compact answers against when requests give none"),
+        )
+        .arg(
+            Arg::with_name("resource_prefix")
+                .long("resource-prefix")
+                .value_name("PATH")
+                .help("Path under which IRIs dereference to their description [default: /id]"),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_args_required() {
+        let args = vec!["knowgraf"];
+        let m = build_cli().get_matches_from_safe(args);
+
+        assert!(m.is_ok());
+    }
+}
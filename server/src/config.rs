@@ -0,0 +1,269 @@
+use clap::ArgMatches;
+use knowgraf::Limits;
+use serde_derive::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Settings for the server process.
+///
+/// Values are layered: built-in defaults, then the TOML file given with
+/// `--config`, then environment variables and finally command-line flags.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind: String,
+    pub port: u16,
+    pub file: PathBuf,
+    pub workers: Option<usize>,
+    /// Size of the thread pool that runs store operations.
+    pub blocking_threads: Option<usize>,
+    pub limits: Limits,
+    /// The file of the JSON-LD context answers are compacted against.
+    pub jsonld_context: Option<PathBuf>,
+    /// The path under which IRIs dereference to their description.
+    pub resource_prefix: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            bind: String::from("127.0.0.1"),
+            port: 8080,
+            file: PathBuf::from("example.db"),
+            workers: None,
+            blocking_threads: None,
+            limits: Limits::default(),
+            jsonld_context: None,
+            resource_prefix: String::from("/id"),
+        }
+    }
+}
+
+impl Config {
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Config> {
+        let contents = fs::read_to_string(path)?;
+        Config::from_toml(&contents)
+    }
+
+    pub fn from_toml(contents: &str) -> io::Result<Config> {
+        toml::from_str(contents).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// The `KNOWGRAF_*` variables of the process environment, as
+    /// [`Config::from_matches`] takes them.
+    pub fn environment() -> HashMap<String, OsString> {
+        env::vars_os()
+            .filter_map(|(name, value)| Some((name.into_string().ok()?, value)))
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+            .collect()
+    }
+
+    /// Builds the settings from the parsed command line and the environment.
+    ///
+    /// Each option is read from its flag, or else from the `KNOWGRAF_*`
+    /// variable of `env` named after it, and applied on top of the config
+    /// file (or the defaults).
+    pub fn from_matches(
+        matches: &ArgMatches,
+        env: &HashMap<String, OsString>,
+    ) -> io::Result<Config> {
+        let value_os = |name: &str| {
+            matches
+                .value_of_os(name)
+                .map(OsStr::to_os_string)
+                .or_else(|| env.get(&env_name(name)).cloned())
+        };
+        let value = |name: &str| {
+            value_os(name)
+                .map(|value| {
+                    value.into_string().map_err(|value| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("invalid value {:?} for {}: not UTF-8", value, name),
+                        )
+                    })
+                })
+                .transpose()
+        };
+        let mut config = match value_os("config") {
+            Some(path) => Config::from_file(path)?,
+            None => Config::default(),
+        };
+        if let Some(bind) = value("bind")? {
+            config.bind = bind;
+        }
+        if let Some(port) = value("port")? {
+            config.port = parse_arg("port", &port)?;
+        }
+        if let Some(file) = value_os("file") {
+            config.file = PathBuf::from(file);
+        }
+        if let Some(workers) = value("workers")? {
+            config.workers = Some(parse_arg("workers", &workers)?);
+        }
+        if let Some(threads) = value("blocking_threads")? {
+            config.blocking_threads = Some(parse_arg("blocking-threads", &threads)?);
+        }
+        if let Some(size) = value("max_upload_size")? {
+            config.limits.max_upload_size = parse_arg("max-upload-size", &size)?;
+        }
+        if let Some(size) = value("max_jsonld_upload_size")? {
+            config.limits.max_jsonld_upload_size = parse_arg("max-jsonld-upload-size", &size)?;
+        }
+        if let Some(secs) = value("upload_idle_timeout")? {
+            config.limits.upload_idle_timeout = parse_arg("upload-idle-timeout", &secs)?;
+        }
+        if let Some(secs) = value("write_idle_timeout")? {
+            config.limits.write_idle_timeout = parse_arg("write-idle-timeout", &secs)?;
+        }
+        if let Some(secs) = value("query_timeout")? {
+            config.limits.query_timeout = parse_arg("query-timeout", &secs)?;
+        }
+        if let Some(secs) = value("max_query_timeout")? {
+            config.limits.max_query_timeout = parse_arg("max-query-timeout", &secs)?;
+        }
+        if let Some(queries) = value("max_concurrent_queries")? {
+            config.limits.max_concurrent_queries = parse_arg("max-concurrent-queries", &queries)?;
+        }
+        if let Some(dumps) = value("max_concurrent_dumps")? {
+            config.limits.max_concurrent_dumps = parse_arg("max-concurrent-dumps", &dumps)?;
+        }
+        if let Some(path) = value_os("jsonld_context") {
+            config.jsonld_context = Some(PathBuf::from(path));
+        }
+        if let Some(prefix) = value("resource_prefix")? {
+            config.resource_prefix = prefix;
+        }
+        Ok(config)
+    }
+}
+
+const ENV_PREFIX: &str = "KNOWGRAF_";
+
+/// The environment variable setting the option of the argument `name`.
+fn env_name(name: &str) -> String {
+    format!("{}{}", ENV_PREFIX, name.to_uppercase())
+}
+
+fn parse_arg<T>(name: &str, value: &str) -> io::Result<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    value.parse().map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid value '{}' for {}: {}", value, name, err),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::build_cli;
+
+    fn matches(args: Vec<&str>) -> ArgMatches<'static> {
+        build_cli().get_matches_from(args)
+    }
+
+    #[test]
+    fn defaults() {
+        let matches = matches(vec!["knowgraf"]);
+        assert_eq!(
+            Config::from_matches(&matches, &HashMap::new()).unwrap(),
+            Config::default()
+        );
+    }
+
+    #[test]
+    fn from_toml() {
+        let config = Config::from_toml(
+            "
+bind = \"0.0.0.0\"
+port = 3030
+workers = 2
+blocking_threads = 16
+jsonld_context = \"context.jsonld\"
+resource_prefix = \"/things\"
+
+[limits]
+max_upload_size = 1024
+max_jsonld_upload_size = 512
+upload_idle_timeout = 10
+query_timeout = 5
+",
+        )
+        .unwrap();
+        assert_eq!(config.bind, "0.0.0.0");
+        assert_eq!(config.port, 3030);
+        assert_eq!(config.file, PathBuf::from("example.db"));
+        assert_eq!(config.workers, Some(2));
+        assert_eq!(config.blocking_threads, Some(16));
+        assert_eq!(config.jsonld_context, Some(PathBuf::from("context.jsonld")));
+        assert_eq!(config.resource_prefix, "/things");
+        assert_eq!(config.limits.max_upload_size, 1024);
+        assert_eq!(config.limits.max_jsonld_upload_size, 512);
+        assert_eq!(config.limits.upload_idle_timeout, 10);
+        assert_eq!(config.limits.query_timeout, 5);
+        assert_eq!(config.limits.max_query_timeout, 600);
+    }
+
+    #[test]
+    fn unknown_key_in_toml() {
+        assert!(Config::from_toml("prot = 3030").is_err());
+    }
+
+    #[test]
+    fn flags_override_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("knowgraf.toml");
+        fs::write(&path, "port = 3030\nfile = \"data.db\"\n").unwrap();
+        let matches = matches(vec![
+            "knowgraf",
+            "--config",
+            path.to_str().unwrap(),
+            "--port",
+            "9090",
+        ]);
+        let config = Config::from_matches(&matches, &HashMap::new()).unwrap();
+        assert_eq!(config.port, 9090);
+        assert_eq!(config.file, PathBuf::from("data.db"));
+    }
+
+    #[test]
+    fn bad_port() {
+        let matches = matches(vec!["knowgraf", "--port", "http"]);
+        assert!(Config::from_matches(&matches, &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn env_overrides_file_and_flags_override_env() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("knowgraf.toml");
+        fs::write(
+            &path,
+            "port = 3030\nbind = \"0.0.0.0\"\n[limits]\nquery_timeout = 5\n",
+        )
+        .unwrap();
+        let env: HashMap<_, _> = vec![
+            ("KNOWGRAF_CONFIG", path.as_os_str()),
+            ("KNOWGRAF_PORT", OsStr::new("4040")),
+            ("KNOWGRAF_QUERY_TIMEOUT", OsStr::new("7")),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.to_os_string()))
+        .collect();
+        let matches = matches(vec!["knowgraf", "--query-timeout", "9"]);
+        let config = Config::from_matches(&matches, &env).unwrap();
+        assert_eq!(config.bind, "0.0.0.0");
+        assert_eq!(config.port, 4040);
+        assert_eq!(config.limits.query_timeout, 9);
+    }
+}
@@ -0,0 +1,257 @@
+use crate::problem;
+use actix_web::{error, http, HttpResponse};
+use derive_more::{Display, Error};
+use oxigraph::model;
+use oxigraph::sparql;
+use std::io;
+use std::time::Duration;
+
+/// The errors the knowgraf handlers respond with.
+///
+/// They are rendered as RFC 7807 problem documents.
+#[derive(Debug, Display, Error)]
+pub enum AppError {
+    #[display(fmt = "io error")]
+    IoError(io::Error),
+    #[display(fmt = "bad input: {}", _0)]
+    BadInput(io::Error),
+    #[display(fmt = "bad url: {}", _0)]
+    BadUrl(actix_web::client::HttpError),
+    #[display(fmt = "url parse error: {}", _0)]
+    UrlParseError(model::IriParseError),
+    #[display(fmt = "bad request: {}", _0)]
+    BadRequest(InnerError),
+    #[display(fmt = "bad request: {}", _0)]
+    BadRequestString(#[error(not(source))] String),
+    #[display(fmt = "internal server error")]
+    InternalServerError(#[error(not(source))] &'static str),
+    #[display(fmt = "query parse error: {}", _0)]
+    QueryParseError(sparql::ParseError),
+    #[display(fmt = "query evalution error: {}", _0)]
+    QueryEvaluationError(sparql::EvaluationError),
+    #[display(fmt = "parse error: {}", _0)]
+    ParseError(InnerError),
+    #[display(fmt = "tostr error: {}", _0)]
+    ToStrError(http::header::ToStrError),
+    #[display(fmt = "bad request: {}", _0)]
+    BadPayload(#[error(not(source))] String),
+    #[display(fmt = "payload larger than {} bytes", _0)]
+    PayloadTooLarge(#[error(not(source))] u64),
+    #[display(fmt = "no data received from the client for {:?}", _0)]
+    UploadTimeout(#[error(not(source))] Duration),
+    #[display(fmt = "query timed out after {:?}", _0)]
+    QueryTimeout(#[error(not(source))] Duration),
+    #[display(fmt = "{} queries are running already, try again later", _0)]
+    TooManyQueries(#[error(not(source))] usize),
+    #[display(fmt = "{} dumps are running already, try again later", _0)]
+    TooManyDumps(#[error(not(source))] usize),
+    #[display(fmt = "not acceptable, supported media types: {}", "_0.join(\", \")")]
+    NotAcceptable(#[error(not(source))] Vec<&'static str>),
+    #[display(fmt = "the graph {} does not exist", _0)]
+    GraphNotFound(#[error(not(source))] model::GraphName),
+    #[display(fmt = "nothing is known about {}", _0)]
+    ResourceNotFound(#[error(not(source))] model::NamedNode),
+    #[display(fmt = "unsupported Content-Type: {}", _0)]
+    UnsupportedMediaType(#[error(not(source))] String),
+}
+
+impl AppError {
+    /// Maps an error from loading RDF data, blaming the client for bad syntax.
+    pub(crate) fn from_load_error(err: io::Error) -> AppError {
+        use io::ErrorKind;
+
+        match err.kind() {
+            ErrorKind::InvalidData | ErrorKind::InvalidInput | ErrorKind::UnexpectedEof => {
+                AppError::BadInput(err)
+            }
+            _ => AppError::from(err),
+        }
+    }
+
+    /// Maps an error that ended a query results stream.
+    pub(crate) fn from_query_io_error(err: io::Error) -> AppError {
+        let kind = err.kind();
+        match err
+            .into_inner()
+            .map(|err| err.downcast::<sparql::EvaluationError>())
+        {
+            Some(Ok(err)) => AppError::QueryEvaluationError(*err),
+            Some(Err(err)) => AppError::IoError(io::Error::new(kind, err)),
+            None => AppError::InternalServerError("query results stream failed"),
+        }
+    }
+}
+
+impl AppError {
+    /// A stable identifier for the kind of error, exposed to clients.
+    fn code(&self) -> &'static str {
+        match self {
+            AppError::IoError(_) => "io_error",
+            AppError::BadInput(_) => "invalid_rdf",
+            AppError::BadUrl(_) => "bad_url",
+            AppError::UrlParseError(_) => "invalid_iri",
+            AppError::BadRequest(_) | AppError::BadRequestString(_) => "bad_request",
+            AppError::BadPayload(_) => "bad_payload",
+            AppError::InternalServerError(_) => "internal_error",
+            AppError::QueryParseError(_) => "parse_error",
+            AppError::QueryEvaluationError(_) => "evaluation_error",
+            AppError::ParseError(_) | AppError::ToStrError(_) => "invalid_header",
+            AppError::PayloadTooLarge(_) => "payload_too_large",
+            AppError::UploadTimeout(_) => "upload_timeout",
+            AppError::QueryTimeout(_) => "query_timeout",
+            AppError::TooManyQueries(_) => "too_many_queries",
+            AppError::TooManyDumps(_) => "too_many_dumps",
+            AppError::NotAcceptable(_) => "not_acceptable",
+            AppError::GraphNotFound(_) => "graph_not_found",
+            AppError::ResourceNotFound(_) => "resource_not_found",
+            AppError::UnsupportedMediaType(_) => "unsupported_media_type",
+        }
+    }
+}
+
+impl problem::ToProblem for AppError {
+    fn to_problem(&self) -> problem::Problem {
+        use error::ResponseError;
+
+        let problem = problem::Problem::new(self.status_code(), self.code(), self.to_string());
+        match self {
+            AppError::QueryParseError(err) => match parse_error_position(err) {
+                Some((line, column)) => problem.at(line, column),
+                None => problem,
+            },
+            _ => problem,
+        }
+    }
+}
+
+/// Extracts the position from the "error at LINE:COLUMN: ..." messages of the
+/// SPARQL parser, which doesn't expose it otherwise. The `parse_error_positions`
+/// test checks that the messages still have this shape.
+fn parse_error_position(err: &sparql::ParseError) -> Option<(u64, u64)> {
+    let message = err.to_string();
+    let position = message.strip_prefix("error at ")?;
+    let (line, rest) = position.split_once(':')?;
+    let column = rest.split(':').next()?;
+    Some((line.parse().ok()?, column.parse().ok()?))
+}
+
+impl error::ResponseError for AppError {
+    fn error_response(&self) -> HttpResponse {
+        use problem::ToProblem;
+
+        if self.status_code().is_server_error() {
+            log::error!("{}: {:?}", self, self);
+        } else {
+            log::debug!("{}: {:?}", self, self);
+        }
+        self.to_problem().to_response()
+    }
+
+    fn status_code(&self) -> http::StatusCode {
+        match *self {
+            AppError::BadInput(_) => http::StatusCode::BAD_REQUEST,
+            AppError::BadUrl(_) => http::StatusCode::BAD_REQUEST,
+            AppError::BadRequest(_) => http::StatusCode::BAD_REQUEST,
+            AppError::BadRequestString(_) => http::StatusCode::BAD_REQUEST,
+            AppError::BadPayload(_) => http::StatusCode::BAD_REQUEST,
+            AppError::ParseError(_) => http::StatusCode::BAD_REQUEST,
+            AppError::ToStrError(_) => http::StatusCode::BAD_REQUEST,
+            AppError::PayloadTooLarge(_) => http::StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::UploadTimeout(_) => http::StatusCode::REQUEST_TIMEOUT,
+            AppError::QueryTimeout(_) => http::StatusCode::SERVICE_UNAVAILABLE,
+            AppError::TooManyQueries(_) => http::StatusCode::SERVICE_UNAVAILABLE,
+            AppError::TooManyDumps(_) => http::StatusCode::SERVICE_UNAVAILABLE,
+            AppError::NotAcceptable(_) => http::StatusCode::NOT_ACCEPTABLE,
+            AppError::GraphNotFound(_) => http::StatusCode::NOT_FOUND,
+            AppError::ResourceNotFound(_) => http::StatusCode::NOT_FOUND,
+            AppError::UnsupportedMediaType(_) => http::StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::QueryParseError(_) => http::StatusCode::BAD_REQUEST,
+            AppError::QueryEvaluationError(_) => http::StatusCode::BAD_REQUEST,
+            _ => http::StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl From<io::Error> for AppError {
+    fn from(err: io::Error) -> AppError {
+        use io::ErrorKind;
+
+        match err.kind() {
+            ErrorKind::UnexpectedEof => AppError::BadRequest(InnerError::IoError(err)),
+            _ => AppError::IoError(err),
+        }
+    }
+}
+
+impl From<model::IriParseError> for AppError {
+    fn from(err: model::IriParseError) -> AppError {
+        AppError::UrlParseError(err)
+    }
+}
+
+impl From<sparql::ParseError> for AppError {
+    fn from(err: sparql::ParseError) -> AppError {
+        AppError::QueryParseError(err)
+    }
+}
+
+impl From<sparql::EvaluationError> for AppError {
+    fn from(err: sparql::EvaluationError) -> AppError {
+        AppError::QueryEvaluationError(err)
+    }
+}
+
+impl From<http::header::ToStrError> for AppError {
+    fn from(err: http::header::ToStrError) -> AppError {
+        AppError::ToStrError(err)
+    }
+}
+
+impl From<actix_web::Error> for AppError {
+    fn from(err: actix_web::Error) -> AppError {
+        AppError::BadPayload(err.to_string())
+    }
+}
+
+impl From<error::BlockingError<AppError>> for AppError {
+    fn from(err: error::BlockingError<AppError>) -> AppError {
+        match err {
+            error::BlockingError::Error(err) => err,
+            error::BlockingError::Canceled => {
+                AppError::InternalServerError("blocking task was cancelled")
+            }
+        }
+    }
+}
+
+impl From<mime::FromStrError> for AppError {
+    fn from(err: mime::FromStrError) -> AppError {
+        AppError::ParseError(InnerError::MimeFromStr(err))
+    }
+}
+
+#[derive(Debug, Display, Error)]
+pub enum InnerError {
+    #[display(fmt = "{}", _0)]
+    Str(#[error(not(source))] &'static str),
+    #[display(fmt = "{}", _0)]
+    MimeFromStr(mime::FromStrError),
+    #[display(fmt = "{}", _0)]
+    IoError(io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_error_positions() {
+        // The position is scraped from the message of the SPARQL parser:
+        // this fails if an upgrade of Oxigraph changes its wording.
+        let err = sparql::Query::parse("SELECT *\nWHERE { ?s ?p }", None).unwrap_err();
+        assert!(err.to_string().starts_with("error at 2:16: "));
+        assert_eq!(parse_error_position(&err), Some((2, 16)));
+        let err = sparql::Query::parse("SELECT", None).unwrap_err();
+        assert_eq!(parse_error_position(&err), Some((1, 7)));
+    }
+}
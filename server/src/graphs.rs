@@ -0,0 +1,191 @@
+use crate::error::AppError;
+use crate::negotiation::{negotiate, GraphSyntax};
+use crate::stats::count_named_graphs;
+use crate::{jsonld, AppState, GraphCount};
+use actix_web::{http, web, HttpRequest, HttpResponse};
+use oxigraph::io::{GraphFormat, GraphSerializer};
+use oxigraph::model::vocab::{rdf, xsd};
+use oxigraph::model::{BlankNode, Literal, NamedNode, NamedNodeRef, NamedOrBlankNode, Triple};
+use std::io::{self, Write};
+
+const SD: &str = "http://www.w3.org/ns/sparql-service-description#";
+const VOID_TRIPLES: &str = "http://rdfs.org/ns/void#triples";
+
+/// Lists the named graphs and their quad counts, as JSON or as an RDF
+/// `sd:GraphCollection`.
+pub(crate) async fn get_graphs(
+    request: HttpRequest,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let media_type = negotiate(
+        request.headers(),
+        &[
+            "application/json",
+            GraphFormat::Turtle.media_type(),
+            GraphFormat::NTriples.media_type(),
+            GraphFormat::RdfXml.media_type(),
+        ],
+    )?;
+    match GraphFormat::from_media_type(media_type) {
+        None => {
+            let graphs = state.run(|store| Ok(GraphCount::list(store)?)).await?;
+            Ok(HttpResponse::Ok().json(graphs))
+        }
+        Some(format) => {
+            let graphs = state.run(|store| Ok(count_named_graphs(store)?)).await?;
+            let mut body = Vec::new();
+            write_description(&graphs, format, &mut body)?;
+            Ok(HttpResponse::Ok()
+                .header(http::header::CONTENT_TYPE, format.media_type())
+                .body(body))
+        }
+    }
+}
+
+/// Describes `graphs` with the SPARQL Service Description vocabulary:
+///
+/// ```turtle
+/// [] a sd:GraphCollection ;
+///     sd:namedGraph [ a sd:NamedGraph ; sd:name <g> ;
+///         sd:graph [ a sd:Graph ; void:triples 42 ] ] .
+/// ```
+fn write_description(
+    graphs: &[(NamedOrBlankNode, usize)],
+    format: GraphFormat,
+    out: impl Write,
+) -> io::Result<()> {
+    let collection = BlankNode::default();
+    let mut triples = vec![Triple::new(
+        collection.clone(),
+        rdf::TYPE,
+        sd("GraphCollection"),
+    )];
+    describe_named_graphs(&collection, graphs, &mut triples);
+    write_triples(&triples, GraphSyntax::Format(format), None, out)
+}
+
+/// Adds an `sd:namedGraph` of `subject` for each of `graphs`.
+///
+/// `sd:name` has to be an IRI, so graphs named by a blank node are left out.
+pub(crate) fn describe_named_graphs(
+    subject: &BlankNode,
+    graphs: &[(NamedOrBlankNode, usize)],
+    triples: &mut Vec<Triple>,
+) {
+    for (graph, quads) in graphs {
+        let graph = match graph {
+            NamedOrBlankNode::NamedNode(graph) => graph,
+            NamedOrBlankNode::BlankNode(_) => continue,
+        };
+        let named_graph = BlankNode::default();
+        triples.push(Triple::new(
+            subject.clone(),
+            sd("namedGraph"),
+            named_graph.clone(),
+        ));
+        triples.push(Triple::new(
+            named_graph.clone(),
+            rdf::TYPE,
+            sd("NamedGraph"),
+        ));
+        triples.push(Triple::new(named_graph.clone(), sd("name"), graph.clone()));
+        let description = describe_graph(*quads, triples);
+        triples.push(Triple::new(named_graph, sd("graph"), description));
+    }
+}
+
+/// Adds an `sd:Graph` holding `quads` quads and returns its node.
+pub(crate) fn describe_graph(quads: usize, triples: &mut Vec<Triple>) -> BlankNode {
+    let description = BlankNode::default();
+    triples.push(Triple::new(description.clone(), rdf::TYPE, sd("Graph")));
+    triples.push(Triple::new(
+        description.clone(),
+        NamedNodeRef::new_unchecked(VOID_TRIPLES),
+        Literal::new_typed_literal(quads.to_string(), xsd::INTEGER),
+    ));
+    description
+}
+
+/// A term of the SPARQL Service Description vocabulary.
+pub(crate) fn sd(name: &str) -> NamedNode {
+    NamedNode::new_unchecked(format!("{}{}", SD, name))
+}
+
+/// Writes `triples` in `syntax`, JSON-LD being compacted against `context`
+/// if given.
+pub(crate) fn write_triples(
+    triples: &[Triple],
+    syntax: GraphSyntax,
+    context: Option<&jsonld::Context>,
+    out: impl Write,
+) -> io::Result<()> {
+    match syntax {
+        GraphSyntax::Format(format) => {
+            let mut writer = GraphSerializer::from_format(format).triple_writer(out)?;
+            for triple in triples {
+                writer.write(triple)?;
+            }
+            writer.finish()
+        }
+        GraphSyntax::JsonLd => jsonld::write_triples(triples, context, out),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_app;
+    use actix_web::{test, App};
+    use oxigraph::io::GraphParser;
+    use oxigraph::SledStore;
+    use tempfile::tempdir;
+
+    async fn get(accept: &str) -> (String, String) {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        store
+            .update("INSERT DATA { GRAPH <http://e/g> { <http://e/s> <http://e/p> 1, 2 } }")
+            .unwrap();
+        let app_state = web::Data::new(AppState::new(store));
+        let mut app = test::init_service(App::new().configure(config_app(app_state))).await;
+        let req = test::TestRequest::get()
+            .uri("/graphs")
+            .header("Accept", accept)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let content_type = resp
+            .headers()
+            .get(http::header::CONTENT_TYPE)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = test::read_body(resp).await;
+        (content_type, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[actix_rt::test]
+    async fn get_graphs_json() {
+        let (content_type, body) = get("application/json").await;
+        assert_eq!(content_type, "application/json");
+        assert_eq!(body, r#"[{"graph":"http://e/g","quads":2}]"#);
+    }
+
+    #[actix_rt::test]
+    async fn get_graphs_rdf() {
+        let (content_type, body) = get("text/turtle").await;
+        assert_eq!(content_type, "text/turtle");
+        let triples = GraphParser::from_format(GraphFormat::Turtle)
+            .read_triples(body.as_bytes())
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(triples.len(), 7);
+        assert!(triples.iter().any(|t| t.predicate.as_str() == VOID_TRIPLES
+            && t.object.to_string() == "\"2\"^^<http://www.w3.org/2001/XMLSchema#integer>"));
+        assert!(triples
+            .iter()
+            .any(|t| t.object.to_string() == "<http://e/g>"));
+    }
+}
@@ -0,0 +1,126 @@
+use crate::resource::Namespace;
+use oxigraph::model::Term;
+use std::fmt::Write;
+
+pub(crate) const MEDIA_TYPE: &str = "text/html; charset=utf-8";
+
+/// What the pages may load and run: nothing but their own inline style.
+pub(crate) const CONTENT_SECURITY_POLICY: &str = "default-src 'none'; style-src 'unsafe-inline'";
+
+/// Escapes `text` for HTML content and attribute values.
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The start of a page titled `title`, up to the opening of its body.
+pub(crate) fn page_start(title: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>table {{ border-collapse: collapse }} th, td {{ border: 1px solid #ccc; \
+         padding: 0.2em 0.5em; text-align: left; vertical-align: top }}</style>\n\
+         </head>\n<body>\n",
+        escape(title)
+    )
+}
+
+/// What closes a page opened by [`page_start`].
+pub(crate) const PAGE_END: &str = "</body>\n</html>\n";
+
+/// A complete page titled `title` around the HTML `body`.
+pub(crate) fn page(title: &str, body: &str) -> String {
+    format!("{}{}{}", page_start(title), body, PAGE_END)
+}
+
+/// A table with a header row, its cells already in HTML.
+pub(crate) fn table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut table = table_start(header);
+    for row in rows {
+        table.push_str(&table_row(row));
+    }
+    table.push_str(TABLE_END);
+    table
+}
+
+/// The start of a table, up to its header row included.
+pub(crate) fn table_start(header: &[&str]) -> String {
+    let mut table = String::from("<table>\n<tr>");
+    for name in header {
+        let _ = write!(table, "<th>{}</th>", escape(name));
+    }
+    table.push_str("</tr>\n");
+    table
+}
+
+/// A row of a table, its cells already in HTML.
+pub(crate) fn table_row(cells: &[String]) -> String {
+    let mut row = String::from("<tr>");
+    for cell in cells {
+        let _ = write!(row, "<td>{}</td>", cell);
+    }
+    row.push_str("</tr>\n");
+    row
+}
+
+/// What closes a table opened by [`table_start`].
+pub(crate) const TABLE_END: &str = "</table>\n";
+
+/// `term` in N-Triples syntax, with IRIs linking to their view if they are
+/// in `namespace`, else to themselves if they are HTTP(S) URLs.
+pub(crate) fn term(term: &Term, namespace: &Namespace) -> String {
+    match term {
+        Term::NamedNode(node) => match namespace.href(node.as_str()) {
+            Some(href) => format!(
+                "<a href=\"{}\">{}</a>",
+                escape(&href),
+                escape(node.as_str())
+            ),
+            None => escape(&term.to_string()),
+        },
+        term => escape(&term.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxigraph::model::{Literal, NamedNode};
+
+    #[test]
+    fn terms() {
+        let namespace = Namespace::new(
+            "http://localhost/resource".into(),
+            vec!["http://localhost/id/".into()],
+        );
+        let iri = NamedNode::new("http://localhost/id/a?b=c&d").unwrap();
+        assert_eq!(
+            term(&iri.into(), &namespace),
+            "<a href=\"http://localhost/resource?iri=http%3A%2F%2Flocalhost%2Fid%2Fa%3Fb%3Dc%26d\">\
+             http://localhost/id/a?b=c&amp;d</a>"
+        );
+        let iri = NamedNode::new("http://e/a?b=c&d").unwrap();
+        assert_eq!(
+            term(&iri.into(), &namespace),
+            "<a href=\"http://e/a?b=c&amp;d\">http://e/a?b=c&amp;d</a>"
+        );
+        let iri = NamedNode::new("javascript:alert(document.cookie)").unwrap();
+        assert_eq!(
+            term(&iri.into(), &namespace),
+            "&lt;javascript:alert(document.cookie)&gt;"
+        );
+        assert_eq!(
+            term(&Literal::new_simple_literal("<b>").into(), &namespace),
+            "&quot;&lt;b&gt;&quot;"
+        );
+    }
+}
@@ -0,0 +1,1094 @@
+//! The subset of JSON-LD 1.1 knowgraf reads and writes.
+//!
+//! Documents are read with local contexts only: `@base`, `@vocab`,
+//! `@language` and term definitions with `@id`, `@type` and `@language`.
+//! Remote contexts, `@reverse`, scoped contexts and containers other than
+//! `@set` are rejected rather than misread.
+//!
+//! Documents are written flattened, a node object per subject, either
+//! expanded or compacted against a [`Context`].
+//!
+//! The server stores a single context, read from `jsonld_context` at
+//! startup, that answers are compacted against unless the request gives its
+//! own `context`. It is shared by the whole dataset: graphs have no context
+//! of their own, and the `@context` of an upload is only used to read it.
+//! Relative IRIs in uploads resolve against the graph IRI, or the request
+//! URL for the default graph and datasets.
+use oxigraph::model::vocab::{rdf, xsd};
+use oxigraph::model::{
+    BlankNode, GraphName, Literal, NamedNode, NamedOrBlankNode, Quad, Term, Triple,
+};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Read, Write};
+
+pub const MEDIA_TYPE: &str = "application/ld+json";
+
+/// A JSON-LD context, to read documents with or to compact them against.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Context {
+    /// The context as given, which compacted documents refer to.
+    value: Value,
+    base: Option<String>,
+    vocab: Option<String>,
+    language: Option<String>,
+    terms: BTreeMap<String, TermDefinition>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct TermDefinition {
+    id: String,
+    /// `@id`, `@vocab` or the IRI of the datatype values are coerced to.
+    type_: Option<String>,
+    language: Option<String>,
+}
+
+impl Context {
+    /// Reads a context, given either as is or as a document with an
+    /// `@context` entry.
+    pub fn parse(value: &Value) -> io::Result<Context> {
+        let value = value.get("@context").unwrap_or(value);
+        let mut context = Context::default().merged(value)?;
+        context.value = value.clone();
+        Ok(context)
+    }
+
+    pub fn from_reader(reader: impl Read) -> io::Result<Context> {
+        Context::parse(&serde_json::from_reader(reader).map_err(invalid)?)
+    }
+
+    /// The context as it was given, without any `@context` wrapper.
+    pub fn as_json(&self) -> &Value {
+        &self.value
+    }
+
+    /// This context updated by the local context `local`.
+    fn merged(&self, local: &Value) -> io::Result<Context> {
+        match local {
+            Value::Null => Ok(Context {
+                base: self.base.clone(),
+                ..Context::default()
+            }),
+            Value::Array(locals) => {
+                let mut context = self.clone();
+                for local in locals {
+                    context = context.merged(local)?;
+                }
+                Ok(context)
+            }
+            Value::Object(definitions) => {
+                let mut context = self.clone();
+                context.define(definitions)?;
+                Ok(context)
+            }
+            Value::String(iri) => Err(invalid(format!(
+                "the remote context {} can't be loaded",
+                iri
+            ))),
+            local => Err(invalid(format!("{} is not a context", local))),
+        }
+    }
+
+    fn define(&mut self, definitions: &Map<String, Value>) -> io::Result<()> {
+        let keyword = |key: &str| match definitions.get(key) {
+            None => Ok(None),
+            Some(Value::Null) => Ok(Some(None)),
+            Some(Value::String(value)) => Ok(Some(Some(value.clone()))),
+            Some(value) => Err(invalid(format!("{} can't be {}", key, value))),
+        };
+        if let Some(base) = keyword("@base")? {
+            self.base = match (base, &self.base) {
+                (Some(base), Some(current)) => Some(resolve(current, &base)),
+                (base, _) => base,
+            };
+        }
+        if let Some(language) = keyword("@language")? {
+            self.language = language;
+        }
+        if let Some(vocab) = keyword("@vocab")? {
+            self.vocab = vocab.map(|vocab| self.expand_iri(&vocab, true).unwrap_or(vocab));
+        }
+
+        // Definitions may use each other as prefixes, so they are all known
+        // before any of them is expanded.
+        let mut definitions = definitions
+            .iter()
+            .filter(|(term, _)| !term.starts_with('@'))
+            .map(|(term, definition)| Ok((term, read_definition(term, definition)?)))
+            .collect::<io::Result<Vec<_>>>()?;
+        for (term, definition) in &definitions {
+            match definition {
+                Some((id, _, language)) => {
+                    self.terms.insert(
+                        term.to_string(),
+                        TermDefinition {
+                            id: id.clone().unwrap_or_else(|| term.to_string()),
+                            type_: None,
+                            language: language.clone(),
+                        },
+                    );
+                }
+                None => {
+                    self.terms.remove(*term);
+                }
+            }
+        }
+        let mut expanded = Vec::new();
+        for (term, definition) in definitions.drain(..) {
+            if let Some((id, type_, _)) = definition {
+                let id = match id {
+                    Some(id) if &id != term => self.expand_iri(&id, true),
+                    _ => self.expand_term_itself(term),
+                }
+                .ok_or_else(|| invalid(format!("the term {} has no IRI", term)))?;
+                let type_ = match type_ {
+                    Some(type_) if type_ == "@id" || type_ == "@vocab" => Some(type_),
+                    Some(type_) => Some(
+                        self.expand_iri(&type_, true)
+                            .ok_or_else(|| invalid(format!("the datatype {}", type_)))?,
+                    ),
+                    None => None,
+                };
+                expanded.push((term, id, type_));
+            }
+        }
+        for (term, id, type_) in expanded {
+            if let Some(definition) = self.terms.get_mut(term) {
+                definition.id = id;
+                definition.type_ = type_;
+            }
+        }
+        Ok(())
+    }
+
+    /// The IRI of a term defined without `@id`: itself if it is a compact or
+    /// absolute IRI, else relative to `@vocab`.
+    fn expand_term_itself(&self, term: &str) -> Option<String> {
+        match term.split_once(':') {
+            Some((prefix, suffix)) => match self.terms.get(prefix) {
+                Some(definition) if definition.id != term => {
+                    Some(format!("{}{}", definition.id, suffix))
+                }
+                _ => Some(term.to_string()),
+            },
+            None => self
+                .vocab
+                .as_ref()
+                .map(|vocab| format!("{}{}", vocab, term)),
+        }
+    }
+
+    /// Expands a term, a compact IRI or a relative reference. Properties and
+    /// types (`vocab`) may be terms and are relative to `@vocab`, node
+    /// identifiers are relative to `@base`.
+    fn expand_iri(&self, value: &str, vocab: bool) -> Option<String> {
+        if value.starts_with('@') {
+            return None;
+        }
+        if vocab {
+            if let Some(definition) = self.terms.get(value) {
+                return Some(definition.id.clone());
+            }
+        }
+        if let Some((prefix, suffix)) = value.split_once(':') {
+            if prefix == "_" || suffix.starts_with("//") {
+                return Some(value.to_string());
+            }
+            return Some(match self.terms.get(prefix) {
+                Some(definition) => format!("{}{}", definition.id, suffix),
+                None => value.to_string(),
+            });
+        }
+        if vocab {
+            self.vocab
+                .as_ref()
+                .map(|vocab| format!("{}{}", vocab, value))
+        } else {
+            self.base.as_ref().map(|base| resolve(base, value))
+        }
+    }
+
+    /// Compacts `iri` to a term or a compact IRI. Properties and types
+    /// (`vocab`) may also be made relative to `@vocab`.
+    fn compact_iri(&self, iri: &str, vocab: bool) -> String {
+        if vocab {
+            let term = self.terms.iter().find(|(_, definition)| {
+                definition.id == iri && definition.type_.is_none() && definition.language.is_none()
+            });
+            if let Some((term, _)) = term {
+                return term.clone();
+            }
+            if let Some(suffix) = self
+                .vocab
+                .as_deref()
+                .and_then(|vocab| iri.strip_prefix(vocab))
+            {
+                if !suffix.is_empty() && !suffix.contains(':') && !self.terms.contains_key(suffix) {
+                    return suffix.to_string();
+                }
+            }
+        }
+        let prefix = self
+            .terms
+            .iter()
+            .filter(|(term, definition)| {
+                !term.contains(':')
+                    && definition.id.ends_with(&['/', '#', ':'][..])
+                    && iri.len() > definition.id.len()
+                    && iri.starts_with(definition.id.as_str())
+                    && !iri[definition.id.len()..].starts_with("//")
+            })
+            .max_by_key(|(_, definition)| definition.id.len());
+        match prefix {
+            Some((term, definition)) => format!("{}:{}", term, &iri[definition.id.len()..]),
+            None => iri.to_string(),
+        }
+    }
+
+    /// Compacts an expanded node object.
+    fn compact_node(&self, node: Map<String, Value>) -> Map<String, Value> {
+        let mut compacted = BTreeMap::<String, Vec<Value>>::new();
+        for (key, values) in node {
+            let values = match values {
+                Value::Array(values) => values,
+                value => vec![value],
+            };
+            for value in values {
+                let (key, value) = match (key.as_str(), value) {
+                    ("@id", Value::String(id)) => {
+                        ("@id".to_string(), self.compact_iri(&id, false).into())
+                    }
+                    ("@type", Value::String(class)) => {
+                        ("@type".to_string(), self.compact_iri(&class, true).into())
+                    }
+                    (property, value) => self.compact_value(property, value),
+                };
+                compacted.entry(key).or_default().push(value);
+            }
+        }
+        compacted
+            .into_iter()
+            .map(|(key, mut values)| {
+                if values.len() == 1 {
+                    (key, values.remove(0))
+                } else {
+                    (key, Value::Array(values))
+                }
+            })
+            .collect()
+    }
+
+    /// The key and compacted form of a value of `property`: a term whose
+    /// coercion fits the value if there is one, else the compacted IRI of
+    /// `property` with the value object as is.
+    fn compact_value(&self, property: &str, value: Value) -> (String, Value) {
+        let id = value.get("@id").and_then(Value::as_str);
+        let datatype = value.get("@type").and_then(Value::as_str);
+        let language = value.get("@language").and_then(Value::as_str);
+        for (term, definition) in &self.terms {
+            if definition.id != property {
+                continue;
+            }
+            let compacted = match (definition.type_.as_deref(), &definition.language) {
+                (Some("@id"), _) => id.map(|id| self.compact_iri(id, false).into()),
+                (Some("@vocab"), _) => id.map(|id| self.compact_iri(id, true).into()),
+                (Some(type_), _) if datatype == Some(type_) => Some(value["@value"].clone()),
+                (None, Some(term_language)) if language == Some(term_language) => {
+                    Some(value["@value"].clone())
+                }
+                (None, None) => Some(self.compact_value_object(value.clone())),
+                _ => None,
+            };
+            if let Some(compacted) = compacted {
+                return (term.clone(), compacted);
+            }
+        }
+        (
+            self.compact_iri(property, true),
+            self.compact_value_object(value),
+        )
+    }
+
+    /// Compacts the IRIs of a value object, or the object to its string if
+    /// that reads back the same.
+    fn compact_value_object(&self, mut value: Value) -> Value {
+        if let Some(id) = value.get("@id").and_then(Value::as_str) {
+            return json!({ "@id": self.compact_iri(id, false) });
+        }
+        if let Some(datatype) = value.get("@type").and_then(Value::as_str) {
+            value["@type"] = self.compact_iri(datatype, true).into();
+        } else if value.get("@language").and_then(Value::as_str) == self.language.as_deref() {
+            return value["@value"].clone();
+        }
+        value
+    }
+}
+
+/// What a term definition gives: `@id`, `@type` and `@language`, or `None`
+/// if it removes the term.
+#[allow(clippy::type_complexity)]
+fn read_definition(
+    term: &str,
+    definition: &Value,
+) -> io::Result<Option<(Option<String>, Option<String>, Option<String>)>> {
+    match definition {
+        Value::Null => Ok(None),
+        Value::String(id) => Ok(Some((Some(id.clone()), None, None))),
+        Value::Object(definition) => {
+            for key in &["@reverse", "@context", "@nest", "@prefix"] {
+                if definition.contains_key(*key) {
+                    return Err(invalid(format!("{} in the definition of {}", key, term)));
+                }
+            }
+            match definition.get("@container") {
+                None | Some(Value::Null) => {}
+                Some(Value::String(container)) if container == "@set" => {}
+                Some(container) => {
+                    return Err(invalid(format!("the container {} of {}", container, term)))
+                }
+            }
+            let string = |key: &str| {
+                definition
+                    .get(key)
+                    .and_then(Value::as_str)
+                    .map(String::from)
+            };
+            Ok(Some((string("@id"), string("@type"), string("@language"))))
+        }
+        definition => Err(invalid(format!(
+            "{} is not a definition of {}",
+            definition, term
+        ))),
+    }
+}
+
+/// Resolves the reference `value` against `base`, enough for the paths,
+/// queries and fragments found in documents. Dot segments are left alone.
+fn resolve(base: &str, value: &str) -> String {
+    if value.contains(':') && !value.starts_with(['/', '?', '#']) {
+        return value.to_string();
+    }
+    let base = base.split('#').next().unwrap_or(base);
+    if value.is_empty() || value.starts_with('#') {
+        return format!("{}{}", base, value);
+    }
+    let base = base.split('?').next().unwrap_or(base);
+    if value.starts_with('?') {
+        return format!("{}{}", base, value);
+    }
+    let authority = match base.find("://") {
+        Some(scheme) => base[scheme + 3..]
+            .find('/')
+            .map_or(base.len(), |path| scheme + 3 + path),
+        None => 0,
+    };
+    if value.starts_with("//") {
+        let scheme = base.find(':').map_or(0, |colon| colon + 1);
+        return format!("{}{}", &base[..scheme], value);
+    }
+    if value.starts_with('/') {
+        return format!("{}{}", &base[..authority], value);
+    }
+    match base[authority..].rfind('/') {
+        Some(slash) => format!("{}{}", &base[..=authority + slash], value),
+        None => format!("{}/{}", &base[..authority], value),
+    }
+}
+
+fn invalid(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Reads the JSON-LD document of `reader` into quads. Relative IRIs are
+/// resolved against `base_iri` unless the document sets its own `@base`.
+pub fn parse(reader: impl Read, base_iri: Option<&str>) -> io::Result<Vec<Quad>> {
+    let document: Value = serde_json::from_reader(reader).map_err(invalid)?;
+    let context = Context {
+        base: base_iri.map(String::from),
+        ..Context::default()
+    };
+    let mut parser = Parser::default();
+    for node in as_array(&document) {
+        parser.top_node(node, &context)?;
+    }
+    Ok(parser.quads)
+}
+
+#[derive(Default)]
+struct Parser {
+    quads: Vec<Quad>,
+    blank_nodes: HashMap<String, BlankNode>,
+}
+
+impl Parser {
+    fn top_node(&mut self, node: &Value, context: &Context) -> io::Result<()> {
+        let object = node
+            .as_object()
+            .ok_or_else(|| invalid(format!("{} is not a node object", node)))?;
+        // Without other entries, `@graph` holds nodes of the default graph.
+        if object.contains_key("@graph")
+            && object
+                .keys()
+                .all(|key| key == "@context" || key == "@graph")
+        {
+            let context = match object.get("@context") {
+                Some(local) => context.merged(local)?,
+                None => context.clone(),
+            };
+            for node in as_array(&object["@graph"]) {
+                self.node(node, &context, &GraphName::DefaultGraph)?;
+            }
+            Ok(())
+        } else {
+            self.node(node, context, &GraphName::DefaultGraph)?;
+            Ok(())
+        }
+    }
+
+    /// Reads a node object into `graph` and returns its subject.
+    fn node(
+        &mut self,
+        node: &Value,
+        context: &Context,
+        graph: &GraphName,
+    ) -> io::Result<NamedOrBlankNode> {
+        let object = node
+            .as_object()
+            .ok_or_else(|| invalid(format!("{} is not a node object", node)))?;
+        let context = match object.get("@context") {
+            Some(local) => context.merged(local)?,
+            None => context.clone(),
+        };
+        let subject = match object.get("@id") {
+            Some(Value::String(id)) => self.resource(id, &context, false)?,
+            Some(id) => return Err(invalid(format!("the @id {} is not a string", id))),
+            None => BlankNode::default().into(),
+        };
+        for (key, value) in object {
+            match key.as_str() {
+                "@context" | "@id" | "@index" => {}
+                "@type" => {
+                    for class in as_array(value) {
+                        let class = class
+                            .as_str()
+                            .ok_or_else(|| invalid(format!("the @type {}", class)))?;
+                        let class = self.resource(class, &context, true)?;
+                        self.push(&subject, rdf::TYPE.into(), class.into(), graph);
+                    }
+                }
+                "@graph" => {
+                    let named = GraphName::from(subject.clone());
+                    for node in as_array(value) {
+                        self.node(node, &context, &named)?;
+                    }
+                }
+                "@included" => {
+                    for node in as_array(value) {
+                        self.node(node, &context, graph)?;
+                    }
+                }
+                key if key.starts_with('@') => {
+                    return Err(invalid(format!("{} in a node object", key)));
+                }
+                key => {
+                    // As JSON-LD processors do, keys that don't expand to
+                    // an IRI are dropped.
+                    let predicate = match context.expand_iri(key, true) {
+                        Some(iri) if !iri.starts_with("_:") => match NamedNode::new(iri) {
+                            Ok(predicate) => predicate,
+                            Err(_) => continue,
+                        },
+                        _ => continue,
+                    };
+                    let definition = context.terms.get(key);
+                    for item in as_array(value) {
+                        if let Some(object) = self.value(item, &context, definition, graph)? {
+                            self.push(&subject, predicate.clone(), object, graph);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(subject)
+    }
+
+    fn value(
+        &mut self,
+        value: &Value,
+        context: &Context,
+        definition: Option<&TermDefinition>,
+        graph: &GraphName,
+    ) -> io::Result<Option<Term>> {
+        let coercion = definition.and_then(|definition| definition.type_.as_deref());
+        Ok(Some(match value {
+            Value::Null => return Ok(None),
+            Value::String(text) => match coercion {
+                Some("@id") => self.resource(text, context, false)?.into(),
+                Some("@vocab") => self.resource(text, context, true)?.into(),
+                Some(datatype) => typed(text, datatype)?.into(),
+                None => {
+                    let language = match definition {
+                        Some(definition) if definition.language.is_some() => {
+                            definition.language.as_ref()
+                        }
+                        _ => context.language.as_ref(),
+                    };
+                    match language {
+                        Some(language) => Literal::new_language_tagged_literal(text, language)
+                            .map_err(invalid)?
+                            .into(),
+                        None => Literal::new_simple_literal(text).into(),
+                    }
+                }
+            },
+            Value::Number(_) | Value::Bool(_) => native(value, coercion)?.into(),
+            Value::Array(_) => return Err(invalid("lists of lists aren't supported")),
+            Value::Object(object) if object.contains_key("@value") => {
+                let datatype = match object.get("@type").and_then(Value::as_str) {
+                    Some(datatype) => Some(
+                        context
+                            .expand_iri(datatype, true)
+                            .ok_or_else(|| invalid(format!("the datatype {}", datatype)))?,
+                    ),
+                    None => None,
+                };
+                let language = object.get("@language").and_then(Value::as_str);
+                match (&object["@value"], datatype, language) {
+                    (Value::Null, _, _) => return Ok(None),
+                    (Value::String(text), Some(datatype), _) => typed(text, &datatype)?.into(),
+                    (Value::String(text), None, Some(language)) => {
+                        Literal::new_language_tagged_literal(text, language)
+                            .map_err(invalid)?
+                            .into()
+                    }
+                    (Value::String(text), None, None) => Literal::new_simple_literal(text).into(),
+                    (value, datatype, _) => native(value, datatype.as_deref())?.into(),
+                }
+            }
+            Value::Object(object) if object.contains_key("@list") => {
+                let mut items = Vec::new();
+                for item in as_array(&object["@list"]) {
+                    if let Some(item) = self.value(item, context, definition, graph)? {
+                        items.push(item);
+                    }
+                }
+                let mut list = Term::from(rdf::NIL);
+                for item in items.into_iter().rev() {
+                    let node = NamedOrBlankNode::from(BlankNode::default());
+                    self.push(&node, rdf::FIRST.into(), item, graph);
+                    self.push(&node, rdf::REST.into(), list, graph);
+                    list = node.into();
+                }
+                list
+            }
+            Value::Object(object) if object.contains_key("@set") => {
+                return Err(invalid("@set objects aren't supported"));
+            }
+            Value::Object(_) => self.node(value, context, graph)?.into(),
+        }))
+    }
+
+    fn resource(
+        &mut self,
+        id: &str,
+        context: &Context,
+        vocab: bool,
+    ) -> io::Result<NamedOrBlankNode> {
+        if let Some(label) = id.strip_prefix("_:") {
+            return Ok(self
+                .blank_nodes
+                .entry(label.to_string())
+                .or_default()
+                .clone()
+                .into());
+        }
+        let iri = context
+            .expand_iri(id, vocab)
+            .ok_or_else(|| invalid(format!("{} can't be expanded to an IRI", id)))?;
+        Ok(NamedNode::new(iri).map_err(invalid)?.into())
+    }
+
+    fn push(
+        &mut self,
+        subject: &NamedOrBlankNode,
+        predicate: NamedNode,
+        object: Term,
+        graph: &GraphName,
+    ) {
+        self.quads
+            .push(Quad::new(subject.clone(), predicate, object, graph.clone()));
+    }
+}
+
+fn as_array(value: &Value) -> &[Value] {
+    match value {
+        Value::Array(values) => values,
+        value => std::slice::from_ref(value),
+    }
+}
+
+fn typed(text: &str, datatype: &str) -> io::Result<Literal> {
+    Ok(Literal::new_typed_literal(
+        text,
+        NamedNode::new(datatype).map_err(invalid)?,
+    ))
+}
+
+/// The literal of a JSON number or boolean, as JSON-LD maps them: numbers
+/// with a fractional part, from 10^21 or typed `xsd:double` are doubles, the
+/// others integers, whether written `1` or `1.0`.
+fn native(value: &Value, datatype: Option<&str>) -> io::Result<Literal> {
+    let (lexical, default) = match value {
+        Value::Bool(value) => (value.to_string(), xsd::BOOLEAN),
+        Value::Number(number) if number.is_f64() || datatype == Some(xsd::DOUBLE.as_str()) => {
+            let double = number.as_f64().unwrap_or_default();
+            if double.fract() != 0.
+                || double.abs() >= 1e21
+                || datatype == Some(xsd::DOUBLE.as_str())
+            {
+                (canonical_double(double), xsd::DOUBLE)
+            } else {
+                // Adding zero turns -0 into 0.
+                (format!("{:.0}", double + 0.), xsd::INTEGER)
+            }
+        }
+        Value::Number(number) => (number.to_string(), xsd::INTEGER),
+        value => return Err(invalid(format!("{} is not a literal", value))),
+    };
+    match datatype {
+        Some(datatype) if !datatype.starts_with('@') => typed(&lexical, datatype),
+        _ => Ok(Literal::new_typed_literal(lexical, default)),
+    }
+}
+
+/// `double` in the canonical form of `xsd:double`, such as `1.0E0`.
+fn canonical_double(double: f64) -> String {
+    let lexical = format!("{:E}", double);
+    match lexical.split_once('E') {
+        Some((mantissa, exponent)) if !mantissa.contains('.') => {
+            format!("{}.0E{}", mantissa, exponent)
+        }
+        _ => lexical,
+    }
+}
+
+/// Writes quads as a JSON-LD document, a node object per line.
+///
+/// Consecutive quads with the same subject make up one node object, and
+/// consecutive quads of the same named graph one graph object, so nothing is
+/// buffered beyond the current subject. Store dumps come grouped that way;
+/// other quads give several node objects for a subject, which is still
+/// valid.
+pub struct JsonLdWriter<W: Write> {
+    out: W,
+    context: Option<Context>,
+    /// The graph of the last quad.
+    graph: GraphName,
+    node: Option<(NamedOrBlankNode, Map<String, Value>)>,
+    /// Whether nothing was written yet at the top level, or in the
+    /// current named graph.
+    empty: bool,
+    empty_graph: bool,
+}
+
+impl<W: Write> JsonLdWriter<W> {
+    /// Starts a document, compacted against `context` if given.
+    pub fn new(mut out: W, context: Option<&Context>) -> io::Result<Self> {
+        match context {
+            Some(context) => write!(
+                out,
+                "{{\"@context\": {},\n\"@graph\": [",
+                serde_json::to_string(&context.value)?
+            )?,
+            None => write!(out, "[")?,
+        }
+        Ok(JsonLdWriter {
+            out,
+            context: context.cloned(),
+            graph: GraphName::DefaultGraph,
+            node: None,
+            empty: true,
+            empty_graph: true,
+        })
+    }
+
+    pub fn write_triple(&mut self, triple: &Triple) -> io::Result<()> {
+        self.write(
+            &triple.subject,
+            &triple.predicate,
+            &triple.object,
+            &GraphName::DefaultGraph,
+        )
+    }
+
+    pub fn write_quad(&mut self, quad: &Quad) -> io::Result<()> {
+        self.write(
+            &quad.subject,
+            &quad.predicate,
+            &quad.object,
+            &quad.graph_name,
+        )
+    }
+
+    fn write(
+        &mut self,
+        subject: &NamedOrBlankNode,
+        predicate: &NamedNode,
+        object: &Term,
+        graph: &GraphName,
+    ) -> io::Result<()> {
+        if graph != &self.graph {
+            self.write_node()?;
+            self.close_graph()?;
+            let id = match graph {
+                GraphName::NamedNode(graph) => Some(graph.as_str().to_string()),
+                GraphName::BlankNode(graph) => Some(format!("_:{}", graph.as_str())),
+                GraphName::DefaultGraph => None,
+            };
+            if let Some(id) = id {
+                let id = match &self.context {
+                    Some(context) => context.compact_iri(&id, false),
+                    None => id,
+                };
+                self.separate(true)?;
+                write!(
+                    self.out,
+                    "\n{{\"@id\": {}, \"@graph\": [",
+                    serde_json::to_string(&id)?
+                )?;
+                self.empty_graph = true;
+            }
+            self.graph = graph.clone();
+        }
+        if self.node.as_ref().map(|(node, _)| node) != Some(subject) {
+            self.write_node()?;
+            let mut node = Map::new();
+            node.insert("@id".into(), id(subject).into());
+            self.node = Some((subject.clone(), node));
+        }
+        if let Some((_, node)) = &mut self.node {
+            let (key, value) = entry(predicate, object);
+            if let Value::Array(values) =
+                node.entry(key).or_insert_with(|| Value::Array(Vec::new()))
+            {
+                values.push(value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Ends the document and returns the output.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_node()?;
+        self.close_graph()?;
+        match self.context {
+            Some(_) => writeln!(self.out, "\n]}}")?,
+            None => writeln!(self.out, "\n]")?,
+        }
+        Ok(self.out)
+    }
+
+    fn write_node(&mut self) -> io::Result<()> {
+        if let Some((_, node)) = self.node.take() {
+            let node = match &self.context {
+                Some(context) => context.compact_node(node),
+                None => node,
+            };
+            self.separate(self.graph.is_default_graph())?;
+            write!(self.out, "\n{}", serde_json::to_string(&node)?)?;
+        }
+        Ok(())
+    }
+
+    fn close_graph(&mut self) -> io::Result<()> {
+        if !self.graph.is_default_graph() {
+            write!(self.out, "\n]}}")?;
+            self.graph = GraphName::DefaultGraph;
+        }
+        Ok(())
+    }
+
+    /// Writes the comma before an object at the top level or in a graph.
+    fn separate(&mut self, top: bool) -> io::Result<()> {
+        let empty = if top {
+            &mut self.empty
+        } else {
+            &mut self.empty_graph
+        };
+        if *empty {
+            *empty = false;
+            Ok(())
+        } else {
+            write!(self.out, ",")
+        }
+    }
+}
+
+/// Writes `triples` as flattened JSON-LD: an array with a node object per
+/// subject, in the order the subjects first appear, or a document compacted
+/// against `context`.
+pub(crate) fn write_triples<'a>(
+    triples: impl IntoIterator<Item = &'a Triple>,
+    context: Option<&Context>,
+    out: impl Write,
+) -> io::Result<()> {
+    let nodes = node_objects(triples);
+    match context {
+        Some(context) => {
+            let nodes = nodes
+                .into_iter()
+                .map(|node| Value::Object(context.compact_node(node)))
+                .collect();
+            serde_json::to_writer_pretty(
+                out,
+                &json!({ "@context": context.value, "@graph": Value::Array(nodes) }),
+            )?;
+        }
+        None => {
+            let nodes = nodes.into_iter().map(Value::Object).collect::<Vec<_>>();
+            serde_json::to_writer_pretty(out, &nodes)?;
+        }
+    }
+    Ok(())
+}
+
+fn node_objects<'a>(triples: impl IntoIterator<Item = &'a Triple>) -> Vec<Map<String, Value>> {
+    let mut nodes = Vec::<Map<String, Value>>::new();
+    let mut index = HashMap::<&NamedOrBlankNode, usize>::new();
+    for triple in triples {
+        let node = *index.entry(&triple.subject).or_insert_with(|| {
+            let mut node = Map::new();
+            node.insert("@id".into(), id(&triple.subject).into());
+            nodes.push(node);
+            nodes.len() - 1
+        });
+        let (key, value) = entry(&triple.predicate, &triple.object);
+        if let Value::Array(values) = nodes[node]
+            .entry(key)
+            .or_insert_with(|| Value::Array(Vec::new()))
+        {
+            values.push(value);
+        }
+    }
+    nodes
+}
+
+/// The key and the expanded value of a triple in its subject's node object.
+fn entry(predicate: &NamedNode, object: &Term) -> (String, Value) {
+    match object {
+        Term::NamedNode(class) if predicate == &rdf::TYPE => {
+            ("@type".to_string(), class.as_str().into())
+        }
+        Term::BlankNode(class) if predicate == &rdf::TYPE => {
+            ("@type".to_string(), format!("_:{}", class.as_str()).into())
+        }
+        object => (predicate.as_str().to_string(), value_object(object)),
+    }
+}
+
+fn id(node: &NamedOrBlankNode) -> String {
+    match node {
+        NamedOrBlankNode::NamedNode(node) => node.as_str().to_string(),
+        NamedOrBlankNode::BlankNode(node) => format!("_:{}", node.as_str()),
+    }
+}
+
+fn value_object(term: &Term) -> Value {
+    match term {
+        Term::NamedNode(node) => json!({ "@id": node.as_str() }),
+        Term::BlankNode(node) => json!({ "@id": format!("_:{}", node.as_str()) }),
+        Term::Literal(literal) => match literal.language() {
+            Some(language) => json!({ "@value": literal.value(), "@language": language }),
+            None if literal.datatype() == xsd::STRING => json!({ "@value": literal.value() }),
+            None => json!({ "@value": literal.value(), "@type": literal.datatype().as_str() }),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxigraph::io::{DatasetFormat, DatasetParser, GraphFormat, GraphParser};
+
+    #[test]
+    fn expanded_nodes() {
+        let triples = GraphParser::from_format(GraphFormat::Turtle)
+            .read_triples(
+                r#"@prefix e: <http://e/> .
+                   e:s a e:C ; e:p "v"@en, 1, e:o ; e:q [ e:p "w" ] ."#
+                    .as_bytes(),
+            )
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let nodes = node_objects(&triples);
+        assert_eq!(nodes.len(), 2);
+        let blank = nodes[0]["http://e/q"][0]["@id"].clone();
+        assert_eq!(
+            Value::Object(nodes[0].clone()),
+            json!({
+                "@id": "http://e/s",
+                "@type": ["http://e/C"],
+                "http://e/p": [
+                    { "@id": "http://e/o" },
+                    { "@value": "1", "@type": "http://www.w3.org/2001/XMLSchema#integer" },
+                    { "@value": "v", "@language": "en" }
+                ],
+                "http://e/q": [{ "@id": blank }]
+            })
+        );
+        assert_eq!(nodes[1]["http://e/p"], json!([{ "@value": "w" }]));
+    }
+
+    #[test]
+    fn numbers() {
+        let literal =
+            |value: Value, datatype: Option<&str>| native(&value, datatype).unwrap().to_string();
+        let integer = "<http://www.w3.org/2001/XMLSchema#integer>";
+        let double = "<http://www.w3.org/2001/XMLSchema#double>";
+        assert_eq!(literal(json!(1), None), format!("\"1\"^^{}", integer));
+        assert_eq!(literal(json!(1.0), None), format!("\"1\"^^{}", integer));
+        assert_eq!(literal(json!(-0.0), None), format!("\"0\"^^{}", integer));
+        assert_eq!(literal(json!(1.5), None), format!("\"1.5E0\"^^{}", double));
+        assert_eq!(
+            literal(json!(1e21), None),
+            format!("\"1.0E21\"^^{}", double)
+        );
+        assert_eq!(
+            literal(json!(2), Some(xsd::DOUBLE.as_str())),
+            format!("\"2.0E0\"^^{}", double)
+        );
+    }
+
+    const QUADS: &str = r#"<http://e/s> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <http://e/C> .
+<http://e/s> <http://e/name> "S"@en .
+<http://e/s> <http://e/knows> <http://e/t> .
+<http://e/s> <http://e/age> "3"^^<http://www.w3.org/2001/XMLSchema#integer> .
+<http://e/t> <http://e/name> "T" <http://e/g> .
+"#;
+
+    fn written(context: Option<&Context>) -> (Vec<Quad>, String) {
+        let quads = DatasetParser::from_format(DatasetFormat::NQuads)
+            .read_quads(QUADS.as_bytes())
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let mut writer = JsonLdWriter::new(Vec::new(), context).unwrap();
+        for quad in &quads {
+            writer.write_quad(quad).unwrap();
+        }
+        let out = String::from_utf8(writer.finish().unwrap()).unwrap();
+        (quads, out)
+    }
+
+    fn sorted(quads: Vec<Quad>) -> Vec<String> {
+        let mut quads = quads.iter().map(Quad::to_string).collect::<Vec<_>>();
+        quads.sort();
+        quads
+    }
+
+    #[test]
+    fn expanded_document() {
+        let (quads, out) = written(None);
+        assert_eq!(
+            out,
+            r#"[
+{"@id":"http://e/s","@type":["http://e/C"],"http://e/age":[{"@type":"http://www.w3.org/2001/XMLSchema#integer","@value":"3"}],"http://e/knows":[{"@id":"http://e/t"}],"http://e/name":[{"@language":"en","@value":"S"}]},
+{"@id": "http://e/g", "@graph": [
+{"@id":"http://e/t","http://e/name":[{"@value":"T"}]}
+]}
+]
+"#
+        );
+        assert_eq!(sorted(parse(out.as_bytes(), None).unwrap()), sorted(quads));
+    }
+
+    #[test]
+    fn compacted_document() {
+        let context = Context::parse(&json!({
+            "@context": {
+                "e": "http://e/",
+                "@vocab": "http://e/",
+                "knows": { "@id": "e:knows", "@type": "@id" },
+                "age": { "@id": "e:age", "@type": "http://www.w3.org/2001/XMLSchema#integer" }
+            }
+        }))
+        .unwrap();
+        let (quads, out) = written(Some(&context));
+        let document: Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(document["@context"]["e"], "http://e/");
+        assert_eq!(
+            document["@graph"][0],
+            json!({
+                "@id": "e:s",
+                "@type": "C",
+                "age": "3",
+                "knows": "e:t",
+                "name": { "@value": "S", "@language": "en" }
+            })
+        );
+        assert_eq!(document["@graph"][1]["@id"], "e:g");
+        assert_eq!(sorted(parse(out.as_bytes(), None).unwrap()), sorted(quads));
+    }
+
+    #[test]
+    fn parse_documents() {
+        let document = r#"{
+            "@context": {
+                "@base": "http://b/dir/",
+                "@language": "sv",
+                "e": "http://e/",
+                "list": { "@id": "e:list" },
+                "tags": { "@id": "e:tag", "@container": "@set" }
+            },
+            "@id": "doc",
+            "e:n": [1, 2.5, true],
+            "tags": ["a", { "@value": "b", "@language": "en" }],
+            "list": { "@list": ["x"] },
+            "e:child": { "e:name": "c" },
+            "e:nothing": null,
+            "undefined": "dropped"
+        }"#;
+        let quads = sorted(parse(document.as_bytes(), None).unwrap());
+        assert_eq!(quads.len(), 10, "{:#?}", quads);
+        for expected in &[
+            "<http://b/dir/doc> <http://e/n> \"1\"^^<http://www.w3.org/2001/XMLSchema#integer>",
+            "<http://b/dir/doc> <http://e/n> \"2.5E0\"^^<http://www.w3.org/2001/XMLSchema#double>",
+            "<http://b/dir/doc> <http://e/n> \"true\"^^<http://www.w3.org/2001/XMLSchema#boolean>",
+            "<http://b/dir/doc> <http://e/tag> \"a\"@sv",
+            "<http://b/dir/doc> <http://e/tag> \"b\"@en",
+            "<http://www.w3.org/1999/02/22-rdf-syntax-ns#first> \"x\"@sv",
+            "<http://e/name> \"c\"@sv",
+        ] {
+            assert!(
+                quads.iter().any(|quad| quad.contains(expected)),
+                "{} in {:#?}",
+                expected,
+                quads
+            );
+        }
+    }
+
+    #[test]
+    fn unsupported_documents() {
+        for document in &[
+            r#"{ "@context": "http://schema.org/", "name": "x" }"#,
+            r#"{ "@context": { "p": { "@reverse": "http://e/p" } } }"#,
+            r#"{ "@context": { "p": { "@id": "http://e/p", "@container": "@list" } } }"#,
+            r#"{ "@id": "relative", "http://e/p": "x" }"#,
+            r#"[1]"#,
+        ] {
+            assert!(parse(document.as_bytes(), None).is_err(), "{}", document);
+        }
+    }
+
+    #[test]
+    fn relative_references() {
+        assert_eq!(resolve("http://b/a/b?q#f", "c"), "http://b/a/c");
+        assert_eq!(resolve("http://b/a/b", "/c"), "http://b/c");
+        assert_eq!(resolve("http://b/a/b", "#x"), "http://b/a/b#x");
+        assert_eq!(resolve("http://b/a/b", "?y"), "http://b/a/b?y");
+        assert_eq!(resolve("http://b", "c"), "http://b/c");
+        assert_eq!(resolve("http://b/a", "urn:x"), "urn:x");
+    }
+}
@@ -1,7 +1,380 @@
+//! A SPARQL 1.1 server backed by an Oxigraph store.
+//!
+//! [`config_app`] registers the SPARQL Query, SPARQL Update and Graph Store
+//! endpoints, so the server can be embedded in another actix-web application:
+//!
+//! ```no_run
+//! use actix_web::{web, App, HttpServer};
+//! use knowgraf::{config_app, AppState, Limits};
+//! use oxigraph::SledStore;
+//!
+//! #[actix_web::main]
+//! async fn main() -> std::io::Result<()> {
+//!     let limits = Limits {
+//!         query_timeout: 10,
+//!         ..Limits::default()
+//!     };
+//!     let state = web::Data::new(AppState::new(SledStore::open("data.db")?).with_limits(limits));
+//!     HttpServer::new(move || {
+//!         App::new().service(web::scope("/sparql").configure(config_app(state.clone())))
+//!     })
+//!     .bind("127.0.0.1:8080")?
+//!     .run()
+//!     .await
+//! }
+//! ```
+use actix_files::NamedFile;
+use actix_web::dev::HttpServiceFactory;
+use actix_web::{http, web, HttpRequest, Resource};
+use oxigraph::SledStore;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+mod blocking;
+mod error;
+mod graphs;
+mod html;
+pub mod jsonld;
+mod limits;
+mod negotiation;
+mod problem;
+mod query;
+mod resource;
+mod service;
+mod stats;
+mod store;
+mod stream;
+mod update;
+mod void;
+
+pub use crate::error::{AppError, InnerError};
+pub use crate::limits::Limits;
+pub use crate::stats::{GraphCount, Stats, TermCount};
+
+/// The state shared by the knowgraf handlers.
+pub struct AppState {
+    pub(crate) store: SledStore,
+    pub(crate) limits: Limits,
+    pub(crate) path: Option<PathBuf>,
+    /// Bumped after every write, see [`AppState::writing`].
+    generation: AtomicU64,
+    pub(crate) replacing: ReplaceLock,
+    pub(crate) blocking: blocking::Blocking,
+    pub(crate) queries: blocking::Running,
+    pub(crate) dumps: blocking::Running,
+    pub(crate) summary: stats::Cache,
+    /// The context JSON-LD is compacted against when requests give none.
+    pub(crate) context: Option<jsonld::Context>,
+    /// The path under which IRIs dereference to their description.
+    resource_prefix: String,
+}
+
+impl AppState {
+    /// Serves `store` with the default [`Limits`].
+    pub fn new(store: SledStore) -> Self {
+        AppState {
+            store,
+            limits: Limits::default(),
+            path: None,
+            generation: AtomicU64::new(0),
+            replacing: ReplaceLock::default(),
+            blocking: blocking::Blocking::new(None),
+            queries: blocking::Running::default(),
+            dumps: blocking::Running::default(),
+            summary: stats::Cache::new(stats::TTL),
+            context: None,
+            resource_prefix: String::from("/id"),
+        }
+    }
+
+    pub fn with_limits(self, limits: Limits) -> Self {
+        AppState { limits, ..self }
+    }
+
+    /// Runs the store operations on `threads` threads instead of 5 per CPU.
+    pub fn with_blocking_threads(self, threads: usize) -> Self {
+        AppState {
+            blocking: blocking::Blocking::new(Some(threads)),
+            ..self
+        }
+    }
+
+    /// Tells where the store is on disk, so that `/stats` can report its size.
+    pub fn with_store_path(self, path: impl Into<PathBuf>) -> Self {
+        AppState {
+            path: Some(path.into()),
+            ..self
+        }
+    }
+
+    /// Dereferences the IRIs under `prefix` instead of `/id`, relative to
+    /// where the endpoints are mounted.
+    pub fn with_resource_prefix(self, prefix: &str) -> Self {
+        AppState {
+            resource_prefix: format!("/{}", prefix.trim_matches('/')),
+            ..self
+        }
+    }
+
+    /// Compacts the JSON-LD the server writes against `context`, unless
+    /// a request gives its own with the `context` parameter.
+    pub fn with_jsonld_context(self, context: jsonld::Context) -> Self {
+        AppState {
+            context: Some(context),
+            ..self
+        }
+    }
+
+    /// Counts the writes to the store, so that what is computed from it can
+    /// tell when it went stale.
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Marks the store as changed once the returned guard is dropped, that
+    /// is when the handler doing the write is done, whether it failed or not.
+    pub(crate) fn writing(&self) -> Writing<'_> {
+        Writing(&self.generation)
+    }
+
+    /// The context to compact JSON-LD against: `given`, the JSON of the
+    /// `context` parameter of a request, or else the server's.
+    pub(crate) fn jsonld_context(
+        &self,
+        given: Option<&str>,
+    ) -> Result<Option<jsonld::Context>, AppError> {
+        match given {
+            Some(given) => serde_json::from_str(given)
+                .map_err(std::io::Error::from)
+                .and_then(|value| jsonld::Context::parse(&value))
+                .map(Some)
+                .map_err(|err| AppError::BadRequestString(format!("Invalid context: {}", err))),
+            None => Ok(self.context.clone()),
+        }
+    }
+
+    /// A new directory to stage a store in, next to the store if its path is
+    /// known so that it is on the same disk, else in the temporary directory.
+    pub(crate) fn staging_dir(&self) -> io::Result<tempfile::TempDir> {
+        let mut builder = tempfile::Builder::new();
+        builder.prefix(".knowgraf-staging-");
+        match self.path.as_deref().and_then(Path::parent) {
+            Some(parent) if !parent.as_os_str().is_empty() => builder.tempdir_in(parent),
+            Some(_) => builder.tempdir_in("."),
+            None => builder.tempdir(),
+        }
+    }
+
+    /// Runs `f` against the store on the blocking thread pool of the state,
+    /// so that slow queries and loads don't hold up the HTTP workers.
+    ///
+    /// It waits while a graph or the whole dataset is being replaced, see
+    /// [`AppState::replace`].
+    pub(crate) async fn run<F, T>(&self, f: F) -> Result<T, AppError>
+    where
+        F: FnOnce(&SledStore) -> Result<T, AppError> + Send + 'static,
+        T: Send + 'static,
+    {
+        let store = self.store.clone();
+        let replacing = self.replacing.clone();
+        Ok(self
+            .blocking
+            .run(move || {
+                let _shared = replacing.shared();
+                f(&store)
+            })
+            .await?)
+    }
+
+    /// Like [`AppState::run`] for the replacement of a graph or the whole
+    /// dataset: `f` waits for the queries and writes running to be done, and
+    /// the new ones wait for it, so that none of them sees it half done.
+    pub(crate) async fn replace<F, T>(&self, f: F) -> Result<T, AppError>
+    where
+        F: FnOnce(&SledStore) -> Result<T, AppError> + Send + 'static,
+        T: Send + 'static,
+    {
+        let store = self.store.clone();
+        let replacing = self.replacing.clone();
+        Ok(self
+            .blocking
+            .run(move || {
+                let _exclusive = replacing.exclusive();
+                f(&store)
+            })
+            .await?)
+    }
+}
+
+pub(crate) struct Writing<'a>(&'a AtomicU64);
+
+impl Drop for Writing<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Lets queries and writes use the store side by side, but not while a graph
+/// or the whole dataset is being replaced.
+///
+/// It is only taken on the blocking thread pool, where waiting for it
+/// doesn't hold up the HTTP workers.
+#[derive(Clone, Default)]
+pub(crate) struct ReplaceLock(Arc<RwLock<()>>);
+
+impl ReplaceLock {
+    /// Held by the queries, and by the writes that change part of the dataset.
+    pub(crate) fn shared(&self) -> RwLockReadGuard<'_, ()> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Held while a graph or the whole dataset is replaced.
+    pub(crate) fn exclusive(&self) -> RwLockWriteGuard<'_, ()> {
+        self.0.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Registers the knowgraf endpoints on an actix-web application or scope.
+///
+/// The routes are relative to where they are mounted: `/query`, `/update`,
+/// the Graph Store under `/store`, the dataset statistics at `/stats`, the
+/// list of named graphs at `/graphs`, the VoID description of the dataset
+/// at `/.well-known/void` and the description of the resources of the server
+/// at `/resource?iri=...`. The IRIs under the resource prefix, `/id` unless
+/// set with [`AppState::with_resource_prefix`], dereference to their own
+/// description. Requests to other paths are left to the application.
+///
+/// A PUT to `/store` replaces a graph, or the whole dataset, atomically: in
+/// one transaction, while the queries and the other writes wait.
+pub fn config_app(app_state: web::Data<AppState>) -> Box<dyn Fn(&mut web::ServiceConfig)> {
+    Box::new(move |cfg: &mut web::ServiceConfig| {
+        cfg.app_data(app_state.clone())
+            .service(with_text_fallback(
+                web::resource("/").route(web::get().to(get_index)),
+            ))
+            .service(with_text_fallback(
+                web::resource("/query")
+                    .route(web::get().to(query::get_query))
+                    .route(web::post().to(query::post_query)),
+            ))
+            .service(with_text_fallback(
+                web::resource("/update").route(web::post().to(update::post_update)),
+            ))
+            .service(with_text_fallback(
+                web::resource("/stats").route(web::get().to(stats::get_stats)),
+            ))
+            .service(with_text_fallback(
+                web::resource("/graphs").route(web::get().to(graphs::get_graphs)),
+            ))
+            .service(with_text_fallback(
+                web::resource("/.well-known/void").route(web::get().to(void::get_void)),
+            ))
+            .service(with_text_fallback(
+                web::resource("/resource")
+                    .name("resource")
+                    .route(web::get().to(resource::get_resource)),
+            ))
+            .service(with_text_fallback(
+                web::resource("/{path:store.*}")
+                    .name("store")
+                    .route(web::put().to(store::put_store))
+                    .route(web::head().to(store::head_store))
+                    .route(web::get().to(store::get_store))
+                    .route(web::post().to(store::post_store))
+                    .route(web::delete().to(store::delete_store)),
+            ))
+            .service(with_text_fallback(
+                web::resource(format!("{}/{{path:.*}}", app_state.resource_prefix))
+                    .name("dereference")
+                    .route(web::get().to(resource::dereference))
+                    .route(web::head().to(resource::dereference)),
+            ));
+    })
+}
+
+/// Answers the errors of `resource` in plain text to the clients that
+/// prefer it. The routes are registered one by one rather than in a scope,
+/// which would take over the routes of the application around them.
+fn with_text_fallback(resource: Resource) -> impl HttpServiceFactory {
+    resource.wrap_fn(problem::text_fallback::<AppError, _>)
+}
+
+async fn get_index() -> Result<NamedFile, AppError> {
+    let path = PathBuf::from("templates/index.html");
+    Ok(NamedFile::open(path)?)
+}
+
+/// The absolute URL of `request`, with `path` instead of its own if given.
+///
+/// Requests normally only carry a path, the scheme and host then come from
+/// the connection and its `Host` header.
+pub(crate) fn base_url(request: &HttpRequest, path: Option<&str>) -> Result<http::Uri, AppError> {
+    let info = request.connection_info();
+    let mut uri = http::Uri::builder();
+    uri = match request.uri().scheme() {
+        Some(scheme) => uri.scheme(scheme.as_str()),
+        None => uri.scheme(info.scheme()),
+    };
+    uri = match request.uri().host() {
+        Some(host) => uri.authority(host),
+        None => uri.authority(info.host()),
+    };
+    if let Some(path) = path {
+        uri = uri.path_and_query(path);
+    } else {
+        uri = uri.path_and_query(request.uri().path());
+    }
+    uri.build().map_err(AppError::BadUrl)
+}
+
+pub(crate) fn url_query(request: &HttpRequest) -> Vec<u8> {
+    request.uri().query().unwrap_or("").as_bytes().to_vec()
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use actix_web::{test, App};
+
+    #[test]
+    fn absolute_uri() {
+        let req = test::TestRequest::with_uri("http://example.com/eat?my=shorts").to_http_request();
+        assert_eq!(
+            base_url(&req, None).unwrap(),
+            http::Uri::from_static("http://example.com/eat")
+        );
+    }
+
     #[test]
-    fn it_works() {
-        assert_eq!(2 + 2, 4);
+    fn absolute_uri_replace_path() {
+        let req = test::TestRequest::with_uri("http://example.com/eat?my=shorts").to_http_request();
+        assert_eq!(
+            base_url(&req, Some("/store/foo")).unwrap(),
+            http::Uri::from_static("http://example.com/store/foo")
+        );
+    }
+
+    #[test]
+    fn relative_uri() {
+        let req = test::TestRequest::with_uri("/query?query=ASK{}")
+            .header("host", "example.com:8080")
+            .to_http_request();
+        assert_eq!(
+            base_url(&req, None).unwrap(),
+            http::Uri::from_static("http://example.com:8080/query")
+        );
+    }
+
+    #[actix_rt::test]
+    async fn get_ui() {
+        let mut app = test::init_service(
+            App::new().service(web::resource("/").route(web::get().to(get_index))),
+        )
+        .await;
+        let req = test::TestRequest::with_header("content-type", "text/plain").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success());
     }
 }
@@ -0,0 +1,124 @@
+use serde_derive::Deserialize;
+use std::time::Duration;
+
+/// The longest any timeout of the limits is taken to be, one year: longer
+/// ones would overflow the clock when added to the current time.
+const LONGEST_TIMEOUT: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// Limits applied to the requests the server accepts.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// Largest body accepted on POST and PUT to the graph store, in bytes.
+    /// The quads are streamed to the store as they are parsed.
+    pub max_upload_size: u64,
+    /// Largest JSON-LD body accepted on POST and PUT to the graph store, in
+    /// bytes. JSON-LD is parsed as a whole in memory before being stored.
+    pub max_jsonld_upload_size: u64,
+    /// Time an upload may go without the client sending anything before it
+    /// is rejected, in seconds.
+    pub upload_idle_timeout: u64,
+    /// Time a response may wait for the client to read more of it before it
+    /// is cut short, in seconds.
+    pub write_idle_timeout: u64,
+    /// Time a query may run when the request doesn't ask for less, in seconds.
+    pub query_timeout: u64,
+    /// Cap on the `timeout` parameter of query requests, in seconds.
+    pub max_query_timeout: u64,
+    /// Number of queries evaluated at once, the others are answered with
+    /// 503 Service Unavailable. Queries and dumps together are also kept
+    /// off a quarter of the blocking threads, left to the other operations.
+    pub max_concurrent_queries: usize,
+    /// Number of dumps of the graph store sent at once, the others are
+    /// answered with 503 Service Unavailable.
+    pub max_concurrent_dumps: usize,
+}
+
+impl Limits {
+    /// Time allowed to a query, given the timeout the request asked for.
+    pub fn query_timeout(&self, requested: Option<Duration>) -> Duration {
+        let max = timeout(self.max_query_timeout);
+        requested
+            .unwrap_or_else(|| timeout(self.query_timeout))
+            .min(max)
+    }
+
+    /// Time an upload may go without data, see
+    /// [`Limits::upload_idle_timeout`](#structfield.upload_idle_timeout).
+    pub fn upload_idle_timeout(&self) -> Duration {
+        timeout(self.upload_idle_timeout)
+    }
+
+    /// Time a response may wait for the client, see
+    /// [`Limits::write_idle_timeout`](#structfield.write_idle_timeout).
+    pub fn write_idle_timeout(&self) -> Duration {
+        timeout(self.write_idle_timeout)
+    }
+
+    /// Largest body accepted for an upload, JSON-LD or not.
+    pub fn upload_size(&self, json_ld: bool) -> u64 {
+        if json_ld {
+            self.max_upload_size.min(self.max_jsonld_upload_size)
+        } else {
+            self.max_upload_size
+        }
+    }
+}
+
+fn timeout(secs: u64) -> Duration {
+    Duration::from_secs(secs).min(LONGEST_TIMEOUT)
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_upload_size: 4 * 1024 * 1024 * 1024,
+            max_jsonld_upload_size: 64 * 1024 * 1024,
+            upload_idle_timeout: 30,
+            write_idle_timeout: 30,
+            query_timeout: 60,
+            max_query_timeout: 600,
+            max_concurrent_queries: 16,
+            max_concurrent_dumps: 4,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_timeout_is_capped() {
+        let limits = Limits {
+            query_timeout: 10,
+            max_query_timeout: 30,
+            ..Limits::default()
+        };
+        assert_eq!(limits.query_timeout(None), Duration::from_secs(10));
+        assert_eq!(
+            limits.query_timeout(Some(Duration::from_millis(500))),
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            limits.query_timeout(Some(Duration::from_secs(3600))),
+            Duration::from_secs(30)
+        );
+    }
+
+    #[test]
+    fn timeouts_fit_the_clock() {
+        let limits = Limits {
+            upload_idle_timeout: u64::MAX,
+            write_idle_timeout: u64::MAX,
+            query_timeout: u64::MAX,
+            max_query_timeout: u64::MAX,
+            ..Limits::default()
+        };
+        let now = std::time::Instant::now();
+        let _ = now + limits.query_timeout(None);
+        let _ = now + limits.query_timeout(Some(Duration::MAX));
+        let _ = now + limits.upload_idle_timeout();
+        let _ = now + limits.write_idle_timeout();
+    }
+}
@@ -0,0 +1,397 @@
+use crate::{html, jsonld, AppError};
+use actix_web::http::header::{self, HeaderMap};
+use actix_web::HttpRequest;
+use oxigraph::io::{DatasetFormat, GraphFormat};
+use oxigraph::sparql::QueryResultsFormat;
+
+/// One entry of an `Accept` header.
+#[derive(Debug, PartialEq)]
+struct MediaRange {
+    /// Lowercased `type/subtype`, either part possibly `*`.
+    essence: String,
+    /// Quality in thousandths, so that 1000 is `q=1`.
+    quality: u16,
+}
+
+impl MediaRange {
+    /// How closely the range matches `essence`: 3 for an exact match, 2 for
+    /// `type/*`, 1 for `*/*` and 0 when it doesn't match at all.
+    fn specificity(&self, essence: &str) -> u8 {
+        if self.essence == essence {
+            3
+        } else if self.essence == "*/*" {
+            1
+        } else if let Some(type_) = self.essence.strip_suffix("/*") {
+            if essence.split('/').next() == Some(type_) {
+                2
+            } else {
+                0
+            }
+        } else {
+            0
+        }
+    }
+}
+
+/// Picks the media type to answer with, following RFC 7231 section 5.3.2.
+///
+/// Each supported type gets the quality of the most specific range that
+/// matches it. The best quality wins, then the most specific match, then the
+/// order of `supported`. Without an `Accept` header the first supported type
+/// is used.
+pub fn negotiate(
+    headers: &HeaderMap,
+    supported: &[&'static str],
+) -> Result<&'static str, AppError> {
+    let mut ranges = Vec::new();
+    for value in headers.get_all(header::ACCEPT) {
+        let value = value.to_str().map_err(|_| {
+            AppError::BadRequestString("Invalid Accept header: not visible ASCII".to_string())
+        })?;
+        ranges.extend(parse_accept(value)?);
+    }
+    if ranges.is_empty() {
+        return supported
+            .first()
+            .copied()
+            .ok_or(AppError::InternalServerError(
+                "No default MIME type provided",
+            ));
+    }
+
+    let mut best: Option<(u16, u8, &'static str)> = None;
+    for &media_type in supported {
+        let essence = essence(media_type);
+        let matched = ranges
+            .iter()
+            .map(|range| (range.specificity(&essence), range.quality))
+            .filter(|&(specificity, _)| specificity > 0)
+            .max();
+        if let Some((specificity, quality)) = matched {
+            let current = best.map(|(quality, specificity, _)| (quality, specificity));
+            if quality > 0 && Some((quality, specificity)) > current {
+                best = Some((quality, specificity, media_type));
+            }
+        }
+    }
+    best.map(|(_, _, media_type)| media_type)
+        .ok_or_else(|| AppError::NotAcceptable(supported.to_vec()))
+}
+
+/// A syntax RDF graphs are read and written in: one of Oxigraph's, or
+/// JSON-LD.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GraphSyntax {
+    Format(GraphFormat),
+    JsonLd,
+}
+
+impl GraphSyntax {
+    pub fn media_type(self) -> &'static str {
+        match self {
+            GraphSyntax::Format(format) => format.media_type(),
+            GraphSyntax::JsonLd => jsonld::MEDIA_TYPE,
+        }
+    }
+
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        if essence(media_type) == jsonld::MEDIA_TYPE {
+            Some(GraphSyntax::JsonLd)
+        } else {
+            GraphFormat::from_media_type(media_type).map(GraphSyntax::Format)
+        }
+    }
+}
+
+/// A syntax RDF datasets are read and written in: one of Oxigraph's, or
+/// JSON-LD.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DatasetSyntax {
+    Format(DatasetFormat),
+    JsonLd,
+}
+
+impl DatasetSyntax {
+    pub fn media_type(self) -> &'static str {
+        match self {
+            DatasetSyntax::Format(format) => format.media_type(),
+            DatasetSyntax::JsonLd => jsonld::MEDIA_TYPE,
+        }
+    }
+
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        if essence(media_type) == jsonld::MEDIA_TYPE {
+            Some(DatasetSyntax::JsonLd)
+        } else {
+            DatasetFormat::from_media_type(media_type).map(DatasetSyntax::Format)
+        }
+    }
+}
+
+/// A syntax SELECT and ASK results are written in: one of Oxigraph's, or an
+/// HTML page for browsers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResultsSyntax {
+    Format(QueryResultsFormat),
+    Html,
+}
+
+impl ResultsSyntax {
+    pub fn media_type(self) -> &'static str {
+        match self {
+            ResultsSyntax::Format(format) => format.media_type(),
+            ResultsSyntax::Html => html::MEDIA_TYPE,
+        }
+    }
+
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        if essence(media_type) == essence(html::MEDIA_TYPE) {
+            Some(ResultsSyntax::Html)
+        } else {
+            QueryResultsFormat::from_media_type(media_type).map(ResultsSyntax::Format)
+        }
+    }
+}
+
+pub fn graph_content_negotiation(request: HttpRequest) -> Result<GraphSyntax, AppError> {
+    content_negotiation(
+        request,
+        &[
+            GraphFormat::NTriples.media_type(),
+            GraphFormat::Turtle.media_type(),
+            GraphFormat::RdfXml.media_type(),
+            jsonld::MEDIA_TYPE,
+        ],
+        GraphSyntax::from_media_type,
+    )
+}
+
+pub fn dataset_content_negotiation(request: HttpRequest) -> Result<DatasetSyntax, AppError> {
+    content_negotiation(
+        request,
+        &[
+            DatasetFormat::NQuads.media_type(),
+            DatasetFormat::TriG.media_type(),
+            jsonld::MEDIA_TYPE,
+        ],
+        DatasetSyntax::from_media_type,
+    )
+}
+
+pub fn results_content_negotiation(request: HttpRequest) -> Result<ResultsSyntax, AppError> {
+    content_negotiation(
+        request,
+        &[
+            QueryResultsFormat::Xml.media_type(),
+            QueryResultsFormat::Json.media_type(),
+            QueryResultsFormat::Csv.media_type(),
+            QueryResultsFormat::Tsv.media_type(),
+            html::MEDIA_TYPE,
+        ],
+        ResultsSyntax::from_media_type,
+    )
+}
+
+pub fn content_negotiation<F>(
+    request: HttpRequest,
+    supported: &[&'static str],
+    parse: impl Fn(&str) -> Option<F>,
+) -> Result<F, AppError> {
+    parse(negotiate(request.headers(), supported)?)
+        .ok_or(AppError::InternalServerError("Unknown mime type"))
+}
+
+/// The lowercased `type/subtype` of a media type, without its parameters.
+fn essence(media_type: &str) -> String {
+    media_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+fn parse_accept(value: &str) -> Result<Vec<MediaRange>, AppError> {
+    split_unquoted(value, ',')
+        .into_iter()
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            parse_media_range(entry).ok_or_else(|| {
+                AppError::BadRequestString(format!("Invalid Accept header entry: {}", entry))
+            })
+        })
+        .collect()
+}
+
+fn parse_media_range(entry: &str) -> Option<MediaRange> {
+    let mut parts = split_unquoted(entry, ';').into_iter();
+    let essence = parts.next()?.trim().to_ascii_lowercase();
+    let (type_, subtype) = essence.split_once('/')?;
+    if !is_token(type_) || !is_token(subtype) || (type_ == "*" && subtype != "*") {
+        return None;
+    }
+
+    let mut quality = 1000;
+    for param in parts {
+        let (name, value) = param.split_once('=')?;
+        let name = name.trim();
+        if !is_token(name) {
+            return None;
+        }
+        if name.eq_ignore_ascii_case("q") {
+            quality = parse_quality(value.trim())?;
+            // Anything after the weight is an accept-extension.
+            break;
+        }
+    }
+    Some(MediaRange { essence, quality })
+}
+
+/// Parses a `qvalue`: "0" or "1" followed by up to three decimals.
+fn parse_quality(value: &str) -> Option<u16> {
+    let (integer, decimals) = value.split_once('.').unwrap_or((value, ""));
+    if decimals.len() > 3 || !decimals.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let thousandths = format!("{:0<3}", decimals).parse::<u16>().ok()?;
+    match integer {
+        "0" => Some(thousandths),
+        "1" if thousandths == 0 => Some(1000),
+        _ => None,
+    }
+}
+
+fn is_token(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+/// Splits `value` on `separator`, leaving quoted strings alone.
+fn split_unquoted(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if quoted && c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            quoted = !quoted;
+        } else if !quoted && c == separator {
+            parts.push(&value[start..i]);
+            start = i + 1;
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::HeaderValue;
+
+    const SUPPORTED: &[&str] = &[
+        "application/sparql-results+xml",
+        "application/sparql-results+json",
+        "text/csv; charset=utf-8",
+    ];
+
+    fn accept(values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(header::ACCEPT, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    fn negotiated(values: &[&str]) -> Result<&'static str, AppError> {
+        negotiate(&accept(values), SUPPORTED)
+    }
+
+    #[test]
+    fn defaults_to_first_supported() {
+        assert_eq!(negotiated(&[]).unwrap(), SUPPORTED[0]);
+    }
+
+    #[test]
+    fn ignores_parameters_of_supported_types() {
+        assert_eq!(negotiated(&["text/csv"]).unwrap(), SUPPORTED[2]);
+    }
+
+    #[test]
+    fn highest_quality_wins() {
+        assert_eq!(
+            negotiated(&["application/sparql-results+xml;q=0.5, text/csv;q=0.9"]).unwrap(),
+            SUPPORTED[2]
+        );
+    }
+
+    #[test]
+    fn entries_from_several_headers() {
+        assert_eq!(
+            negotiated(&["text/html", "application/sparql-results+json"]).unwrap(),
+            SUPPORTED[1]
+        );
+    }
+
+    #[test]
+    fn wildcards() {
+        assert_eq!(negotiated(&["*/*"]).unwrap(), SUPPORTED[0]);
+        assert_eq!(negotiated(&["text/*"]).unwrap(), SUPPORTED[2]);
+        assert_eq!(
+            negotiated(&["application/sparql-results+json, */*"]).unwrap(),
+            SUPPORTED[1]
+        );
+    }
+
+    #[test]
+    fn most_specific_range_sets_quality() {
+        assert_eq!(
+            negotiated(&["text/*;q=0.1, text/csv;q=0, */*;q=0.5"]).unwrap(),
+            SUPPORTED[0]
+        );
+        assert_eq!(
+            negotiated(&["*/*;q=0.1, application/*;q=0"]).unwrap(),
+            SUPPORTED[2]
+        );
+    }
+
+    #[test]
+    fn not_acceptable() {
+        match negotiated(&["text/html, application/sparql-results+xml;q=0"]) {
+            Err(AppError::NotAcceptable(types)) => assert_eq!(types, SUPPORTED),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn accept_extensions_and_quoted_parameters() {
+        assert_eq!(
+            negotiated(&["text/csv;foo=\"a,b;c\";q=0.8;ext=1, */*;q=0.2"]).unwrap(),
+            SUPPORTED[2]
+        );
+    }
+
+    #[test]
+    fn malformed_headers() {
+        for value in &[
+            "text",
+            "*/csv",
+            "text/csv;q=2",
+            "text/csv;q=0.0001",
+            "text/csv;q",
+        ] {
+            assert!(
+                matches!(negotiated(&[value]), Err(AppError::BadRequestString(_))),
+                "{} should be rejected",
+                value
+            );
+        }
+    }
+}
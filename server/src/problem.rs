@@ -0,0 +1,148 @@
+use crate::negotiation;
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::http::{HeaderMap, StatusCode};
+use actix_web::{Error, HttpResponse, ResponseError};
+use futures::future::{FutureExt, LocalBoxFuture};
+use serde_derive::Serialize;
+
+pub const MEDIA_TYPE: &str = "application/problem+json";
+
+/// An RFC 7807 problem details document.
+///
+/// `code` is a stable identifier clients can match on, while `detail` is
+/// meant for humans and may change between releases.
+#[derive(Debug, Serialize)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    type_: &'static str,
+    title: &'static str,
+    status: u16,
+    code: &'static str,
+    detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    column: Option<u64>,
+}
+
+impl Problem {
+    pub fn new(status: StatusCode, code: &'static str, detail: String) -> Problem {
+        Problem {
+            type_: "about:blank",
+            title: status.canonical_reason().unwrap_or("Error"),
+            status: status.as_u16(),
+            code,
+            detail,
+            line: None,
+            column: None,
+        }
+    }
+
+    /// Points the problem at a position in the request body.
+    pub fn at(mut self, line: u64, column: u64) -> Problem {
+        self.line = Some(line);
+        self.column = Some(column);
+        self
+    }
+
+    pub fn to_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status())
+            .content_type(MEDIA_TYPE)
+            .json(self)
+    }
+
+    pub fn to_text_response(&self) -> HttpResponse {
+        let mut text = format!("{}: {}", self.code, self.detail);
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            text.push_str(&format!(" (line {}, column {})", line, column));
+        }
+        HttpResponse::build(self.status())
+            .content_type("text/plain; charset=utf-8")
+            .body(text)
+    }
+
+    fn status(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+/// Errors that can describe themselves as a [`Problem`].
+pub trait ToProblem: 'static {
+    fn to_problem(&self) -> Problem;
+}
+
+/// Middleware swapping problem documents for plain text when the client
+/// prefers it, which is the case for browsers.
+pub fn text_fallback<E, S>(
+    request: ServiceRequest,
+    service: &mut S,
+) -> LocalBoxFuture<'static, Result<ServiceResponse, Error>>
+where
+    E: ToProblem + ResponseError,
+    S: Service<Request = ServiceRequest, Response = ServiceResponse, Error = Error>,
+    S::Future: 'static,
+{
+    let text = prefers_text(request.headers());
+    service
+        .call(request)
+        .map(move |response| {
+            let response = response?;
+            if !text {
+                return Ok(response);
+            }
+            let problem = response
+                .response()
+                .error()
+                .and_then(|err| err.as_error::<E>())
+                .map(ToProblem::to_problem);
+            Ok(match problem {
+                Some(problem) => response.into_response(problem.to_text_response()),
+                None => response,
+            })
+        })
+        .boxed_local()
+}
+
+fn prefers_text(headers: &HeaderMap) -> bool {
+    match negotiation::negotiate(headers, &[MEDIA_TYPE, "text/plain", "text/html"]) {
+        Ok(media_type) => media_type.starts_with("text/"),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::{header, HeaderValue};
+
+    #[test]
+    fn serializes_as_rfc7807() {
+        let problem =
+            Problem::new(StatusCode::BAD_REQUEST, "parse_error", "oops".to_string()).at(2, 5);
+        assert_eq!(
+            serde_json::to_value(&problem).unwrap(),
+            serde_json::json!({
+                "type": "about:blank",
+                "title": "Bad Request",
+                "status": 400,
+                "code": "parse_error",
+                "detail": "oops",
+                "line": 2,
+                "column": 5
+            })
+        );
+    }
+
+    #[test]
+    fn browsers_prefer_text() {
+        let mut headers = HeaderMap::new();
+        assert!(!prefers_text(&headers));
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("text/html,application/xhtml+xml,*/*;q=0.8"),
+        );
+        assert!(prefers_text(&headers));
+        headers.insert(header::ACCEPT, HeaderValue::from_static("*/*"));
+        assert!(!prefers_text(&headers));
+    }
+}
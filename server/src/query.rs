@@ -0,0 +1,960 @@
+use crate::error::{AppError, InnerError};
+use crate::negotiation::{graph_content_negotiation, results_content_negotiation};
+use crate::negotiation::{GraphSyntax, ResultsSyntax};
+use crate::resource::Namespace;
+use crate::{base_url, html, jsonld, url_query, AppState};
+use actix_web::{http, web, HttpRequest, HttpResponse};
+use futures::{Stream, StreamExt};
+use oxigraph::io::{GraphFormat, GraphSerializer};
+use oxigraph::model;
+use oxigraph::sparql;
+use std::io;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// #[get("/query")]
+pub(crate) async fn get_query(
+    request: HttpRequest,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    //     log::info!("query: {:?}", form);
+    //     format!("get_query: {:?}", form)
+    let encoded = url_query(&request);
+    if !form_urlencoded::parse(&encoded).any(|(k, _)| k == "query") {
+        return crate::service::get_service_description(request, state).await;
+    }
+    configure_and_evaluate_sparql_query(state, &encoded, None, request).await
+}
+
+pub(crate) async fn post_query(
+    request: HttpRequest,
+    state: web::Data<AppState>,
+    payload: web::Payload,
+) -> Result<HttpResponse, AppError> {
+    use actix_web::dev;
+    use actix_web::FromRequest;
+    use http::header;
+
+    let mut payload: dev::Payload = payload.into_inner();
+
+    // let mut bytes = web::BytesMut::new();
+    // while let Some(item) = payload.next().await {
+    //     bytes.extend_from_slice(&item?);
+    // }
+
+    if let Some(content_type) = request.headers().get(header::CONTENT_TYPE) {
+        let content_type = content_type.to_str()?;
+        if content_type == "application/sparql-query" {
+            let body: String = String::from_request(&request, &mut payload).await?;
+            configure_and_evaluate_sparql_query(state, &url_query(&request), Some(body), request)
+                .await
+        } else if content_type == "application/x-www-form-urlencoded" {
+            let buffer = web::Bytes::from_request(&request, &mut payload).await?;
+            configure_and_evaluate_sparql_query(state, &buffer, None, request).await
+        } else {
+            Err(AppError::UnsupportedMediaType(content_type.to_string()))
+        }
+    } else {
+        Err(AppError::BadRequest(InnerError::Str(
+            "No Content-Type given",
+        )))
+    }
+}
+
+async fn configure_and_evaluate_sparql_query(
+    state: web::Data<AppState>,
+    encoded: &[u8], // Vec<u8>,
+    mut query: Option<String>,
+    request: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let mut default_graph_uris = Vec::new();
+    let mut named_graph_uris = Vec::new();
+    let mut timeout = None;
+    let mut context = None;
+    for (k, v) in form_urlencoded::parse(encoded) {
+        match k.as_ref() {
+            "query" => {
+                if query.is_some() {
+                    return Err(AppError::BadRequest(InnerError::Str(
+                        "Multiple query parameters provided",
+                    )));
+                }
+                query = Some(v.into_owned())
+            }
+            "default-graph-uri" => default_graph_uris.push(v.into_owned()),
+            "named-graph-uri" => named_graph_uris.push(v.into_owned()),
+            "timeout" => timeout = Some(parse_timeout(&v)?),
+            "context" => context = Some(v.into_owned()),
+            _ => {
+                return Err(AppError::BadRequestString(format!(
+                    "Unexpected parameter: {}",
+                    k
+                )));
+            }
+        }
+    }
+    if let Some(query) = query {
+        let timeout = state.limits.query_timeout(timeout);
+        evaluate_sparql_query(
+            state,
+            query,
+            default_graph_uris,
+            named_graph_uris,
+            timeout,
+            context,
+            request,
+        )
+        .await
+    } else {
+        Err(AppError::BadRequest(InnerError::Str(
+            "You should set the 'query' parameter",
+        )))
+    }
+}
+
+async fn evaluate_sparql_query(
+    state: web::Data<AppState>,
+    query: String,
+    default_graph_uris: Vec<String>,
+    named_graph_uris: Vec<String>,
+    timeout: Duration,
+    context: Option<String>,
+    request: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    use sparql::Query;
+
+    let base_iri = base_url(&request, None)?.to_string();
+    let default_graph_uris = default_graph_uris
+        .into_iter()
+        .map(|e| Ok(model::NamedNode::new(e)?.into()))
+        .collect::<Result<Vec<model::GraphName>, model::IriParseError>>()
+        .map_err(AppError::UrlParseError)?;
+    let named_graph_uris = named_graph_uris
+        .into_iter()
+        .map(|e| Ok(model::NamedNode::new(e)?.into()))
+        .collect::<Result<Vec<model::NamedOrBlankNode>, model::IriParseError>>()
+        .map_err(AppError::UrlParseError)?;
+
+    // The parsed query can't leave this thread, so it is only used to
+    // validate the query and pick the result format. The blocking task
+    // parses it again.
+    let parsed = prepare_query(
+        &query,
+        &base_iri,
+        default_graph_uris.clone(),
+        named_graph_uris.clone(),
+    )?;
+    let text = query.clone();
+    let store = state.store.clone();
+    let evaluate = move || {
+        store.query(prepare_query(
+            &query,
+            &base_iri,
+            default_graph_uris,
+            named_graph_uris,
+        )?)
+    };
+    if let Query::Construct { .. } | Query::Describe { .. } = parsed {
+        let syntax = graph_content_negotiation(request)?;
+        let context = state.jsonld_context(context.as_deref())?;
+        let body = stream_query_results(
+            &state,
+            evaluate,
+            move |results, interrupt, writer| match syntax {
+                GraphSyntax::Format(format) => write_graph(results, interrupt, format, writer),
+                GraphSyntax::JsonLd => write_json_ld(results, interrupt, context.as_ref(), writer),
+            },
+            timeout,
+        )
+        .await?;
+        Ok(HttpResponse::Ok()
+            .content_type(syntax.media_type())
+            .streaming(body))
+    } else {
+        let syntax = results_content_negotiation(request.clone())?;
+        let namespace = Namespace::of(&request)?;
+        let started = Instant::now();
+        let body = stream_query_results(
+            &state,
+            evaluate,
+            move |results, _, writer| match syntax {
+                ResultsSyntax::Format(format) => results.write(writer, format),
+                ResultsSyntax::Html => write_html(results, &text, &namespace, started, writer),
+            },
+            timeout,
+        )
+        .await?;
+        Ok(HttpResponse::Ok()
+            .header(http::header::CONTENT_TYPE, syntax.media_type())
+            .header(
+                http::header::CONTENT_SECURITY_POLICY,
+                html::CONTENT_SECURITY_POLICY,
+            )
+            .streaming(body))
+    }
+}
+
+/// Writes the triples of CONSTRUCT and DESCRIBE results, like
+/// `QueryResults::write_graph` does, checking `interrupt` before each.
+fn write_graph(
+    results: sparql::QueryResults,
+    interrupt: &Interrupt,
+    format: GraphFormat,
+    out: impl io::Write,
+) -> Result<(), sparql::EvaluationError> {
+    let mut writer = GraphSerializer::from_format(format).triple_writer(out)?;
+    for triple in triples(results)? {
+        interrupt.check()?;
+        writer.write(&triple?)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Writes the triples of CONSTRUCT and DESCRIBE results as JSON-LD, checking
+/// `interrupt` before each.
+fn write_json_ld(
+    results: sparql::QueryResults,
+    interrupt: &Interrupt,
+    context: Option<&jsonld::Context>,
+    out: impl io::Write,
+) -> Result<(), sparql::EvaluationError> {
+    let mut writer = jsonld::JsonLdWriter::new(out, context)?;
+    for triple in triples(results)? {
+        interrupt.check()?;
+        writer.write_triple(&triple?)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+fn triples(results: sparql::QueryResults) -> Result<sparql::QueryTripleIter, io::Error> {
+    if let sparql::QueryResults::Graph(triples) = results {
+        Ok(triples)
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Bindings or booleans could not be formatted as an RDF graph",
+        ))
+    }
+}
+
+/// Writes SELECT and ASK results as an HTML page for browsers: the query,
+/// a table of the results with the IRIs of `namespace` linking to their view,
+/// then how many rows there are and how long it took since `started`.
+fn write_html(
+    results: sparql::QueryResults,
+    query: &str,
+    namespace: &Namespace,
+    started: Instant,
+    mut out: impl io::Write,
+) -> Result<(), sparql::EvaluationError> {
+    write!(
+        out,
+        "{}<h1>SPARQL query</h1>\n<pre>{}</pre>\n",
+        html::page_start("SPARQL query results"),
+        html::escape(query)
+    )?;
+    let rows = match results {
+        sparql::QueryResults::Solutions(solutions) => {
+            let variables = solutions.variables().to_vec();
+            let header = variables.iter().map(|v| v.as_str()).collect::<Vec<_>>();
+            out.write_all(html::table_start(&header).as_bytes())?;
+            let mut rows = 0;
+            for solution in solutions {
+                let solution = solution?;
+                let cells = variables
+                    .iter()
+                    .map(|v| {
+                        solution
+                            .get(v)
+                            .map_or(String::new(), |t| html::term(t, namespace))
+                    })
+                    .collect::<Vec<_>>();
+                out.write_all(html::table_row(&cells).as_bytes())?;
+                rows += 1;
+            }
+            rows
+        }
+        sparql::QueryResults::Boolean(value) => {
+            out.write_all(html::table_start(&["boolean"]).as_bytes())?;
+            out.write_all(html::table_row(&[value.to_string()]).as_bytes())?;
+            1
+        }
+        sparql::QueryResults::Graph(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Graphs could not be formatted as an HTML table",
+            )
+            .into())
+        }
+    };
+    write!(
+        out,
+        "{}<p>{} row{} in {} ms.</p>\n{}",
+        html::TABLE_END,
+        rows,
+        if rows == 1 { "" } else { "s" },
+        started.elapsed().as_millis(),
+        html::PAGE_END
+    )?;
+    Ok(())
+}
+
+fn prepare_query(
+    query: &str,
+    base_iri: &str,
+    default_graph_uris: Vec<model::GraphName>,
+    named_graph_uris: Vec<model::NamedOrBlankNode>,
+) -> Result<sparql::Query, sparql::ParseError> {
+    let mut query = sparql::Query::parse(query, Some(base_iri))?;
+    if !default_graph_uris.is_empty() || !named_graph_uris.is_empty() {
+        query.dataset_mut().set_default_graph(default_graph_uris);
+        query
+            .dataset_mut()
+            .set_available_named_graphs(named_graph_uris);
+    }
+    Ok(query)
+}
+
+/// Evaluates a query on the blocking thread pool and streams the results
+/// serialized by `write`.
+///
+/// The response is held back until the first chunk of results is ready, so
+/// errors and timeouts up to that point get a proper error response. Later
+/// ones abort the body. At most `max_concurrent_queries` queries are
+/// evaluated at once, and never on the whole blocking thread pool, the others
+/// are turned away.
+///
+/// Oxigraph can't interrupt a running evaluation, so the deadline, and
+/// whether the response is still wanted, are checked before each solution or
+/// triple is pulled from the results: a query that timed out or whose client
+/// went away stops at the next one. `write` is given the [`Interrupt`] to
+/// check for the triples, the solutions are checked before it gets them.
+///
+/// A client that stops reading gets its response cut short once it has read
+/// nothing for `write_idle_timeout`, or once the deadline has passed, so that
+/// it doesn't keep the thread either.
+///
+/// The operators that need all their input before giving a first result,
+/// such as ORDER BY, GROUP BY and the aggregates, and the evaluation of
+/// CONSTRUCT queries, ignore the timeout: the client gets its error response
+/// on time, but the query keeps its thread and counts as running until they
+/// are done.
+async fn stream_query_results<E, W>(
+    state: &AppState,
+    evaluate: E,
+    write: W,
+    timeout: Duration,
+) -> Result<impl Stream<Item = Result<web::Bytes, io::Error>> + Unpin, AppError>
+where
+    E: FnOnce() -> Result<sparql::QueryResults, sparql::EvaluationError> + Send + 'static,
+    W: FnOnce(
+            sparql::QueryResults,
+            &Interrupt,
+            &mut crate::stream::ChunkWriter,
+        ) -> Result<(), sparql::EvaluationError>
+        + Send
+        + 'static,
+{
+    use actix_web::rt::time;
+    use futures::channel::oneshot;
+    use futures::stream;
+
+    let max = state.limits.max_concurrent_queries;
+    let permit = state
+        .blocking
+        .start_read(&state.queries, max)
+        .ok_or_else(|| AppError::TooManyQueries(max.min(state.blocking.read_threads())))?;
+    let interrupt = Interrupt {
+        deadline: Instant::now() + timeout,
+        cancelled: Arc::new(AtomicBool::new(false)),
+    };
+    // Cancels the evaluation once the response is dropped, whether it was
+    // sent, failed or the client went away.
+    let cancel = Cancel(interrupt.cancelled.clone());
+    let (started_tx, started_rx) = oneshot::channel();
+    let replacing = state.replacing.clone();
+    let idle = state.limits.write_idle_timeout();
+    let mut body = crate::stream::spawn(&state.blocking, move |writer| {
+        let _permit = permit;
+        let _shared = replacing.shared();
+        writer.set_deadline(interrupt.deadline);
+        writer.set_idle_timeout(idle);
+        let results = match evaluate() {
+            Ok(results) => {
+                let _ = started_tx.send(Ok(()));
+                interrupt.wrap(results)
+            }
+            Err(err) => {
+                let _ = started_tx.send(Err(err));
+                return Ok(());
+            }
+        };
+        write(results, &interrupt, writer).map_err(|err| match err {
+            sparql::EvaluationError::Io(err) => err,
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        })
+    });
+    let first = time::timeout(timeout, async {
+        started_rx
+            .await
+            .map_err(|_| AppError::InternalServerError("query evaluation was cancelled"))??;
+        Ok::<_, AppError>(body.next().await)
+    })
+    .await
+    .map_err(|_| AppError::QueryTimeout(timeout))??;
+    let first = match first {
+        Some(Ok(chunk)) => Some(Ok(chunk)),
+        Some(Err(err)) if err.kind() == io::ErrorKind::TimedOut => {
+            return Err(AppError::QueryTimeout(timeout))
+        }
+        Some(Err(err)) => return Err(AppError::from_query_io_error(err)),
+        None => None,
+    };
+    Ok(stream::iter(first).chain(body).map(move |chunk| {
+        let _ = &cancel;
+        chunk
+    }))
+}
+
+/// Tells a query evaluation to stop: once its deadline has passed, or once
+/// its response was dropped.
+#[derive(Clone)]
+struct Interrupt {
+    deadline: Instant,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Interrupt {
+    fn check(&self) -> Result<(), sparql::EvaluationError> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            Err(io::Error::new(io::ErrorKind::ConnectionAborted, "the query was cancelled").into())
+        } else if Instant::now() >= self.deadline {
+            Err(io::Error::new(io::ErrorKind::TimedOut, "the deadline has passed").into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Makes the solutions of `results` check `self` before each is pulled.
+    fn wrap(&self, results: sparql::QueryResults) -> sparql::QueryResults {
+        match results {
+            sparql::QueryResults::Solutions(solutions) => {
+                let variables = Rc::new(solutions.variables().to_vec());
+                let interrupt = self.clone();
+                let mut solutions = solutions.fuse();
+                sparql::QueryResults::Solutions(sparql::QuerySolutionIter::new(
+                    variables,
+                    Box::new(std::iter::from_fn(move || {
+                        if let Err(err) = interrupt.check() {
+                            return Some(Err(err));
+                        }
+                        let solution = solutions.next()?;
+                        Some(
+                            solution
+                                .map(|solution| solution.values().map(|v| v.cloned()).collect()),
+                        )
+                    })),
+                ))
+            }
+            results => results,
+        }
+    }
+}
+
+struct Cancel(Arc<AtomicBool>);
+
+impl Drop for Cancel {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Parses the `timeout` parameter, in seconds. Timeouts too long for a
+/// `Duration` are taken as the longest one, which the limits then cap.
+fn parse_timeout(value: &str) -> Result<Duration, AppError> {
+    match value.parse::<f64>() {
+        Ok(secs) if secs.is_finite() && secs > 0. => {
+            Ok(Duration::try_from_secs_f64(secs).unwrap_or(Duration::MAX))
+        }
+        _ => Err(AppError::BadRequestString(format!(
+            "Invalid timeout: {}",
+            value
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config_app, AppState};
+    use actix_web::{http, test, App};
+    use oxigraph::io::GraphFormat;
+    use oxigraph::SledStore;
+    use tempfile::tempdir;
+
+    #[actix_rt::test]
+    async fn get_query() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::get()
+            .uri("http://localhost/query?query=SELECT%20*%20WHERE%20{%20?s%20?p%20?o%20}")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn get_query_large_results() {
+        let path = tempdir().unwrap();
+        let store = SledStore::open(path.path()).unwrap();
+        let data: String = (0..2000)
+            .map(|i| {
+                format!(
+                    "<http://example.com/s{}> <http://example.com/p> \"{}\" .\n",
+                    i, i
+                )
+            })
+            .collect();
+        store
+            .load_graph(
+                data.as_bytes(),
+                GraphFormat::NTriples,
+                &model::GraphName::DefaultGraph,
+                None,
+            )
+            .unwrap();
+        let app_state = web::Data::new(AppState::new(store));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::get()
+            .uri("http://localhost/query?query=SELECT%20*%20WHERE%20{%20?s%20?p%20?o%20}")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let body = test::read_body(resp).await;
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert_eq!(body.matches("<result>").count(), 2000);
+    }
+
+    #[actix_rt::test]
+    async fn get_query_csv() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::get()
+            .uri("http://localhost/query?query=SELECT%20*%20WHERE%20{%20?s%20?p%20?o%20}")
+            .header("Accept", "image/png, text/csv;q=0.9, */*;q=0.1")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(http::header::CONTENT_TYPE).unwrap(),
+            "text/csv; charset=utf-8"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn get_query_html() {
+        let path = tempdir().unwrap();
+        let store = SledStore::open(path.path()).unwrap();
+        store
+            .update("INSERT DATA { <http://e/s> <http://e/p> \"<o>\" }")
+            .unwrap();
+        let app_state = web::Data::new(AppState::new(store));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::get()
+            .uri("http://localhost/query?query=SELECT%20?s%20?o%20WHERE%20{%20?s%20?p%20?o%20}")
+            .header("Accept", "text/html,application/xhtml+xml,*/*;q=0.8")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(http::header::CONTENT_TYPE).unwrap(),
+            "text/html; charset=utf-8"
+        );
+        assert!(resp
+            .headers()
+            .contains_key(http::header::CONTENT_SECURITY_POLICY));
+        let body = test::read_body(resp).await;
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("<pre>SELECT ?s ?o WHERE { ?s ?p ?o }</pre>"));
+        assert!(body.contains("<tr><th>s</th><th>o</th></tr>"));
+        assert!(body.contains(
+            "<td><a href=\"http://e/s\">http://e/s</a></td>\
+             <td>&quot;&lt;o&gt;&quot;</td>"
+        ));
+        assert!(body.contains("<p>1 row in "));
+
+        let req = test::TestRequest::get()
+            .uri("http://localhost/query?query=ASK%20{%20?s%20?p%20?o%20}")
+            .header("Accept", "text/html")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let body = test::read_body(resp).await;
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("<tr><td>true</td></tr>"));
+    }
+
+    #[actix_rt::test]
+    async fn construct_json_ld() {
+        let path = tempdir().unwrap();
+        let store = SledStore::open(path.path()).unwrap();
+        store
+            .update("INSERT DATA { <http://e/s> <http://e/p> <http://e/o> }")
+            .unwrap();
+        let app_state = web::Data::new(AppState::new(store));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::get()
+            .uri("http://localhost/query?query=CONSTRUCT%20WHERE%20{%20?s%20?p%20?o%20}\
+                  &context=%7B%22p%22%3A%7B%22%40id%22%3A%22http%3A%2F%2Fe%2Fp%22%2C%22%40type%22%3A%22%40id%22%7D%7D")
+            .header("Accept", "application/ld+json")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(http::header::CONTENT_TYPE).unwrap(),
+            "application/ld+json"
+        );
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(
+            body["@graph"],
+            serde_json::json!([{ "@id": "http://e/s", "p": "http://e/o" }])
+        );
+    }
+
+    #[actix_rt::test]
+    async fn get_query_not_acceptable() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::get()
+            .uri("http://localhost/query?query=SELECT%20*%20WHERE%20{%20?s%20?p%20?o%20}")
+            .header("Accept", "image/png")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::NOT_ACCEPTABLE);
+        let body = test::read_body(resp).await;
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("application/sparql-results+json"));
+    }
+
+    #[actix_rt::test]
+    async fn get_query_malformed_accept() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::get()
+            .uri("http://localhost/query?query=SELECT%20*%20WHERE%20{%20?s%20?p%20?o%20}")
+            .header("Accept", "text/csv;q=high")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn get_query_timeout() {
+        let path = tempdir().unwrap();
+        let store = SledStore::open(path.path()).unwrap();
+        let data: String = (0..1000)
+            .map(|i| {
+                format!(
+                    "<http://example.com/s{}> <http://example.com/p> \"{}\" .\n",
+                    i, i
+                )
+            })
+            .collect();
+        store
+            .load_graph(
+                data.as_bytes(),
+                GraphFormat::NTriples,
+                &model::GraphName::DefaultGraph,
+                None,
+            )
+            .unwrap();
+        let app_state = web::Data::new(AppState::new(store));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        // The deadline has passed by the time the first solution is pulled.
+        let req = test::TestRequest::get()
+            .uri("http://localhost/query?query=SELECT%20*%20WHERE%20{%20?s%20?p%20?o%20}&timeout=0.000000001")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[actix_rt::test]
+    async fn huge_configured_timeouts() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(
+            AppState::new(SledStore::open(path.path()).unwrap()).with_limits(crate::Limits {
+                query_timeout: u64::MAX,
+                max_query_timeout: u64::MAX,
+                ..crate::Limits::default()
+            }),
+        );
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        for uri in &[
+            "http://localhost/query?query=ASK%20{}",
+            "http://localhost/query?query=ASK%20{}&timeout=1e20",
+        ] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(resp.status(), http::StatusCode::OK);
+        }
+    }
+
+    #[test]
+    fn huge_timeouts_are_capped() {
+        assert_eq!(parse_timeout("1e20").unwrap(), Duration::MAX);
+        assert_eq!(parse_timeout("1.5").unwrap(), Duration::from_millis(1500));
+        assert!(parse_timeout("inf").is_err());
+        assert!(parse_timeout("-1").is_err());
+        assert_eq!(
+            crate::Limits::default().query_timeout(Some(parse_timeout("1e20").unwrap())),
+            Duration::from_secs(600)
+        );
+    }
+
+    #[test]
+    fn cancelled_queries_stop() {
+        let store = SledStore::new().unwrap();
+        store
+            .update("INSERT DATA { <http://e/s> <http://e/p> 1, 2 }")
+            .unwrap();
+        let interrupt = Interrupt {
+            deadline: Instant::now() + Duration::from_secs(60),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        };
+        let results = interrupt.wrap(store.query("SELECT * WHERE { ?s ?p ?o }").unwrap());
+        let mut solutions = match results {
+            sparql::QueryResults::Solutions(solutions) => solutions,
+            _ => unreachable!(),
+        };
+        assert!(solutions.next().unwrap().is_ok());
+        drop(Cancel(interrupt.cancelled.clone()));
+        assert!(solutions.next().unwrap().is_err());
+    }
+
+    #[actix_rt::test]
+    async fn too_many_queries() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(
+            AppState::new(SledStore::open(path.path()).unwrap()).with_limits(crate::Limits {
+                max_concurrent_queries: 1,
+                ..crate::Limits::default()
+            }),
+        );
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let running = app_state
+            .blocking
+            .start_read(&app_state.queries, 1)
+            .unwrap();
+        let req = test::TestRequest::get()
+            .uri("http://localhost/query?query=ASK%20{}")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+        drop(running);
+        let req = test::TestRequest::get()
+            .uri("http://localhost/query?query=ASK%20{}")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn get_query_bad_timeout() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::get()
+            .uri("http://localhost/query?query=SELECT%20*%20WHERE%20{%20?s%20?p%20?o%20}&timeout=soon")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn get_query_named_graph() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::get()
+            .uri(
+                "http://localhost/query?query=SELECT%20*%20WHERE%20{%20?s%20?p%20?o%20}&named-graph-uri=http://example.com/a&named-graph-uri=http://example.com/b"
+            )
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn get_query_default_graph() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::get()
+            .uri(
+                "http://localhost/query?query=SELECT%20*%20WHERE%20{%20?s%20?p%20?o%20}&default-graph-uri=http://example.com/a&default-graph-uri=http://example.com/b"
+            )
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn get_bad_query() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::get()
+            .uri("http://localhost/query?query=SELECT")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn get_bad_query_problem() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::get()
+            .uri("http://localhost/query?query=SELECT%20*%0AWHERE%20{%20?s%20?p%20}")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+        assert_eq!(
+            resp.headers().get(http::header::CONTENT_TYPE).unwrap(),
+            "application/problem+json"
+        );
+        let body = test::read_body(resp).await;
+        let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem["status"], 400);
+        assert_eq!(problem["code"], "parse_error");
+        assert_eq!(problem["line"], 2);
+        assert!(problem["column"].is_u64());
+    }
+
+    #[actix_rt::test]
+    async fn get_bad_query_text() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::get()
+            .uri("http://localhost/query?query=SELECT")
+            .header("Accept", "text/html,application/xhtml+xml,*/*;q=0.8")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+        assert_eq!(
+            resp.headers().get(http::header::CONTENT_TYPE).unwrap(),
+            "text/plain; charset=utf-8"
+        );
+        let body = test::read_body(resp).await;
+        assert!(body.starts_with(b"parse_error: "));
+    }
+
+    #[actix_rt::test]
+    async fn get_without_query() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::get()
+            .uri("http://localhost/query")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(http::header::CONTENT_TYPE).unwrap(),
+            "application/n-triples"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn post_query() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::post()
+            .uri("http://localhost/query")
+            .header("Content-type", "application/sparql-query")
+            .set_payload("SELECT * WHERE { ?s ?p ?o }")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        println!("response: {:?}", resp.response().body().as_ref());
+        assert_eq!(resp.status(), http::StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn post_bad_query() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::post()
+            .uri("http://localhost/query")
+            .header("Content-type", "application/sparql-query")
+            .set_payload("SELECT")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn post_unknown_query() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::post()
+            .uri("http://localhost/query")
+            .header("Content-type", "application/sparql-todo")
+            .set_payload("SELECT")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[actix_rt::test]
+    async fn post_query_no_content_type() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::post()
+            .uri("http://localhost/query")
+            .set_payload("SELECT")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn post_federated_query() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::post()
+            .uri("http://localhost/query")
+            .header("Content-type", "application/sparql-query")
+            .set_payload("SELECT * WHERE { SERVICE <https://query.wikidata.org/sparql> { <https://en.wikipedia.org/wiki/Paris> ?p ?o } }")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn post_query_form() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::post()
+            .uri("http://localhost/query")
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .set_payload("query=SELECT%20*%20WHERE%20{%20?s%20?p%20?o%20}")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+    }
+}
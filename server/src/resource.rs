@@ -0,0 +1,388 @@
+use crate::error::{AppError, InnerError};
+use crate::graphs::write_triples;
+use crate::negotiation::{negotiate, GraphSyntax};
+use crate::{base_url, html, jsonld, AppState};
+use actix_web::{http, web, HttpRequest, HttpResponse};
+use oxigraph::model::{NamedNode, NamedOrBlankNode, Term, Triple};
+use oxigraph::SledStore;
+use serde_derive::Deserialize;
+use std::collections::HashSet;
+use std::io;
+
+/// The representations of a resource, the first being the default.
+const MEDIA_TYPES: &[&str] = &[
+    "text/turtle",
+    jsonld::MEDIA_TYPE,
+    "application/n-triples",
+    html::MEDIA_TYPE,
+];
+
+/// How many incoming links the HTML view lists at most.
+const MAX_INCOMING: usize = 500;
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct ResourceParams {
+    iri: Option<String>,
+}
+
+/// Describes a resource of the server's [`Namespace`], given by its IRI:
+/// `/resource?iri=...`.
+pub(crate) async fn get_resource(
+    request: HttpRequest,
+    params: web::Query<ResourceParams>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    match params.into_inner().iri {
+        Some(iri) => {
+            let iri = NamedNode::new(iri)?;
+            if !Namespace::of(&request)?.contains(iri.as_str()) {
+                return Err(AppError::ResourceNotFound(iri));
+            }
+            describe(request, state, iri).await
+        }
+        None => Err(AppError::BadRequest(InnerError::Str(
+            "You should set the 'iri' parameter",
+        ))),
+    }
+}
+
+/// Dereferences the IRIs under the resource prefix, by describing the IRI
+/// of the request.
+pub(crate) async fn dereference(
+    request: HttpRequest,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let iri = NamedNode::new(base_url(&request, None)?.to_string())?;
+    describe(request, state, iri).await
+}
+
+/// The IRIs the server describes: those under its resource prefix, and the
+/// graph IRIs under its graph store.
+pub(crate) struct Namespace {
+    /// The URL of the `/resource` endpoint.
+    resource: String,
+    prefixes: Vec<String>,
+}
+
+impl Namespace {
+    pub(crate) fn new(resource: String, prefixes: Vec<String>) -> Self {
+        Namespace { resource, prefixes }
+    }
+
+    /// The namespace of the server `request` was sent to.
+    pub(crate) fn of(request: &HttpRequest) -> Result<Namespace, AppError> {
+        let url = |name, elements: &[&str]| {
+            request
+                .url_for(name, elements)
+                .map(String::from)
+                .map_err(|_| AppError::InternalServerError("no route to the resources"))
+        };
+        Ok(Namespace::new(
+            url("resource", &[])?,
+            vec![url("dereference", &[""])?, url("store", &["store/"])?],
+        ))
+    }
+
+    pub(crate) fn contains(&self, iri: &str) -> bool {
+        self.prefixes.iter().any(|prefix| iri.starts_with(prefix))
+    }
+
+    /// Where HTML pages link `iri` to: its view if it is in the namespace,
+    /// else the IRI itself if it is an HTTP(S) URL. Other schemes, such as
+    /// `javascript:`, are not linked.
+    pub(crate) fn href(&self, iri: &str) -> Option<String> {
+        if self.contains(iri) {
+            Some(format!(
+                "{}?iri={}",
+                self.resource,
+                form_urlencoded::byte_serialize(iri.as_bytes()).collect::<String>()
+            ))
+        } else if is_web_url(iri) {
+            Some(iri.to_string())
+        } else {
+            None
+        }
+    }
+}
+
+fn is_web_url(iri: &str) -> bool {
+    let scheme = iri.split(':').next().unwrap_or("");
+    scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https")
+}
+
+/// Whether the client asks for HTML rather than RDF, as browsers do.
+pub(crate) fn prefers_html(request: &HttpRequest) -> bool {
+    matches!(
+        negotiate(request.headers(), MEDIA_TYPES),
+        Ok(html::MEDIA_TYPE)
+    )
+}
+
+/// Answers with the Concise Bounded Description of `iri` in RDF, or with an
+/// HTML page of its outgoing and incoming links.
+pub(crate) async fn describe(
+    request: HttpRequest,
+    state: web::Data<AppState>,
+    iri: NamedNode,
+) -> Result<HttpResponse, AppError> {
+    let media_type = negotiate(request.headers(), MEDIA_TYPES)?;
+    let node = iri.clone();
+    let description = state
+        .run(move |store| Ok(Description::compute(store, &node)?))
+        .await?;
+    if description.is_empty() {
+        return Err(AppError::ResourceNotFound(iri));
+    }
+    let mut body = Vec::new();
+    if media_type == html::MEDIA_TYPE {
+        body = description
+            .to_html(&iri, &Namespace::of(&request)?)
+            .into_bytes();
+    } else {
+        let syntax = GraphSyntax::from_media_type(media_type)
+            .ok_or(AppError::InternalServerError("Unknown mime type"))?;
+        write_triples(&description.cbd, syntax, state.context.as_ref(), &mut body)?;
+    }
+    Ok(HttpResponse::Ok()
+        .header(http::header::CONTENT_TYPE, media_type)
+        .header(
+            http::header::CONTENT_SECURITY_POLICY,
+            html::CONTENT_SECURITY_POLICY,
+        )
+        .body(body))
+}
+
+/// What the store says about a resource, in all its graphs.
+#[derive(Debug)]
+struct Description {
+    /// The triples about the resource and, recursively, about the blank
+    /// nodes they point to.
+    cbd: Vec<Triple>,
+    /// Triples pointing to the resource, at most [`MAX_INCOMING`] of them.
+    incoming: Vec<Triple>,
+    /// The quads in the graph the resource names, if it names one.
+    graph: Option<usize>,
+}
+
+impl Description {
+    fn compute(store: &SledStore, iri: &NamedNode) -> io::Result<Description> {
+        let mut cbd = Vec::new();
+        let mut seen = HashSet::new();
+        let mut visited = HashSet::new();
+        let mut pending = vec![NamedOrBlankNode::from(iri.clone())];
+        while let Some(subject) = pending.pop() {
+            if !visited.insert(subject.clone()) {
+                continue;
+            }
+            for quad in store.quads_for_pattern(Some(subject.as_ref()), None, None, None) {
+                let triple = Triple::from(quad?);
+                if let Term::BlankNode(object) = &triple.object {
+                    pending.push(object.clone().into());
+                }
+                if seen.insert(triple.clone()) {
+                    cbd.push(triple);
+                }
+            }
+        }
+
+        let mut incoming = Vec::new();
+        let mut seen = HashSet::new();
+        for quad in store.quads_for_pattern(None, None, Some(iri.as_ref().into()), None) {
+            let triple = Triple::from(quad?);
+            if seen.insert(triple.clone()) {
+                incoming.push(triple);
+                if incoming.len() == MAX_INCOMING {
+                    break;
+                }
+            }
+        }
+
+        let graph = if store.contains_named_graph(iri)? {
+            Some(
+                store
+                    .quads_for_pattern(None, None, None, Some(iri.as_ref().into()))
+                    .count(),
+            )
+        } else {
+            None
+        };
+        Ok(Description {
+            cbd,
+            incoming,
+            graph,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.cbd.is_empty() && self.incoming.is_empty() && self.graph.is_none()
+    }
+
+    fn to_html(&self, iri: &NamedNode, namespace: &Namespace) -> String {
+        let mut body = format!("<h1>{}</h1>\n", html::escape(iri.as_str()));
+        if let Some(quads) = self.graph {
+            body.push_str(&format!(
+                "<p>This is also the name of a graph of {} quads.</p>\n",
+                quads
+            ));
+        }
+        let term = |term: Term| html::term(&term, namespace);
+        body.push_str("<h2>Outgoing links</h2>\n");
+        let rows = self
+            .cbd
+            .iter()
+            .map(|t| {
+                vec![
+                    term(t.subject.clone().into()),
+                    term(t.predicate.clone().into()),
+                    term(t.object.clone()),
+                ]
+            })
+            .collect::<Vec<_>>();
+        body.push_str(&html::table(&["subject", "predicate", "object"], &rows));
+        body.push_str("<h2>Incoming links</h2>\n");
+        let rows = self
+            .incoming
+            .iter()
+            .map(|t| {
+                vec![
+                    term(t.subject.clone().into()),
+                    term(t.predicate.clone().into()),
+                ]
+            })
+            .collect::<Vec<_>>();
+        body.push_str(&html::table(&["subject", "predicate"], &rows));
+        if self.incoming.len() == MAX_INCOMING {
+            body.push_str(&format!(
+                "<p>Only the first {} incoming links are shown.</p>\n",
+                MAX_INCOMING
+            ));
+        }
+        html::page(iri.as_str(), &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_app;
+    use actix_web::{test, App};
+    use oxigraph::io::{GraphFormat, GraphParser};
+    use tempfile::tempdir;
+
+    async fn get(uri: &str, accept: &str) -> (http::StatusCode, String) {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        store
+            .update(
+                "PREFIX e: <http://e/>
+                 PREFIX l: <http://localhost/id/>
+                 INSERT DATA {
+                     l:a e:p \"x\" ; e:q [ e:r e:b ] .
+                     e:c e:link l:a .
+                     <http://localhost/store/abc> e:p \"z\" .
+                     GRAPH l:g { l:a e:p \"y\" }
+                 }",
+            )
+            .unwrap();
+        let app_state = web::Data::new(AppState::new(store));
+        let mut app = test::init_service(App::new().configure(config_app(app_state))).await;
+        let req = test::TestRequest::get()
+            .uri(uri)
+            .header("Accept", accept)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let status = resp.status();
+        let body = test::read_body(resp).await;
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[actix_rt::test]
+    async fn dereference_local_iri() {
+        let (status, body) = get("http://localhost/id/a", "text/turtle").await;
+        assert_eq!(status, http::StatusCode::OK);
+        let triples = GraphParser::from_format(GraphFormat::Turtle)
+            .read_triples(body.as_bytes())
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(triples.len(), 4);
+        assert!(triples
+            .iter()
+            .all(|t| t.predicate.as_str() != "http://e/link"));
+
+        let (status, _) = get("http://localhost/id/missing", "text/turtle").await;
+        assert_eq!(status, http::StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn resource_html() {
+        let (status, body) = get(
+            "http://localhost/resource?iri=http%3A%2F%2Flocalhost%2Fid%2Fg",
+            "text/html,application/xhtml+xml,*/*;q=0.8",
+        )
+        .await;
+        assert_eq!(status, http::StatusCode::OK);
+        assert!(body.contains("<p>This is also the name of a graph of 1 quads.</p>"));
+
+        let (_, body) = get("http://localhost/id/a", "text/html").await;
+        assert!(body.contains("<h2>Incoming links</h2>"));
+        assert!(body.contains("<td><a href=\"http://e/c\">http://e/c</a></td>"));
+    }
+
+    #[actix_rt::test]
+    async fn resource_json_ld() {
+        let (status, body) = get(
+            "http://localhost/resource?iri=http%3A%2F%2Flocalhost%2Fid%2Fa",
+            "application/ld+json",
+        )
+        .await;
+        assert_eq!(status, http::StatusCode::OK);
+        let nodes: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(nodes[0]["@id"], "http://localhost/id/a");
+        assert_eq!(nodes.as_array().unwrap().len(), 2);
+    }
+
+    #[actix_rt::test]
+    async fn dereference_store_iri() {
+        let (status, body) = get("http://localhost/store/abc", "application/n-triples").await;
+        assert_eq!(status, http::StatusCode::OK);
+        assert_eq!(body, "<http://localhost/store/abc> <http://e/p> \"z\" .\n");
+        let (status, _) = get("http://localhost/store/xyz", "application/n-triples").await;
+        assert_eq!(status, http::StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn only_the_namespace_is_described() {
+        let (status, _) = get(
+            "http://localhost/resource?iri=http%3A%2F%2Fe%2Fc",
+            "text/turtle",
+        )
+        .await;
+        assert_eq!(status, http::StatusCode::NOT_FOUND);
+
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        store
+            .update("INSERT DATA { <http://localhost/things/a> <http://e/p> 1 }")
+            .unwrap();
+        let app_state = web::Data::new(AppState::new(store).with_resource_prefix("things/"));
+        let mut app = test::init_service(
+            App::new()
+                .configure(config_app(app_state))
+                .route("/home", web::get().to(|| HttpResponse::Ok().body("home"))),
+        )
+        .await;
+        let req = test::TestRequest::get().uri("/home").to_request();
+        assert_eq!(test::read_response(&mut app, req).await, "home");
+        let req = test::TestRequest::get()
+            .uri("http://localhost/things/a")
+            .header("Accept", "application/n-triples")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let req = test::TestRequest::get().uri("/id/a").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+        assert!(resp.response().error().is_none());
+    }
+}
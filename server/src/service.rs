@@ -0,0 +1,126 @@
+use crate::error::AppError;
+use crate::graphs::{describe_graph, describe_named_graphs, sd, write_triples};
+use crate::negotiation::graph_content_negotiation;
+use crate::{base_url, AppState};
+use actix_web::{http, web, HttpRequest, HttpResponse};
+use oxigraph::model::vocab::rdf;
+use oxigraph::model::{BlankNode, NamedNode, Triple};
+
+const FORMATS: &str = "http://www.w3.org/ns/formats/";
+
+/// The formats `/query` can answer with, by their name in the W3C formats
+/// namespace.
+const RESULT_FORMATS: &[&str] = &[
+    "SPARQL_Results_XML",
+    "SPARQL_Results_JSON",
+    "SPARQL_Results_CSV",
+    "SPARQL_Results_TSV",
+    "N-Triples",
+    "Turtle",
+    "RDF_XML",
+    "JSON-LD",
+];
+
+/// Answers a `GET /query` without a query with a SPARQL 1.1 Service
+/// Description of the endpoint and of its dataset.
+///
+/// The sizes of the graphs come from the summary of the store cached by the
+/// state, so that they may be [`crate::stats::TTL`] old, and more while a
+/// new summary is computed.
+pub(crate) async fn get_service_description(
+    request: HttpRequest,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let endpoint = base_url(&request, None)?.to_string();
+    let format = graph_content_negotiation(request)?;
+    let summary = state.summary.summary(&state).await?;
+
+    let service = BlankNode::default();
+    let dataset = BlankNode::default();
+    let mut triples = vec![
+        Triple::new(service.clone(), rdf::TYPE, sd("Service")),
+        Triple::new(
+            service.clone(),
+            sd("endpoint"),
+            NamedNode::new_unchecked(endpoint),
+        ),
+        Triple::new(
+            service.clone(),
+            sd("supportedLanguage"),
+            sd("SPARQL10Query"),
+        ),
+        Triple::new(
+            service.clone(),
+            sd("supportedLanguage"),
+            sd("SPARQL11Query"),
+        ),
+        Triple::new(service.clone(), sd("feature"), sd("EmptyGraphs")),
+        Triple::new(service.clone(), sd("defaultDataset"), dataset.clone()),
+        Triple::new(dataset.clone(), rdf::TYPE, sd("Dataset")),
+    ];
+    for format in RESULT_FORMATS {
+        triples.push(Triple::new(
+            service.clone(),
+            sd("resultFormat"),
+            NamedNode::new_unchecked(format!("{}{}", FORMATS, format)),
+        ));
+    }
+    let default_graph = describe_graph(summary.default_graph, &mut triples);
+    triples.push(Triple::new(
+        dataset.clone(),
+        sd("defaultGraph"),
+        default_graph,
+    ));
+    describe_named_graphs(&dataset, &summary.graphs, &mut triples);
+
+    let mut body = Vec::new();
+    write_triples(&triples, format, None, &mut body)?;
+    Ok(HttpResponse::Ok()
+        .header(http::header::CONTENT_TYPE, format.media_type())
+        .body(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_app;
+    use actix_web::{test, App};
+    use oxigraph::io::{GraphFormat, GraphParser};
+    use oxigraph::SledStore;
+    use tempfile::tempdir;
+
+    #[actix_rt::test]
+    async fn describes_the_service() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        store
+            .update("INSERT DATA { <http://e/s> <http://e/p> 1 . GRAPH <http://e/g> { <http://e/s> <http://e/p> 2 } }")
+            .unwrap();
+        let app_state = web::Data::new(AppState::new(store));
+        let mut app = test::init_service(App::new().configure(config_app(app_state))).await;
+        let req = test::TestRequest::get()
+            .uri("http://localhost/query")
+            .header("Accept", "text/turtle")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let body = test::read_body(resp).await;
+        let triples = GraphParser::from_format(GraphFormat::Turtle)
+            .read_triples(body.as_ref())
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let objects = |predicate: &str| {
+            triples
+                .iter()
+                .filter(|t| t.predicate == sd(predicate))
+                .map(|t| t.object.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(objects("endpoint"), vec!["<http://localhost/query>"]);
+        assert_eq!(objects("resultFormat").len(), 8);
+        assert!(objects("resultFormat").contains(&"<http://www.w3.org/ns/formats/JSON-LD>".into()));
+        assert_eq!(objects("defaultGraph").len(), 1);
+        assert_eq!(objects("name"), vec!["<http://e/g>"]);
+    }
+}
@@ -0,0 +1,552 @@
+use crate::error::AppError;
+use crate::AppState;
+use actix_web::{web, HttpResponse};
+use oxigraph::model::vocab::rdf;
+use oxigraph::model::{GraphName, NamedNode, NamedOrBlankNode, Term};
+use oxigraph::SledStore;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How many resources are given as examples of the dataset.
+const EXAMPLES: usize = 3;
+
+/// How long a [`Summary`] is served after a write before it is computed
+/// again, so that a busy store isn't scanned on every request.
+pub(crate) const TTL: Duration = Duration::from_secs(60);
+
+/// Statistics of a dataset, served at `/stats` and printed by `kg-cli stats`.
+///
+/// The distinct subjects and objects, and the instances of classes, are
+/// counted by a hash of the terms, in memory that grows with the number of
+/// distinct terms. Collisions may count a few of them as one, so these
+/// fields are listed in `estimated`.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    /// Quads in all graphs.
+    pub quads: usize,
+    /// Quads in the default graph.
+    pub default_graph: usize,
+    /// Quads in each named graph, by graph name.
+    pub graphs: Vec<GraphCount>,
+    /// An estimate, see `estimated`.
+    pub distinct_subjects: usize,
+    pub distinct_predicates: usize,
+    /// An estimate, see `estimated`.
+    pub distinct_objects: usize,
+    /// The most used predicates, most used first.
+    pub top_predicates: Vec<TermCount>,
+    /// The classes with the most distinct instances, most instances first.
+    /// The instance counts are estimates, see `estimated`.
+    pub top_classes: Vec<TermCount>,
+    /// Bytes taken by the store files, if known.
+    pub disk_size: Option<u64>,
+    /// The fields whose counts are estimates rather than exact.
+    #[serde(default)]
+    pub estimated: Vec<String>,
+}
+
+/// The fields of [`Stats`] counted by hash.
+const ESTIMATED: &[&str] = &["distinct_subjects", "distinct_objects", "top_classes"];
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct GraphCount {
+    pub graph: String,
+    pub quads: usize,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct TermCount {
+    pub term: String,
+    pub count: usize,
+}
+
+impl GraphCount {
+    /// The named graphs of `store` with their quad counts, by name.
+    pub fn list(store: &SledStore) -> io::Result<Vec<GraphCount>> {
+        let mut graphs = count_named_graphs(store)?
+            .into_iter()
+            .map(|(graph, quads)| GraphCount {
+                graph: name(&graph.into()),
+                quads,
+            })
+            .collect::<Vec<_>>();
+        graphs.sort_by(|a, b| a.graph.cmp(&b.graph));
+        Ok(graphs)
+    }
+}
+
+/// The named graphs of `store` with their quad counts.
+pub(crate) fn count_named_graphs(store: &SledStore) -> io::Result<Vec<(NamedOrBlankNode, usize)>> {
+    let mut graphs = Vec::new();
+    for graph in store.named_graphs() {
+        let graph = graph?;
+        let quads = store
+            .quads_for_pattern(None, None, None, Some(graph.as_ref().into()))
+            .count();
+        graphs.push((graph, quads));
+    }
+    Ok(graphs)
+}
+
+impl Stats {
+    /// Computes the statistics of `store` in a single scan, keeping the
+    /// `top` most used predicates and classes. The disk size is that of the
+    /// directory at `path`, if given.
+    pub fn compute(store: &SledStore, path: Option<&Path>, top: usize) -> io::Result<Stats> {
+        Stats::from_summary(&Summary::compute(store)?, path, top)
+    }
+
+    fn from_summary(summary: &Summary, path: Option<&Path>, top: usize) -> io::Result<Stats> {
+        let mut graphs = summary
+            .graphs
+            .iter()
+            .map(|(graph, quads)| GraphCount {
+                graph: name(&graph.clone().into()),
+                quads: *quads,
+            })
+            .collect::<Vec<_>>();
+        graphs.sort_by(|a, b| a.graph.cmp(&b.graph));
+        Ok(Stats {
+            quads: summary.quads,
+            default_graph: summary.default_graph,
+            graphs,
+            distinct_subjects: summary.distinct_subjects,
+            distinct_predicates: summary.properties.len(),
+            distinct_objects: summary.distinct_objects,
+            top_predicates: top_counts(&summary.properties, top),
+            top_classes: top_counts(&summary.classes, top),
+            disk_size: path.map(disk_size).transpose()?,
+            estimated: ESTIMATED.iter().map(|field| field.to_string()).collect(),
+        })
+    }
+}
+
+/// What the statistics, the VoID description and the service description
+/// tell about a store, gathered in a single scan of it.
+///
+/// The distinct terms are counted by a 64-bit hash of them, which keeps the
+/// memory needed small next to the store, if still in proportion to its
+/// number of terms. Stores with billions of terms may see a few of them
+/// counted as one.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Summary {
+    pub(crate) quads: usize,
+    pub(crate) default_graph: usize,
+    /// Quads in each named graph, the empty ones included, by name.
+    pub(crate) graphs: Vec<(NamedOrBlankNode, usize)>,
+    pub(crate) distinct_subjects: usize,
+    /// Distinct subjects that are IRIs.
+    pub(crate) entities: usize,
+    pub(crate) distinct_objects: usize,
+    /// The properties with their number of triples, by IRI.
+    pub(crate) properties: Vec<(NamedNode, usize)>,
+    /// The classes with their number of distinct instances, by IRI.
+    pub(crate) classes: Vec<(NamedNode, usize)>,
+    /// The first IRI subjects of the store.
+    pub(crate) examples: Vec<NamedNode>,
+}
+
+impl Summary {
+    pub(crate) fn compute(store: &SledStore) -> io::Result<Summary> {
+        let mut default_graph = 0;
+        let mut graphs = HashMap::<NamedOrBlankNode, usize>::new();
+        let mut subjects = HashSet::new();
+        let mut entities = 0;
+        let mut objects = HashSet::new();
+        let mut properties = HashMap::<NamedNode, usize>::new();
+        let mut classes = HashMap::<NamedNode, HashSet<u64>>::new();
+        let mut examples = Vec::new();
+        let mut quads = 0;
+        for quad in store.iter() {
+            let quad = quad?;
+            quads += 1;
+            match quad.graph_name {
+                GraphName::DefaultGraph => default_graph += 1,
+                GraphName::NamedNode(graph) => *graphs.entry(graph.into()).or_default() += 1,
+                GraphName::BlankNode(graph) => *graphs.entry(graph.into()).or_default() += 1,
+            }
+            let subject = hash(&quad.subject);
+            if subjects.insert(subject) {
+                if let NamedOrBlankNode::NamedNode(node) = &quad.subject {
+                    entities += 1;
+                    if examples.len() < EXAMPLES {
+                        examples.push(node.clone());
+                    }
+                }
+            }
+            if quad.predicate == rdf::TYPE {
+                if let Term::NamedNode(class) = &quad.object {
+                    classes.entry(class.clone()).or_default().insert(subject);
+                }
+            }
+            objects.insert(hash(&quad.object));
+            *properties.entry(quad.predicate).or_default() += 1;
+        }
+        for graph in store.named_graphs() {
+            graphs.entry(graph?).or_default();
+        }
+        let mut classes = classes
+            .into_iter()
+            .map(|(class, instances)| (class, instances.len()))
+            .collect::<Vec<_>>();
+        classes.sort();
+        let mut properties = properties.into_iter().collect::<Vec<_>>();
+        properties.sort();
+        let mut graphs = graphs.into_iter().collect::<Vec<_>>();
+        graphs.sort_by_key(|(graph, _)| graph.to_string());
+        Ok(Summary {
+            quads,
+            default_graph,
+            graphs,
+            distinct_subjects: subjects.len(),
+            entities,
+            distinct_objects: objects.len(),
+            properties,
+            classes,
+            examples,
+        })
+    }
+}
+
+fn hash(term: &impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    term.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The last computed [`Summary`], with the write generation of the store it
+/// was computed at.
+///
+/// Once the store changed and the summary is older than the TTL, it is
+/// computed again in the background while the stale one is still served, so
+/// that the requests on a store with steady writes don't each wait for a
+/// scan. Only the first summary is waited for.
+pub(crate) struct Cache {
+    ttl: Duration,
+    cached: Arc<Mutex<Option<Cached>>>,
+    /// Set while a summary is computed in the background, so that a single
+    /// scan of the store runs at a time.
+    refreshing: Arc<AtomicBool>,
+    /// Held while the first summary is computed, so that the requests
+    /// meanwhile wait for it rather than scan the store too.
+    computing: futures::lock::Mutex<()>,
+}
+
+struct Cached {
+    generation: u64,
+    computed_at: Instant,
+    summary: Arc<Summary>,
+}
+
+impl Cache {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Cache {
+            ttl,
+            cached: Arc::new(Mutex::new(None)),
+            refreshing: Arc::new(AtomicBool::new(false)),
+            computing: futures::lock::Mutex::new(()),
+        }
+    }
+
+    /// The summary of the store of `state`, which may be stale while a new
+    /// one is computed, see [`Cache`].
+    pub(crate) async fn summary(&self, state: &AppState) -> Result<Arc<Summary>, AppError> {
+        if let Some(summary) = self.get(state) {
+            return Ok(summary);
+        }
+        let _computing = self.computing.lock().await;
+        // The summary may have been computed while this request waited.
+        if let Some(summary) = self.get(state) {
+            return Ok(summary);
+        }
+        // Read before the scan, so that writes made during it make the result
+        // stale.
+        let generation = state.generation();
+        let summary = Arc::new(state.run(|store| Ok(Summary::compute(store)?)).await?);
+        put(&self.cached, generation, summary.clone());
+        Ok(summary)
+    }
+
+    /// The cached summary, if any. A new one is computed in the background
+    /// if the store changed since and the TTL has passed.
+    fn get(&self, state: &AppState) -> Option<Arc<Summary>> {
+        let generation = state.generation();
+        let cached = self.cached.lock().unwrap();
+        let cached = cached.as_ref()?;
+        if cached.generation != generation && cached.computed_at.elapsed() >= self.ttl {
+            self.refresh(state);
+        }
+        Some(cached.summary.clone())
+    }
+
+    /// Computes the summary again on the blocking pool, unless that is being
+    /// done already.
+    fn refresh(&self, state: &AppState) {
+        if self.refreshing.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let refreshing = Refreshing(self.refreshing.clone());
+        let generation = state.generation();
+        let cached = self.cached.clone();
+        let store = state.store.clone();
+        let replacing = state.replacing.clone();
+        state.blocking.execute(move || {
+            let _refreshing = refreshing;
+            let summary = {
+                let _shared = replacing.shared();
+                Summary::compute(&store)
+            };
+            match summary {
+                Ok(summary) => put(&cached, generation, Arc::new(summary)),
+                Err(err) => log::warn!("failed to compute the summary of the store: {}", err),
+            }
+        });
+    }
+}
+
+fn put(cached: &Mutex<Option<Cached>>, generation: u64, summary: Arc<Summary>) {
+    *cached.lock().unwrap() = Some(Cached {
+        generation,
+        computed_at: Instant::now(),
+        summary,
+    });
+}
+
+/// Clears the flag of a background refresh once it is done, even if it
+/// panicked.
+struct Refreshing(Arc<AtomicBool>);
+
+impl Drop for Refreshing {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// IRIs as is, other terms in N-Triples syntax.
+fn name(term: &Term) -> String {
+    match term {
+        Term::NamedNode(node) => node.as_str().to_string(),
+        term => term.to_string(),
+    }
+}
+
+fn top_counts(counts: &[(NamedNode, usize)], top: usize) -> Vec<TermCount> {
+    let mut counts = counts
+        .iter()
+        .map(|(term, count)| TermCount {
+            term: term.as_str().to_string(),
+            count: *count,
+        })
+        .collect::<Vec<_>>();
+    counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.term.cmp(&b.term)));
+    counts.truncate(top);
+    counts
+}
+
+/// The size of the files under `path`.
+fn disk_size(path: &Path) -> io::Result<u64> {
+    let metadata = fs::metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        size += disk_size(&entry?.path())?;
+    }
+    Ok(size)
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct StatsParams {
+    top: Option<usize>,
+}
+
+/// Serves the statistics from the [`Summary`] cached by the state, so that
+/// a single scan of the store runs at a time whatever the number of
+/// requests, and none while the summary is fresh.
+///
+/// The fields counted by hash are listed in `estimated` in the response.
+pub(crate) async fn get_stats(
+    params: web::Query<StatsParams>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let top = params.top.unwrap_or(10);
+    let summary = state.summary.summary(&state).await?;
+    let path = state.path.clone();
+    let stats = state
+        .run(move |_| Ok(Stats::from_summary(&summary, path.as_deref(), top)?))
+        .await?;
+    Ok(HttpResponse::Ok().json(stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_app;
+    use actix_web::{http, test, App};
+    use oxigraph::io::DatasetFormat;
+    use tempfile::tempdir;
+
+    const DATA: &str = "\
+<http://e/a> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <http://e/C> .
+<http://e/b> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <http://e/C> .
+<http://e/a> <http://e/p> \"1\" <http://e/g> .
+<http://e/a> <http://e/p> \"2\" <http://e/g> .
+<http://e/a> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <http://e/C> <http://e/g> .
+<http://e/b> <http://e/q> \"1\" _:g .
+";
+
+    fn store() -> (tempfile::TempDir, SledStore) {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        store
+            .load_dataset(DATA.as_bytes(), DatasetFormat::NQuads, None)
+            .unwrap();
+        (dir, store)
+    }
+
+    #[test]
+    fn counts() {
+        let (dir, store) = store();
+        let stats = Stats::compute(&store, Some(dir.path()), 2).unwrap();
+        assert_eq!(stats.quads, 6);
+        assert_eq!(stats.default_graph, 2);
+        assert_eq!(stats.graphs.len(), 2);
+        assert_eq!(
+            stats.graphs[1],
+            GraphCount {
+                graph: "http://e/g".into(),
+                quads: 3
+            }
+        );
+        assert_eq!(stats.distinct_subjects, 2);
+        assert_eq!(stats.distinct_predicates, 3);
+        assert_eq!(stats.distinct_objects, 3);
+        assert_eq!(
+            stats
+                .top_predicates
+                .iter()
+                .map(|p| (p.term.as_str(), p.count))
+                .collect::<Vec<_>>(),
+            vec![
+                ("http://www.w3.org/1999/02/22-rdf-syntax-ns#type", 3),
+                ("http://e/p", 2)
+            ]
+        );
+        // The instance in two graphs counts once, as in the VoID description.
+        assert_eq!(
+            stats.top_classes,
+            vec![TermCount {
+                term: "http://e/C".into(),
+                count: 2
+            }]
+        );
+        assert!(stats.disk_size.unwrap() > 0);
+        assert_eq!(
+            stats.estimated,
+            vec!["distinct_subjects", "distinct_objects", "top_classes"]
+        );
+    }
+
+    #[test]
+    fn summary() {
+        let store = SledStore::new().unwrap();
+        store
+            .update(
+                "PREFIX e: <http://e/>
+                 INSERT DATA {
+                     e:a a e:C ; e:p 1 .
+                     _:b a e:C .
+                     GRAPH e:g { e:a a e:C ; e:p 2 }
+                 }",
+            )
+            .unwrap();
+        store
+            .insert_named_graph(NamedNode::new("http://e/empty").unwrap().as_ref())
+            .unwrap();
+        let iri = |iri: &str| NamedNode::new(iri).unwrap();
+        let summary = Summary::compute(&store).unwrap();
+        assert_eq!(summary.quads, 5);
+        assert_eq!(summary.default_graph, 3);
+        assert_eq!(
+            summary.graphs,
+            vec![
+                (iri("http://e/empty").into(), 0),
+                (iri("http://e/g").into(), 2)
+            ]
+        );
+        assert_eq!(summary.entities, 1);
+        assert_eq!(summary.distinct_subjects, 2);
+        assert_eq!(summary.distinct_objects, 3);
+        assert_eq!(summary.classes, vec![(iri("http://e/C"), 2)]);
+        assert_eq!(
+            summary.properties,
+            vec![
+                (iri("http://e/p"), 2),
+                (iri("http://www.w3.org/1999/02/22-rdf-syntax-ns#type"), 3)
+            ]
+        );
+        assert_eq!(summary.examples, vec![iri("http://e/a")]);
+    }
+
+    #[actix_rt::test]
+    async fn cache_follows_writes() {
+        let (_dir, store) = store();
+        let state = AppState::new(store);
+        let cache = Cache::new(Duration::from_secs(0));
+        let summary = cache.summary(&state).await.unwrap();
+        assert!(Arc::ptr_eq(&summary, &cache.summary(&state).await.unwrap()));
+        drop(state.writing());
+        // The stale summary is served while the new one is computed.
+        assert!(Arc::ptr_eq(&summary, &cache.summary(&state).await.unwrap()));
+        let mut refreshed = cache.summary(&state).await.unwrap();
+        for _ in 0..100 {
+            if !Arc::ptr_eq(&summary, &refreshed) {
+                break;
+            }
+            actix_web::rt::time::delay_for(Duration::from_millis(10)).await;
+            refreshed = cache.summary(&state).await.unwrap();
+        }
+        assert!(!Arc::ptr_eq(&summary, &refreshed));
+        assert!(Arc::ptr_eq(
+            &refreshed,
+            &cache.summary(&state).await.unwrap()
+        ));
+
+        let cache = Cache::new(TTL);
+        let summary = cache.summary(&state).await.unwrap();
+        drop(state.writing());
+        assert!(Arc::ptr_eq(&summary, &cache.summary(&state).await.unwrap()));
+    }
+
+    #[actix_rt::test]
+    async fn concurrent_misses_share_a_computation() {
+        let (_dir, store) = store();
+        let state = AppState::new(store);
+        let cache = Cache::new(Duration::from_secs(0));
+        let (a, b) = futures::join!(cache.summary(&state), cache.summary(&state));
+        assert!(Arc::ptr_eq(&a.unwrap(), &b.unwrap()));
+    }
+
+    #[actix_rt::test]
+    async fn get_stats_json() {
+        let (_dir, store) = store();
+        let app_state = web::Data::new(AppState::new(store));
+        let mut app = test::init_service(App::new().configure(config_app(app_state))).await;
+        let req = test::TestRequest::get().uri("/stats?top=1").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let stats: Stats = test::read_body_json(resp).await;
+        assert_eq!(stats.quads, 6);
+        assert_eq!(stats.top_predicates.len(), 1);
+        assert_eq!(stats.disk_size, None);
+    }
+}
@@ -0,0 +1,1165 @@
+use crate::error::{AppError, InnerError};
+use crate::jsonld::{self, JsonLdWriter};
+use crate::negotiation::{
+    dataset_content_negotiation, graph_content_negotiation, DatasetSyntax, GraphSyntax,
+};
+use crate::{base_url, resource, stream, AppState};
+use actix_web::{http, web, HttpRequest, HttpResponse};
+use oxigraph::io::GraphFormat;
+use oxigraph::model;
+use oxigraph::SledStore;
+use serde_derive::Deserialize;
+use std::io::{self, BufRead};
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct StoreGraphInfo {
+    default: Option<String>,
+    graph: Option<String>,
+    /// The JSON-LD context to compact answers against.
+    context: Option<String>,
+}
+
+pub(crate) async fn delete_store(
+    request: HttpRequest,
+    info: web::Query<StoreGraphInfo>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let _writing = state.writing();
+    use model::{GraphName, GraphNameRef};
+
+    if let Some(target) = store_target(&request, info.into_inner())? {
+        let graph = target.clone();
+        let found = state
+            .run(move |store| {
+                Ok(match &graph {
+                    GraphName::DefaultGraph => {
+                        store.clear_graph(GraphNameRef::DefaultGraph)?;
+                        true
+                    }
+                    GraphName::NamedNode(graph) => remove_named_graph(store, graph)?,
+                    GraphName::BlankNode(graph) => remove_named_graph(store, graph)?,
+                })
+            })
+            .await?;
+        if !found {
+            return Err(AppError::GraphNotFound(target));
+        }
+    } else {
+        state.run(|store| Ok(store.clear()?)).await?;
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub(crate) async fn get_store(
+    request: HttpRequest,
+    info: web::Query<StoreGraphInfo>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let store = state.store.clone();
+    let info = info.into_inner();
+    let context = info.context.clone();
+    let (format, body) = if let Some(target) = store_target(&request, info)? {
+        // A graph IRI fetched directly, as those minted by POST, is also a
+        // resource: browsers get its page, and it is described if it
+        // doesn't name a graph.
+        if let (model::GraphName::NamedNode(iri), false) =
+            (&target, request.match_info().get("path") == Some("store"))
+        {
+            if resource::prefers_html(&request) || !graph_exists(&state, &target).await? {
+                return resource::describe(request, state, iri.clone()).await;
+            }
+        }
+        if !graph_exists(&state, &target).await? {
+            return Err(AppError::GraphNotFound(target));
+        }
+        let syntax = graph_content_negotiation(request)?;
+        let body = match syntax {
+            GraphSyntax::Format(format) => spawn_dump(&state, move |writer| {
+                store.dump_graph(writer, format, &target)
+            })?,
+            GraphSyntax::JsonLd => {
+                let context = state.jsonld_context(context.as_deref())?;
+                spawn_dump(&state, move |writer| {
+                    let mut writer = JsonLdWriter::new(writer, context.as_ref())?;
+                    for quad in store.quads_for_pattern(None, None, None, Some(target.as_ref())) {
+                        writer.write_triple(&quad?.into())?;
+                    }
+                    writer.finish()?;
+                    Ok(())
+                })?
+            }
+        };
+        (syntax.media_type(), body)
+    } else {
+        let syntax = dataset_content_negotiation(request)?;
+        let body = match syntax {
+            DatasetSyntax::Format(format) => {
+                spawn_dump(&state, move |writer| store.dump_dataset(writer, format))?
+            }
+            DatasetSyntax::JsonLd => {
+                let context = state.jsonld_context(context.as_deref())?;
+                spawn_dump(&state, move |writer| {
+                    let mut writer = JsonLdWriter::new(writer, context.as_ref())?;
+                    for quad in store.iter() {
+                        writer.write_quad(&quad?)?;
+                    }
+                    writer.finish()?;
+                    Ok(())
+                })?
+            }
+        };
+        (syntax.media_type(), body)
+    };
+    Ok(HttpResponse::Ok()
+        .header(http::header::CONTENT_TYPE, format)
+        .streaming(body))
+}
+
+/// Streams what `write` writes on the blocking thread pool, unless
+/// `max_concurrent_dumps` dumps, or reads of all kinds on as many threads as
+/// they may take, are running already.
+///
+/// The dump is cut short once the client has read nothing for
+/// `write_idle_timeout`, so that a client that stops reading doesn't keep
+/// the thread.
+fn spawn_dump<F>(state: &AppState, write: F) -> Result<stream::Body, AppError>
+where
+    F: FnOnce(&mut stream::ChunkWriter) -> io::Result<()> + Send + 'static,
+{
+    let max = state.limits.max_concurrent_dumps;
+    let permit = state
+        .blocking
+        .start_read(&state.dumps, max)
+        .ok_or_else(|| AppError::TooManyDumps(max.min(state.blocking.read_threads())))?;
+    let replacing = state.replacing.clone();
+    let idle = state.limits.write_idle_timeout();
+    Ok(stream::spawn(&state.blocking, move |writer| {
+        let _permit = permit;
+        let _shared = replacing.shared();
+        writer.set_idle_timeout(idle);
+        write(writer)
+    }))
+}
+
+pub(crate) async fn head_store(
+    request: HttpRequest,
+    info: web::Query<StoreGraphInfo>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    if let Some(target) = store_target(&request, info.into_inner())? {
+        if graph_exists(&state, &target).await? {
+            Ok(HttpResponse::Ok().finish())
+        } else {
+            Err(AppError::GraphNotFound(target))
+        }
+    } else {
+        Ok(HttpResponse::Ok().finish())
+    }
+}
+
+// #[post("/store")]
+pub(crate) async fn post_store(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    payload: web::Payload,
+    info: web::Query<StoreGraphInfo>,
+) -> Result<HttpResponse, AppError> {
+    let _writing = state.writing();
+    use mime::Mime;
+    use model::NamedNode;
+    use std::str::FromStr;
+
+    if let Some(content_type) = req.headers().get("content-type") {
+        let content_type: Mime = Mime::from_str(content_type.to_str()?)?;
+        log::debug!("post_store: content_type = {}", content_type);
+        if let Some(target) = store_target(&req, info.into_inner())? {
+            if let Some(syntax) = GraphSyntax::from_media_type(content_type.essence_str()) {
+                let new = !graph_exists(&state, &target).await?;
+                let base_iri = upload_base_iri(&req, &target)?;
+                let staged = stage(
+                    &state,
+                    payload,
+                    state.limits.upload_size(syntax == GraphSyntax::JsonLd),
+                    move |staged, reader| {
+                        load_graph(
+                            staged,
+                            reader,
+                            syntax,
+                            &model::GraphName::DefaultGraph,
+                            &base_iri,
+                        )
+                    },
+                )
+                .await?;
+                state
+                    .run(move |store| Ok(copy_graph(&staged.store, store, &target)?))
+                    .await?;
+                Ok(if new {
+                    HttpResponse::Created().finish()
+                } else {
+                    HttpResponse::NoContent().finish()
+                })
+            } else {
+                Err(AppError::UnsupportedMediaType(content_type.to_string()))
+            }
+        } else if let Some(syntax) = DatasetSyntax::from_media_type(content_type.essence_str()) {
+            let base_iri = base_url(&req, None)?.to_string();
+            let staged = stage(
+                &state,
+                payload,
+                state.limits.upload_size(syntax == DatasetSyntax::JsonLd),
+                move |staged, reader| load_dataset(staged, reader, syntax, &base_iri),
+            )
+            .await?;
+            state
+                .run(move |store| Ok(copy_dataset(&staged.store, store)?))
+                .await?;
+            Ok(HttpResponse::NoContent().finish())
+        } else if let Some(format) = GraphFormat::from_media_type(content_type.essence_str()) {
+            let graph = NamedNode::new(
+                base_url(
+                    &req,
+                    Some(&format!(
+                        "{}/{:x}",
+                        req.uri().path(),
+                        rand::random::<u128>()
+                    )),
+                )?
+                .to_string(),
+            )?;
+            let graph_iri = graph.as_str().to_owned();
+
+            let staged = stage(
+                &state,
+                payload,
+                state.limits.upload_size(false),
+                move |staged, reader| {
+                    staged.load_graph(
+                        reader,
+                        format,
+                        &model::GraphName::DefaultGraph,
+                        Some(graph_iri.as_str()),
+                    )
+                },
+            )
+            .await?;
+            let target = graph.clone().into();
+            state
+                .run(move |store| Ok(copy_graph(&staged.store, store, &target)?))
+                .await?;
+            Ok(HttpResponse::Created()
+                .header(http::header::LOCATION, graph.into_string())
+                .finish())
+        } else {
+            Err(AppError::UnsupportedMediaType(content_type.to_string()))
+        }
+    } else {
+        Err(AppError::BadRequest(InnerError::Str(
+            "No Content-Type given",
+        )))
+    }
+}
+
+/// Replaces a graph, or the whole dataset when given a dataset media type and
+/// no graph, with the body of the request, atomically.
+pub(crate) async fn put_store(
+    request: HttpRequest,
+    info: web::Query<StoreGraphInfo>,
+    payload: web::Payload,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let _writing = state.writing();
+    use http::header;
+    use mime::Mime;
+    use model::GraphName;
+    use std::str::FromStr;
+
+    if let Some(content_type) = request.headers().get(header::CONTENT_TYPE) {
+        let content_type: Mime = Mime::from_str(content_type.to_str()?)?;
+        log::debug!(
+            "put_store: content_type = {}, query = {:?}",
+            content_type,
+            info
+        );
+        if let Some(target) = store_target(&request, info.into_inner())? {
+            if let Some(syntax) = GraphSyntax::from_media_type(content_type.essence_str()) {
+                let base_iri = upload_base_iri(&request, &target)?;
+                let staged = stage(
+                    &state,
+                    payload,
+                    state.limits.upload_size(syntax == GraphSyntax::JsonLd),
+                    move |staged, reader| {
+                        load_graph(staged, reader, syntax, &GraphName::DefaultGraph, &base_iri)
+                    },
+                )
+                .await?;
+                let new = !state
+                    .replace(move |store| Ok(replace_graph(store, &staged.store, &target)?))
+                    .await?;
+                if new {
+                    Ok(HttpResponse::Created().finish())
+                } else {
+                    Ok(HttpResponse::NoContent().finish())
+                }
+            } else {
+                Err(AppError::UnsupportedMediaType(content_type.to_string()))
+            }
+        } else if let Some(syntax) = DatasetSyntax::from_media_type(content_type.essence_str()) {
+            let base_iri = base_url(&request, None)?.to_string();
+            let staged = stage(
+                &state,
+                payload,
+                state.limits.upload_size(syntax == DatasetSyntax::JsonLd),
+                move |staged, reader| load_dataset(staged, reader, syntax, &base_iri),
+            )
+            .await?;
+            let new = state
+                .replace(move |store| Ok(replace_dataset(store, &staged.store)?))
+                .await?;
+            if new {
+                Ok(HttpResponse::Created().finish())
+            } else {
+                Ok(HttpResponse::NoContent().finish())
+            }
+        } else {
+            Err(AppError::UnsupportedMediaType(content_type.to_string()))
+        }
+    } else {
+        Err(AppError::BadRequest(InnerError::Str(
+            "No Content-Type given",
+        )))
+    }
+}
+
+/// A request payload parsed into a store of its own, on disk next to the
+/// served one, until it is copied there.
+struct Staged {
+    store: SledStore,
+    _dir: tempfile::TempDir,
+}
+
+/// Parses `payload` with `load` into a new [`Staged`] store.
+///
+/// A payload that fails to parse, is too large or is cut short so leaves the
+/// served store untouched, and the writes to it only start once the whole
+/// payload has been received, however slowly the client sends it.
+async fn stage<F>(
+    state: &AppState,
+    payload: web::Payload,
+    limit: u64,
+    load: F,
+) -> Result<Staged, AppError>
+where
+    F: FnOnce(&SledStore, stream::PayloadReader) -> io::Result<()> + Send + 'static,
+{
+    let dir = state.staging_dir()?;
+    let idle = state.limits.upload_idle_timeout();
+    stream::read_payload(&state.blocking, payload, limit, idle, move |reader| {
+        let store = SledStore::open(dir.path())?;
+        load(&store, reader)?;
+        Ok(Staged { store, _dir: dir })
+    })
+    .await
+}
+
+/// Loads a graph written in `syntax` into `graph`, resolving relative IRIs
+/// against `base_iri`.
+fn load_graph(
+    store: &SledStore,
+    reader: impl BufRead,
+    syntax: GraphSyntax,
+    graph: &model::GraphName,
+    base_iri: &str,
+) -> io::Result<()> {
+    match syntax {
+        GraphSyntax::Format(format) => store.load_graph(reader, format, graph, Some(base_iri)),
+        GraphSyntax::JsonLd => {
+            let quads = jsonld::parse(reader, Some(base_iri))?;
+            if quads.iter().any(|quad| !quad.graph_name.is_default_graph()) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "a graph can't contain named graphs",
+                ));
+            }
+            for quad in &quads {
+                store.insert(model::QuadRef::new(
+                    quad.subject.as_ref(),
+                    quad.predicate.as_ref(),
+                    quad.object.as_ref(),
+                    graph.as_ref(),
+                ))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Loads a dataset written in `syntax`, resolving relative IRIs against
+/// `base_iri`.
+fn load_dataset(
+    store: &SledStore,
+    reader: impl BufRead,
+    syntax: DatasetSyntax,
+    base_iri: &str,
+) -> io::Result<()> {
+    match syntax {
+        DatasetSyntax::Format(format) => store.load_dataset(reader, format, Some(base_iri)),
+        DatasetSyntax::JsonLd => {
+            for quad in jsonld::parse(reader, Some(base_iri))? {
+                store.insert(&quad)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// The IRI relative IRIs in an upload to `target` resolve against: the graph
+/// IRI, or the request URL for the default graph.
+fn upload_base_iri(request: &HttpRequest, target: &model::GraphName) -> Result<String, AppError> {
+    match target {
+        model::GraphName::NamedNode(graph) => Ok(graph.as_str().to_owned()),
+        _ => Ok(base_url(request, None)?.to_string()),
+    }
+}
+
+async fn graph_exists(state: &AppState, graph: &model::GraphName) -> Result<bool, AppError> {
+    use model::GraphName;
+
+    let graph = graph.clone();
+    state
+        .run(move |store| {
+            Ok(match &graph {
+                GraphName::DefaultGraph => true,
+                GraphName::NamedNode(graph) => store.contains_named_graph(graph)?,
+                GraphName::BlankNode(graph) => store.contains_named_graph(graph)?,
+            })
+        })
+        .await
+}
+
+/// Removes a named graph, returning `false` if it did not exist.
+fn remove_named_graph<'a>(
+    store: &SledStore,
+    graph: impl Into<model::NamedOrBlankNodeRef<'a>>,
+) -> io::Result<bool> {
+    let graph = graph.into();
+    if store.contains_named_graph(graph)? {
+        store.remove_named_graph(graph)?;
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+/// Copies the default graph of `staged` into `graph` of `store`.
+fn copy_graph(staged: &SledStore, store: &SledStore, graph: &model::GraphName) -> io::Result<()> {
+    match graph {
+        model::GraphName::NamedNode(graph) => store.insert_named_graph(graph)?,
+        model::GraphName::BlankNode(graph) => store.insert_named_graph(graph)?,
+        model::GraphName::DefaultGraph => (),
+    }
+    for quad in staged.quads_for_pattern(None, None, None, Some(model::GraphNameRef::DefaultGraph))
+    {
+        let quad = quad?;
+        store.insert(model::QuadRef::new(
+            quad.subject.as_ref(),
+            quad.predicate.as_ref(),
+            quad.object.as_ref(),
+            graph.as_ref(),
+        ))?;
+    }
+    Ok(())
+}
+
+/// Copies the named graphs and the quads of `staged` into `store`.
+fn copy_dataset(staged: &SledStore, store: &SledStore) -> io::Result<()> {
+    for graph in staged.named_graphs() {
+        store.insert_named_graph(&graph?)?;
+    }
+    for quad in staged.iter() {
+        store.insert(&quad?)?;
+    }
+    Ok(())
+}
+
+/// Replaces the content of `graph` in `store` with the default graph of
+/// `staged`, creating the graph if needed. Returns `true` if it existed.
+///
+/// See [`replace_dataset`] for how it is kept atomic.
+fn replace_graph(
+    store: &SledStore,
+    staged: &SledStore,
+    graph: &model::GraphName,
+) -> io::Result<bool> {
+    use model::{GraphName, GraphNameRef, Quad};
+
+    let existed = match graph {
+        GraphName::NamedNode(graph) => store.contains_named_graph(graph)?,
+        GraphName::BlankNode(graph) => store.contains_named_graph(graph)?,
+        GraphName::DefaultGraph => true,
+    };
+    let mut removed = Vec::new();
+    for quad in store.quads_for_pattern(None, None, None, Some(graph.as_ref())) {
+        let quad = quad?;
+        let staged_quad = Quad::new(
+            quad.subject.clone(),
+            quad.predicate.clone(),
+            quad.object.clone(),
+            GraphName::DefaultGraph,
+        );
+        if !staged.contains(&staged_quad)? {
+            removed.push(quad);
+        }
+    }
+    let mut added = Vec::new();
+    for quad in staged.quads_for_pattern(None, None, None, Some(GraphNameRef::DefaultGraph)) {
+        let quad = quad?;
+        let quad = Quad::new(quad.subject, quad.predicate, quad.object, graph.clone());
+        if !store.contains(&quad)? {
+            added.push(quad);
+        }
+    }
+    swap(store, &removed, &added)?;
+    // Transactions can't add a named graph without quads.
+    match graph {
+        GraphName::NamedNode(graph) => store.insert_named_graph(graph)?,
+        GraphName::BlankNode(graph) => store.insert_named_graph(graph)?,
+        GraphName::DefaultGraph => (),
+    }
+    Ok(existed)
+}
+
+/// Replaces the content of `store`, named graphs included, with the one of
+/// `staged`. Returns `true` if `store` was empty.
+///
+/// The quads that change are swapped in one transaction, so the store never
+/// holds a partly replaced dataset, even if the server stops meanwhile. Run
+/// it with [`AppState::replace`] so that no query sees it either.
+/// Transactions can't add or remove named graphs without quads, so those
+/// are only added and removed once it is committed.
+///
+/// Sled can't be read from while a transaction is open, and keeps the
+/// transaction in memory until it is committed, so the quads that change are
+/// held in memory: the upload limits bound how many.
+fn replace_dataset(store: &SledStore, staged: &SledStore) -> io::Result<bool> {
+    let new = store.is_empty();
+    let mut removed = Vec::new();
+    for quad in store.iter() {
+        let quad = quad?;
+        if !staged.contains(&quad)? {
+            removed.push(quad);
+        }
+    }
+    let mut added = Vec::new();
+    for quad in staged.iter() {
+        let quad = quad?;
+        if !store.contains(&quad)? {
+            added.push(quad);
+        }
+    }
+    swap(store, &removed, &added)?;
+    for graph in store.named_graphs() {
+        let graph = graph?;
+        if !staged.contains_named_graph(&graph)? {
+            store.remove_named_graph(&graph)?;
+        }
+    }
+    for graph in staged.named_graphs() {
+        store.insert_named_graph(&graph?)?;
+    }
+    Ok(new)
+}
+
+/// Removes `removed` from `store` and inserts `added` in one transaction.
+fn swap(store: &SledStore, removed: &[model::Quad], added: &[model::Quad]) -> io::Result<()> {
+    use oxigraph::store::sled::SledConflictableTransactionError;
+
+    store
+        .transaction(|transaction| {
+            for quad in removed {
+                transaction.remove(quad)?;
+            }
+            for quad in added {
+                transaction.insert(quad)?;
+            }
+            Ok(()) as Result<(), SledConflictableTransactionError<io::Error>>
+        })
+        .map_err(io::Error::from)
+}
+
+fn store_target(
+    request: &HttpRequest,
+    info: StoreGraphInfo,
+) -> Result<Option<model::GraphName>, AppError> {
+    use oxigraph::model::NamedNode;
+
+    // Compare the matched route rather than the whole path, so that the
+    // service can be mounted under a prefix.
+    if request.match_info().get("path") == Some("store") {
+        if let Some(graph) = info.graph {
+            if info.default.is_some() {
+                Err(AppError::BadRequest(InnerError::Str(
+                    "Both graph and default parameters should not be set at the same time",
+                )))
+            } else if let Ok(graph) = NamedNode::new(graph.as_str()) {
+                Ok(Some(graph.into()))
+            } else {
+                // A relative reference, taken as a path on this server.
+                Ok(Some(
+                    NamedNode::new(base_url(request, Some(&graph))?.to_string())?.into(),
+                ))
+            }
+        } else if info.default.is_some() {
+            Ok(Some(model::GraphName::DefaultGraph))
+        } else {
+            Ok(None)
+        }
+    } else {
+        Ok(Some(
+            NamedNode::new(base_url(request, None)?.to_string())?.into(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config_app, AppState};
+    use actix_web::{http, test, App};
+    use oxigraph::SledStore;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[actix_rt::test]
+    async fn post_dataset_file() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::post()
+            .uri("/store")
+            .header("Content-Type", "application/trig")
+            .set_payload("<http://example.com> <http://example.com> <http://example.com> .")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+    }
+
+    #[actix_rt::test]
+    async fn post_graph_file() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::post()
+            .uri("http://example.com/store")
+            .header("Content-Type", "text/turtle")
+            .set_payload("<http://example.com/ns/data#i01> <http://example.com/ns/book#firstName> \"Richard\" .")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::CREATED);
+        assert!(resp.headers().get(http::header::LOCATION).is_some());
+    }
+
+    #[actix_rt::test]
+    async fn post_graph_file_default() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::post()
+            .uri("http://example.com/store?default")
+            .header("Content-Type", "text/turtle")
+            .set_payload("<http://example.com/ns/data#i01> <http://example.com/ns/book#firstName> \"Richard\" .")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+        // assert!(resp.headers().get(http::header::LOCATION).is_some());
+    }
+
+    #[actix_rt::test]
+    async fn post_graph_file_indirect() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::post()
+            .uri("/store?graph=http%3A%2F%2Fexample.com%2Fg")
+            .header("Content-Type", "text/turtle")
+            .set_payload("<http://example.com/s> <http://example.com/p> \"o\" .")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::CREATED);
+        let graph = model::NamedNode::new("http://example.com/g").unwrap();
+        assert!(app_state.store.contains_named_graph(&graph).unwrap());
+    }
+
+    #[actix_rt::test]
+    async fn post_no_content() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::post().uri("/store").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn post_unsupported_file() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::post()
+            .header("Content-Type", "text/foo")
+            .uri("/store")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[actix_rt::test]
+    async fn post_wrong_file() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/trig")
+            .uri("/store")
+            .set_payload("<http://example.com>")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn post_too_large_file() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(
+            AppState::new(SledStore::open(path.path()).unwrap()).with_limits(crate::Limits {
+                max_upload_size: 16,
+                ..crate::Limits::default()
+            }),
+        );
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::post()
+            .uri("/store?default")
+            .header("Content-Type", "application/n-triples")
+            .set_payload("<http://example.com> <http://example.com> <http://example.com> .")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[actix_rt::test]
+    async fn put_too_large_json_ld_dataset() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(
+            AppState::new(SledStore::open(path.path()).unwrap()).with_limits(crate::Limits {
+                max_jsonld_upload_size: 16,
+                ..crate::Limits::default()
+            }),
+        );
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::put()
+            .uri("/store")
+            .header("Content-Type", "application/ld+json")
+            .set_payload(r#"{"@id": "http://example.com/s", "http://example.com/p": 1}"#)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::PAYLOAD_TOO_LARGE);
+
+        let req = test::TestRequest::put()
+            .uri("/store")
+            .header("Content-Type", "application/n-quads")
+            .set_payload("<http://example.com/s> <http://example.com/p> \"1\" .")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::CREATED);
+    }
+
+    #[actix_rt::test]
+    async fn put_dataset_file() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::put()
+            .uri("/store")
+            .header("Content-Type", "application/n-quads")
+            .set_payload("<http://example.com/a> <http://example.com/p> <http://example.com/o> <http://example.com/g> .")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::CREATED);
+        app_state
+            .store
+            .insert_named_graph(model::NamedNodeRef::new("http://example.com/empty").unwrap())
+            .unwrap();
+
+        let req = test::TestRequest::put()
+            .uri("/store")
+            .header("Content-Type", "application/n-quads")
+            .set_payload("<http://example.com/b> <http://example.com/p> <http://example.com/o> .")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+
+        let req = test::TestRequest::get()
+            .uri("/store")
+            .header("Accept", "application/n-quads")
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(
+            body,
+            "<http://example.com/b> <http://example.com/p> <http://example.com/o> .\n"
+        );
+        assert_eq!(app_state.store.named_graphs().count(), 0);
+    }
+
+    #[actix_rt::test]
+    async fn json_ld_graph() {
+        let path = tempdir().unwrap();
+        let context = jsonld::Context::parse(&serde_json::json!({ "e": "http://e/" })).unwrap();
+        let app_state = web::Data::new(
+            AppState::new(SledStore::open(path.path()).unwrap()).with_jsonld_context(context),
+        );
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::post()
+            .uri("/store?graph=http%3A%2F%2Fe%2Fg")
+            .header("Content-Type", "application/ld+json; charset=utf-8")
+            .set_payload(
+                r#"{ "@context": { "@vocab": "http://e/" }, "@id": "http://e/s", "name": "S" }"#,
+            )
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::CREATED);
+
+        let req = test::TestRequest::get()
+            .uri("/store?graph=http%3A%2F%2Fe%2Fg")
+            .header("Accept", "application/ld+json")
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(
+            body,
+            "{\"@context\": {\"e\":\"http://e/\"},\n\"@graph\": [\n\
+             {\"@id\":\"e:s\",\"e:name\":\"S\"}\n]}\n"
+        );
+
+        let req = test::TestRequest::get()
+            .uri("/store?graph=http%3A%2F%2Fe%2Fg&context=%7B%22name%22%3A%22http%3A%2F%2Fe%2Fname%22%7D")
+            .header("Accept", "application/ld+json")
+            .to_request();
+        let body: serde_json::Value = test::read_response_json(&mut app, req).await;
+        assert_eq!(body["@graph"][0]["name"], "S");
+
+        let req = test::TestRequest::get()
+            .uri("/store?graph=http%3A%2F%2Fe%2Fg&context=%5B")
+            .header("Accept", "application/ld+json")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::put()
+            .uri("/store?graph=http%3A%2F%2Fe%2Fg")
+            .header("Content-Type", "application/ld+json")
+            .set_payload(r#"{ "@id": "http://e/h", "@graph": { "http://e/p": "x" } }"#)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn put_json_ld_dataset() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::put()
+            .uri("/store")
+            .header("Content-Type", "application/ld+json")
+            .set_payload(
+                r#"[
+                    { "@id": "http://e/a", "http://e/p": { "@id": "http://e/o" } },
+                    { "@id": "http://e/g", "@graph": [{ "@id": "http://e/b", "http://e/p": 1 }] }
+                ]"#,
+            )
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::CREATED);
+
+        let req = test::TestRequest::get()
+            .uri("/store")
+            .header("Accept", "application/n-quads")
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(
+            body,
+            "<http://e/a> <http://e/p> <http://e/o> .\n\
+             <http://e/b> <http://e/p> \"1\"^^<http://www.w3.org/2001/XMLSchema#integer> <http://e/g> .\n"
+        );
+
+        let req = test::TestRequest::get()
+            .uri("/store")
+            .header("Accept", "application/ld+json")
+            .to_request();
+        let body: serde_json::Value = test::read_response_json(&mut app, req).await;
+        assert_eq!(body[1]["@id"], "http://e/g");
+        assert_eq!(body[1]["@graph"][0]["@id"], "http://e/b");
+    }
+
+    #[actix_rt::test]
+    async fn relative_iris_in_uploads() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::put()
+            .uri("/store?graph=http%3A%2F%2Fe%2Fdir%2Fg")
+            .header("Content-Type", "application/ld+json")
+            .set_payload(r#"{ "@id": "s", "http://e/p": { "@id": "o" } }"#)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::CREATED);
+
+        let req = test::TestRequest::get()
+            .uri("/store?graph=http%3A%2F%2Fe%2Fdir%2Fg")
+            .header("Accept", "application/n-triples")
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(body, "<http://e/dir/s> <http://e/p> <http://e/dir/o> .\n");
+
+        let req = test::TestRequest::put()
+            .uri("http://localhost/store")
+            .header("Content-Type", "application/ld+json")
+            .set_payload(r#"{ "@id": "a", "http://e/p": { "@id": "b" } }"#)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+
+        let req = test::TestRequest::post()
+            .uri("http://localhost/store?default")
+            .header("Content-Type", "text/turtle")
+            .set_payload("<c> <http://e/p> <d> .")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+
+        let req = test::TestRequest::get()
+            .uri("/store")
+            .header("Accept", "application/n-quads")
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(
+            body,
+            "<http://localhost/a> <http://e/p> <http://localhost/b> .\n\
+             <http://localhost/c> <http://e/p> <http://localhost/d> .\n"
+        );
+        assert_eq!(app_state.store.len(), 2);
+    }
+
+    #[actix_rt::test]
+    async fn get_large_graph() {
+        let path = tempdir().unwrap();
+        let store = SledStore::open(path.path()).unwrap();
+        let graph = model::NamedNode::new("http://localhost/store/big").unwrap();
+        let data: String = (0..2000)
+            .map(|i| {
+                format!(
+                    "<http://example.com/s{}> <http://example.com/p> \"{}\" .\n",
+                    i, i
+                )
+            })
+            .collect();
+        store
+            .load_graph(data.as_bytes(), GraphFormat::NTriples, &graph, None)
+            .unwrap();
+        let app_state = web::Data::new(AppState::new(store));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::get()
+            .uri("http://localhost/store/big")
+            .header("Accept", "application/n-triples")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let body = test::read_body(resp).await;
+        assert_eq!(body.iter().filter(|b| **b == b'\n').count(), 2000);
+    }
+
+    #[actix_rt::test]
+    async fn stalled_dump_releases_its_thread() {
+        let path = tempdir().unwrap();
+        let store = SledStore::open(path.path()).unwrap();
+        let data: String = (0..5000)
+            .map(|i| {
+                format!(
+                    "<http://example.com/s{}> <http://example.com/p> \"{}\" .\n",
+                    i, i
+                )
+            })
+            .collect();
+        store
+            .load_graph(
+                data.as_bytes(),
+                GraphFormat::NTriples,
+                &model::GraphName::DefaultGraph,
+                None,
+            )
+            .unwrap();
+        let app_state = web::Data::new(AppState::new(store).with_blocking_threads(1).with_limits(
+            crate::Limits {
+                write_idle_timeout: 1,
+                ..crate::Limits::default()
+            },
+        ));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::get()
+            .uri("/store")
+            .header("Accept", "application/n-quads")
+            .to_request();
+        // The body is never read.
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        let freed =
+            actix_web::rt::time::timeout(Duration::from_secs(10), app_state.run(|_| Ok(()))).await;
+        assert!(matches!(freed, Ok(Ok(()))));
+        drop(resp);
+    }
+
+    #[actix_rt::test]
+    async fn too_many_dumps() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(
+            AppState::new(SledStore::open(path.path()).unwrap()).with_limits(crate::Limits {
+                max_concurrent_dumps: 1,
+                ..crate::Limits::default()
+            }),
+        );
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let running = app_state.blocking.start_read(&app_state.dumps, 1).unwrap();
+        let req = test::TestRequest::get()
+            .uri("/store")
+            .header("Accept", "application/n-quads")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+        drop(running);
+        let req = test::TestRequest::get()
+            .uri("/store")
+            .header("Accept", "application/n-quads")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn put_wrong_graph_file() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        app_state
+            .store
+            .update("INSERT DATA { GRAPH <http://example.com/g> { <http://example.com/a> <http://example.com/p> 2 } }")
+            .unwrap();
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        for graph in &["g", "new"] {
+            let req = test::TestRequest::put()
+                .uri(&format!(
+                    "/store?graph=http%3A%2F%2Fexample.com%2F{}",
+                    graph
+                ))
+                .header("Content-Type", "text/turtle")
+                .set_payload(
+                    "<http://example.com/s> <http://example.com/p> 1 .\n<http://example.com>",
+                )
+                .to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+        }
+        assert_eq!(app_state.store.len(), 1);
+        assert_eq!(app_state.store.named_graphs().count(), 1);
+
+        let req = test::TestRequest::put()
+            .uri("/store?graph=http%3A%2F%2Fexample.com%2Fg")
+            .header("Content-Type", "text/turtle")
+            .set_payload("<http://example.com/s> <http://example.com/p> 1 .")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+        let req = test::TestRequest::get()
+            .uri("/store?graph=http%3A%2F%2Fexample.com%2Fg")
+            .header("Accept", "application/n-triples")
+            .to_request();
+        let body = test::read_response(&mut app, req).await;
+        assert_eq!(
+            body,
+            "<http://example.com/s> <http://example.com/p> \"1\"^^<http://www.w3.org/2001/XMLSchema#integer> .\n"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn put_wrong_dataset_file() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::put()
+            .uri("/store")
+            .header("Content-Type", "application/trig")
+            .set_payload("<http://example.com/s> <http://example.com/p> 1 .\n<http://example.com>")
+            .to_request();
+        app_state
+            .store
+            .update("INSERT DATA { <http://example.com/a> <http://example.com/p> 2 }")
+            .unwrap();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+        assert_eq!(app_state.store.len(), 1);
+    }
+
+    #[actix_rt::test]
+    async fn queries_see_the_old_or_the_new_dataset() {
+        use oxigraph::io::DatasetFormat;
+        use std::collections::HashSet;
+
+        let dataset = |dir: &tempfile::TempDir, value: &str| {
+            let store = SledStore::open(dir.path()).unwrap();
+            let data: String = (0..500)
+                .map(|i| {
+                    format!(
+                        "<http://e/s{}> <http://e/p> \"{}\" <http://e/g> .\n",
+                        i, value
+                    )
+                })
+                .collect();
+            store
+                .load_dataset(data.as_bytes(), DatasetFormat::NQuads, None)
+                .unwrap();
+            store
+        };
+        let (path, old_path, new_path) =
+            (tempdir().unwrap(), tempdir().unwrap(), tempdir().unwrap());
+        let app_state = AppState::new(dataset(&path, "old")).with_blocking_threads(4);
+        let old = dataset(&old_path, "old");
+        let new = dataset(&new_path, "new");
+
+        let replacements = async {
+            for i in 0..10 {
+                let staged = if i % 2 == 0 { new.clone() } else { old.clone() };
+                app_state
+                    .replace(move |store| Ok(replace_dataset(store, &staged)?))
+                    .await
+                    .unwrap();
+            }
+        };
+        let reads = async {
+            for _ in 0..50 {
+                let (len, values) = app_state
+                    .run(|store| {
+                        let mut len = 0;
+                        let mut values = HashSet::new();
+                        for quad in store.iter() {
+                            len += 1;
+                            values.insert(quad?.object.to_string());
+                        }
+                        Ok((len, values))
+                    })
+                    .await
+                    .unwrap();
+                assert_eq!(len, 500);
+                assert_eq!(values.len(), 1, "saw a mix of datasets: {:?}", values);
+            }
+        };
+        futures::join!(replacements, reads);
+    }
+}
@@ -0,0 +1,360 @@
+use crate::blocking::Blocking;
+use crate::AppError;
+use actix_web::{error, rt, web};
+use futures::channel::mpsc;
+use futures::executor::block_on;
+use futures::task::{self, Context, Poll};
+use futures::{SinkExt, Stream, StreamExt};
+use std::io::{self, BufRead, Read, Write};
+use std::mem;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Size of the chunks handed to the HTTP layer.
+const CHUNK_SIZE: usize = 16 * 1024;
+/// Number of chunks that may be waiting for the client before writes block.
+const CHANNEL_CAPACITY: usize = 4;
+
+/// A streaming response body fed by a [`ChunkWriter`].
+pub type Body = mpsc::Receiver<Result<web::Bytes, io::Error>>;
+
+/// A writer that forwards its output to a [`Body`] in fixed-size chunks.
+///
+/// Writes block while the channel is full, so a slow client slows down the
+/// serializer instead of making the server buffer the whole response.
+/// Once the client goes away writes fail with `BrokenPipe`, and once it has
+/// read nothing for the idle timeout, or the deadline has passed, they fail
+/// with `TimedOut`, so that a client that stops reading doesn't keep the
+/// thread writing forever.
+pub struct ChunkWriter {
+    buffer: Vec<u8>,
+    sender: mpsc::Sender<Result<web::Bytes, io::Error>>,
+    /// A sender of its own has room for one more item whatever the client
+    /// does: it ends the body on errors.
+    spare: mpsc::Sender<Result<web::Bytes, io::Error>>,
+    deadline: Option<Instant>,
+    idle: Option<Duration>,
+}
+
+impl ChunkWriter {
+    pub fn new() -> (ChunkWriter, Body) {
+        let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+        (
+            ChunkWriter {
+                buffer: Vec::with_capacity(CHUNK_SIZE),
+                spare: sender.clone(),
+                sender,
+                deadline: None,
+                idle: None,
+            },
+            receiver,
+        )
+    }
+
+    /// Makes writes fail with `TimedOut` once `deadline` has passed.
+    pub fn set_deadline(&mut self, deadline: Instant) {
+        self.deadline = Some(deadline);
+    }
+
+    /// Makes writes fail with `TimedOut` once the client has read nothing
+    /// for `idle` while the channel is full.
+    pub fn set_idle_timeout(&mut self, idle: Duration) {
+        self.idle = Some(idle);
+    }
+
+    /// Ends the body with `error` so the client sees a truncated response.
+    pub fn abort(mut self, error: io::Error) {
+        let _ = self.flush();
+        let _ = self.spare.try_send(Err(error));
+    }
+
+    /// Sends `item`, waiting for room in the channel until the client has
+    /// read nothing for the idle timeout or the deadline has passed.
+    fn send(&mut self, item: Result<web::Bytes, io::Error>) -> io::Result<()> {
+        let waker = task::waker(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let idle = self.idle.and_then(|idle| Instant::now().checked_add(idle));
+        loop {
+            match self.sender.poll_ready(&mut cx) {
+                Poll::Ready(Ok(())) => break,
+                Poll::Ready(Err(_)) => return Err(disconnected()),
+                Poll::Pending => {}
+            }
+            let now = Instant::now();
+            if matches!(self.deadline, Some(deadline) if now >= deadline) {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "the deadline has passed",
+                ));
+            }
+            if matches!(idle, Some(idle) if now >= idle) {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "the client stopped reading",
+                ));
+            }
+            match self.deadline.into_iter().chain(idle).min() {
+                Some(until) => thread::park_timeout(until - now),
+                None => thread::park(),
+            }
+        }
+        self.sender.start_send(item).map_err(|_| disconnected())
+    }
+}
+
+fn disconnected() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "the client has disconnected")
+}
+
+/// Wakes the thread waiting in [`ChunkWriter::send`] once the channel has
+/// room again.
+struct Unpark(thread::Thread);
+
+impl task::ArcWake for Unpark {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.0.unpark();
+    }
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if matches!(self.deadline, Some(deadline) if Instant::now() >= deadline) {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "the deadline has passed",
+            ));
+        }
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.buffer.is_empty() {
+            let chunk = mem::replace(&mut self.buffer, Vec::with_capacity(CHUNK_SIZE));
+            self.send(Ok(chunk.into()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs `write` on `blocking` and returns its output as a body.
+///
+/// An error returned by `write` aborts the body after whatever was already
+/// written has been sent.
+pub(crate) fn spawn<F>(blocking: &Blocking, write: F) -> Body
+where
+    F: FnOnce(&mut ChunkWriter) -> io::Result<()> + Send + 'static,
+{
+    let (mut writer, body) = ChunkWriter::new();
+    blocking.execute(move || {
+        if let Err(err) = write(&mut writer).and_then(|()| writer.flush()) {
+            log::warn!("aborting response body: {}", err);
+            writer.abort(err);
+        }
+    });
+    body
+}
+
+/// A reader over a request payload, fed chunk by chunk by [`read_payload`].
+pub struct PayloadReader {
+    receiver: mpsc::Receiver<Result<web::Bytes, io::Error>>,
+    chunk: web::Bytes,
+}
+
+impl Read for PayloadReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl BufRead for PayloadReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.chunk.is_empty() {
+            match block_on(self.receiver.next()) {
+                Some(Ok(chunk)) => self.chunk = chunk,
+                Some(Err(err)) => return Err(err),
+                None => break,
+            }
+        }
+        Ok(&self.chunk)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        let _ = self.chunk.split_to(amt);
+    }
+}
+
+/// Runs `read` on `blocking` over the request payload.
+///
+/// The payload is forwarded as it arrives, so the body is never held in
+/// memory as a whole. Once more than `limit` bytes have been received the
+/// reader fails and the upload is rejected with `PayloadTooLarge`. If the
+/// client sends nothing for `idle`, the reader fails too and the upload is
+/// rejected with `UploadTimeout`, so that slow clients don't keep the
+/// blocking threads waiting.
+pub(crate) async fn read_payload<P, F, T>(
+    blocking: &Blocking,
+    mut payload: P,
+    limit: u64,
+    idle: Duration,
+    read: F,
+) -> Result<T, AppError>
+where
+    P: Stream<Item = Result<web::Bytes, error::PayloadError>> + Unpin,
+    F: FnOnce(PayloadReader) -> io::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let (mut sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+    let reader = PayloadReader {
+        receiver,
+        chunk: web::Bytes::new(),
+    };
+    let feed = async move {
+        let mut size = 0;
+        loop {
+            let chunk = match rt::time::timeout(idle, payload.next()).await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break,
+                Err(_) => {
+                    let _ = sender
+                        .send(Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            "the client stopped sending",
+                        )))
+                        .await;
+                    return Err(AppError::UploadTimeout(idle));
+                }
+            };
+            let (item, result) = match chunk {
+                Ok(chunk) => {
+                    size += chunk.len() as u64;
+                    if size > limit {
+                        (
+                            Err(io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                "payload too large",
+                            )),
+                            Err(AppError::PayloadTooLarge(limit)),
+                        )
+                    } else {
+                        (Ok(chunk), Ok(()))
+                    }
+                }
+                Err(err) => (
+                    Err(io::Error::new(io::ErrorKind::InvalidData, err.to_string())),
+                    Err(AppError::from(actix_web::Error::from(err))),
+                ),
+            };
+            let stop = item.is_err();
+            // The reader hangs up early when it fails to parse the payload.
+            if sender.send(item).await.is_err() || stop {
+                return result;
+            }
+        }
+        Ok(())
+    };
+    let (fed, read) = futures::join!(feed, blocking.run(move || read(reader)));
+    fed?;
+    read.map_err(|err| match err {
+        error::BlockingError::Error(err) => AppError::from_load_error(err),
+        error::BlockingError::Canceled => AppError::InternalServerError("loading was cancelled"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_rt::test]
+    async fn sends_everything_written() {
+        let body = spawn(&Blocking::new(Some(1)), |writer| {
+            for _ in 0..CHUNK_SIZE {
+                writer.write_all(b"ab")?;
+            }
+            Ok(())
+        });
+        let chunks: Vec<_> = body.collect().await;
+        assert!(chunks.len() > 1);
+        let len: usize = chunks.into_iter().map(|chunk| chunk.unwrap().len()).sum();
+        assert_eq!(len, 2 * CHUNK_SIZE);
+    }
+
+    #[actix_rt::test]
+    async fn error_ends_body() {
+        let body = spawn(&Blocking::new(Some(1)), |writer| {
+            writer.write_all(b"partial")?;
+            Err(io::Error::new(io::ErrorKind::InvalidData, "boom"))
+        });
+        let chunks: Vec<_> = body.collect().await;
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].as_ref().unwrap().as_ref(), b"partial");
+        assert!(chunks[1].is_err());
+    }
+
+    #[actix_rt::test]
+    async fn idle_uploads_time_out() {
+        let payload = futures::stream::iter(vec![Ok(web::Bytes::from_static(b"<a> "))])
+            .chain(futures::stream::pending());
+        let result = read_payload(
+            &Blocking::new(Some(1)),
+            payload,
+            1024,
+            Duration::from_millis(50),
+            |mut reader| {
+                let mut body = Vec::new();
+                reader.read_to_end(&mut body)?;
+                Ok(body)
+            },
+        )
+        .await;
+        assert!(matches!(result, Err(AppError::UploadTimeout(_))));
+    }
+
+    #[test]
+    fn write_fails_without_receiver() {
+        let (mut writer, body) = ChunkWriter::new();
+        drop(body);
+        writer.write_all(b"data").unwrap();
+        assert_eq!(
+            writer.flush().unwrap_err().kind(),
+            io::ErrorKind::BrokenPipe
+        );
+    }
+
+    #[test]
+    fn write_fails_once_the_client_stops_reading() {
+        let (mut writer, body) = ChunkWriter::new();
+        writer.set_idle_timeout(Duration::from_millis(50));
+        let chunk = vec![0; CHUNK_SIZE];
+        let started = Instant::now();
+        let err = (0..2 * CHANNEL_CAPACITY)
+            .try_for_each(|_| writer.write_all(&chunk))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(started.elapsed() >= Duration::from_millis(50));
+
+        // The error still gets through, after the chunks already sent.
+        writer.abort(err);
+        let chunks: Vec<_> = block_on(body.collect());
+        assert!(chunks[..chunks.len() - 1].iter().all(Result::is_ok));
+        assert!(chunks.last().unwrap().is_err());
+    }
+
+    #[test]
+    fn write_fails_after_deadline() {
+        let (mut writer, _body) = ChunkWriter::new();
+        writer.set_deadline(Instant::now());
+        assert_eq!(
+            writer.write(b"data").unwrap_err().kind(),
+            io::ErrorKind::TimedOut
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use crate::error::{AppError, InnerError};
+use crate::{base_url, url_query, AppState};
+use actix_web::{http, web, HttpRequest, HttpResponse};
+use oxigraph::model;
+use oxigraph::sparql;
+
+pub(crate) async fn post_update(
+    request: HttpRequest,
+    payload: web::Payload,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    use actix_web::FromRequest;
+    use http::header;
+    use mime::Mime;
+    use std::str::FromStr;
+
+    if let Some(content_type) = request.headers().get(header::CONTENT_TYPE) {
+        let content_type: Mime = Mime::from_str(content_type.to_str()?)?;
+        let mut payload = payload.into_inner();
+        if content_type.essence_str() == "application/sparql-update" {
+            let buffer = String::from_request(&request, &mut payload).await?;
+
+            configure_and_evaluate_sparql_update(state, &url_query(&request), Some(buffer), request)
+                .await
+        } else if content_type.essence_str() == "application/x-www-form-urlencoded" {
+            let buffer = web::Bytes::from_request(&request, &mut payload).await?;
+            configure_and_evaluate_sparql_update(state, &buffer, None, request).await
+        } else {
+            Err(AppError::UnsupportedMediaType(content_type.to_string()))
+        }
+    } else {
+        Err(AppError::BadRequest(InnerError::Str(
+            "No Content-Type given",
+        )))
+    }
+}
+
+async fn configure_and_evaluate_sparql_update(
+    state: web::Data<AppState>,
+    encoded: &[u8],
+    mut update: Option<String>,
+    request: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let mut default_graph_uris = Vec::new();
+    let mut named_graph_uris = Vec::new();
+    for (k, v) in form_urlencoded::parse(encoded) {
+        match k.as_ref() {
+            "update" => {
+                if update.is_some() {
+                    return Err(AppError::BadRequest(InnerError::Str(
+                        "Multiple update parameters provided",
+                    )));
+                }
+                update = Some(v.into_owned())
+            }
+            "using-graph-uri" => default_graph_uris.push(v.into_owned()),
+            "using-named-graph-uri" => named_graph_uris.push(v.into_owned()),
+            _ => {
+                return Err(AppError::BadRequestString(format!(
+                    "Unexpected parameter: {}",
+                    k
+                )));
+            }
+        }
+    }
+    if let Some(update) = update {
+        evaluate_sparql_update(state, update, default_graph_uris, named_graph_uris, request).await
+    } else {
+        Err(AppError::BadRequest(InnerError::Str(
+            "You should set the 'update' parameter",
+        )))
+    }
+}
+
+async fn evaluate_sparql_update(
+    state: web::Data<AppState>,
+    update: String,
+    default_graph_uris: Vec<String>,
+    named_graph_uris: Vec<String>,
+    request: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    use model::{GraphName, NamedNode, NamedOrBlankNode};
+
+    let base_iri = base_url(&request, None)?.to_string();
+    let default_graph_uris = default_graph_uris
+        .into_iter()
+        .map(|e| Ok(NamedNode::new(e)?.into()))
+        .collect::<Result<Vec<GraphName>, AppError>>()?;
+    let named_graph_uris = named_graph_uris
+        .into_iter()
+        .map(|e| Ok(NamedNode::new(e)?.into()))
+        .collect::<Result<Vec<NamedOrBlankNode>, AppError>>()?;
+    // As for queries, the parsed update can't be sent to the blocking pool.
+    prepare_update(&update, &base_iri, &default_graph_uris, &named_graph_uris)?;
+    let _writing = state.writing();
+    state
+        .run(move |store| {
+            let update =
+                prepare_update(&update, &base_iri, &default_graph_uris, &named_graph_uris)?;
+            Ok(store.update(update)?)
+        })
+        .await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+fn prepare_update(
+    update: &str,
+    base_iri: &str,
+    default_graph_uris: &[model::GraphName],
+    named_graph_uris: &[model::NamedOrBlankNode],
+) -> Result<sparql::Update, AppError> {
+    use sparql::{algebra::GraphUpdateOperation, Update};
+
+    let mut update = Update::parse(update, Some(base_iri))?;
+    if !default_graph_uris.is_empty() || !named_graph_uris.is_empty() {
+        for operation in &mut update.operations {
+            if let GraphUpdateOperation::DeleteInsert { using, .. } = operation {
+                if !using.is_default_dataset() {
+                    return Err(AppError::BadRequest(InnerError::Str(
+                        "using-graph-uri and using-named-graph-uri must not be used with a SPARQL UPDATE containing USING",
+                    )));
+                }
+                using.set_default_graph(default_graph_uris.to_vec());
+                using.set_available_named_graphs(named_graph_uris.to_vec());
+            }
+        }
+    }
+    Ok(update)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config_app, AppState};
+    use actix_web::{http, test, App};
+    use oxigraph::SledStore;
+    use tempfile::tempdir;
+
+    #[actix_rt::test]
+    async fn post_update() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::post()
+            .uri("http://localhost/update")
+            .header("Content-Type", "application/sparql-update")
+            .set_payload(
+                "INSERT DATA { <http://example.com> <http://example.com> <http://example.com> }",
+            )
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+        assert_eq!(app_state.generation(), 1);
+    }
+
+    #[actix_rt::test]
+    async fn post_bad_update() {
+        let path = tempdir().unwrap();
+        let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+        let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+        let req = test::TestRequest::post()
+            .uri("http://localhost/update")
+            .header("Content-Type", "application/sparql-update")
+            .set_payload("INSERT")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::error::AppError;
+use crate::graphs::write_triples;
+use crate::negotiation::graph_content_negotiation;
+use crate::stats::Summary;
+use crate::{base_url, AppState};
+use actix_web::{http, web, HttpRequest, HttpResponse};
+use oxigraph::model::vocab::{rdf, xsd};
+use oxigraph::model::{BlankNode, Literal, NamedNode, Triple};
+
+const VOID: &str = "http://rdfs.org/ns/void#";
+const FOAF_PRIMARY_TOPIC: &str = "http://xmlns.com/foaf/0.1/primaryTopic";
+
+/// Describes the store summed up in `summary` as `<document#dataset>`, the
+/// server being mounted where `document` is `.../.well-known/void`.
+fn describe(summary: &Summary, document: &str) -> Vec<Triple> {
+    let root = document.trim_end_matches("/.well-known/void");
+    let dataset = NamedNode::new_unchecked(format!("{}#dataset", document));
+    let iri = |iri: String| NamedNode::new_unchecked(iri);
+    let mut triples = vec![
+        Triple::new(
+            iri(document.to_string()),
+            rdf::TYPE,
+            void("DatasetDescription"),
+        ),
+        Triple::new(
+            iri(document.to_string()),
+            NamedNode::new_unchecked(FOAF_PRIMARY_TOPIC),
+            dataset.clone(),
+        ),
+        Triple::new(dataset.clone(), rdf::TYPE, void("Dataset")),
+        Triple::new(
+            dataset.clone(),
+            void("sparqlEndpoint"),
+            iri(format!("{}/query", root)),
+        ),
+        Triple::new(
+            dataset.clone(),
+            void("dataDump"),
+            iri(format!("{}/store", root)),
+        ),
+        Triple::new(dataset.clone(), void("triples"), count(summary.quads)),
+        Triple::new(dataset.clone(), void("entities"), count(summary.entities)),
+        Triple::new(
+            dataset.clone(),
+            void("distinctSubjects"),
+            count(summary.distinct_subjects),
+        ),
+        Triple::new(
+            dataset.clone(),
+            void("distinctObjects"),
+            count(summary.distinct_objects),
+        ),
+        Triple::new(
+            dataset.clone(),
+            void("classes"),
+            count(summary.classes.len()),
+        ),
+        Triple::new(
+            dataset.clone(),
+            void("properties"),
+            count(summary.properties.len()),
+        ),
+    ];
+    for example in &summary.examples {
+        triples.push(Triple::new(
+            dataset.clone(),
+            void("exampleResource"),
+            example.clone(),
+        ));
+    }
+    for (class, entities) in &summary.classes {
+        let partition = BlankNode::default();
+        triples.push(Triple::new(
+            dataset.clone(),
+            void("classPartition"),
+            partition.clone(),
+        ));
+        triples.push(Triple::new(partition.clone(), void("class"), class.clone()));
+        triples.push(Triple::new(partition, void("entities"), count(*entities)));
+    }
+    for (property, uses) in &summary.properties {
+        let partition = BlankNode::default();
+        triples.push(Triple::new(
+            dataset.clone(),
+            void("propertyPartition"),
+            partition.clone(),
+        ));
+        triples.push(Triple::new(
+            partition.clone(),
+            void("property"),
+            property.clone(),
+        ));
+        triples.push(Triple::new(partition, void("triples"), count(*uses)));
+    }
+    triples
+}
+
+fn void(name: &str) -> NamedNode {
+    NamedNode::new_unchecked(format!("{}{}", VOID, name))
+}
+
+fn count(value: usize) -> Literal {
+    Literal::new_typed_literal(value.to_string(), xsd::INTEGER)
+}
+
+pub(crate) async fn get_void(
+    request: HttpRequest,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let document = base_url(&request, None)?.to_string();
+    let format = graph_content_negotiation(request)?;
+    let summary = state.summary.summary(&state).await?;
+    let mut body = Vec::new();
+    write_triples(&describe(&summary, &document), format, None, &mut body)?;
+    Ok(HttpResponse::Ok()
+        .header(http::header::CONTENT_TYPE, format.media_type())
+        .body(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_app;
+    use actix_web::{test, App};
+    use oxigraph::io::{GraphFormat, GraphParser};
+    use oxigraph::SledStore;
+    use tempfile::tempdir;
+
+    fn store() -> (tempfile::TempDir, SledStore) {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        store
+            .update(
+                "PREFIX e: <http://e/>
+                 INSERT DATA {
+                     e:a a e:C ; e:p 1 .
+                     _:b a e:C .
+                     GRAPH e:g { e:a a e:C ; e:p 2 }
+                 }",
+            )
+            .unwrap();
+        (dir, store)
+    }
+
+    #[actix_rt::test]
+    async fn get_void_turtle() {
+        let (_dir, store) = store();
+        let app_state = web::Data::new(AppState::new(store));
+        let mut app = test::init_service(App::new().configure(config_app(app_state))).await;
+        let req = test::TestRequest::get()
+            .uri("http://localhost/.well-known/void")
+            .header("Accept", "text/turtle")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let body = test::read_body(resp).await;
+        let triples = GraphParser::from_format(GraphFormat::Turtle)
+            .read_triples(body.as_ref())
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let object = |predicate: &str| {
+            triples
+                .iter()
+                .find(|t| t.predicate == void(predicate))
+                .map(|t| t.object.to_string())
+                .unwrap()
+        };
+        assert_eq!(object("sparqlEndpoint"), "<http://localhost/query>");
+        assert_eq!(object("dataDump"), "<http://localhost/store>");
+        assert_eq!(
+            object("triples"),
+            "\"5\"^^<http://www.w3.org/2001/XMLSchema#integer>"
+        );
+        assert_eq!(
+            triples
+                .iter()
+                .filter(|t| t.predicate == void("propertyPartition"))
+                .count(),
+            2
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use actix_web::{http, test, web, App};
+use knowgraf::{config_app, AppState};
+use oxigraph::SledStore;
+use tempfile::tempdir;
+
+#[actix_rt::test]
+async fn graph_store_protocol() {
+    // Tests from https://www.w3.org/2009/sparql/docs/tests/data-sparql11/http-rdf-update/
+
+    let path = tempdir().unwrap();
+    let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+    let mut app = test::init_service(App::new().configure(config_app(app_state.clone()))).await;
+
+    // PUT - Initial state
+    println!("PUT - Initial state");
+    let req = test::TestRequest::put()
+        .uri("http://localhost/store/person/1.ttl")
+        .header("Content-Type", "text/turtle; charset=utf-8")
+        .set_payload(
+            "
+@prefix foaf: <http://xmlns.com/foaf/0.1/> .
+@prefix v: <http://www.w3.org/2006/vcard/ns#> .
+
+<http://$HOST$/$GRAPHSTORE$/person/1> a foaf:Person;
+foaf:businessCard [
+    a v:VCard;
+    v:fn \"John Doe\"
+].
+",
+        )
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::CREATED);
+
+    // HEAD on an existing graph
+    println!("HEAD on an existing graph");
+    let req = test::TestRequest::default()
+        .method(http::Method::HEAD)
+        .uri("http://localhost/store/person/1.ttl")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    // HEAD on an non-existing graph
+    println!("HEAD on an non-existing graph");
+    let req = test::TestRequest::default()
+        .method(http::Method::HEAD)
+        .uri("http://localhost/store/person/4.ttl")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+
+    // PUT - graph already in store
+    println!("PUT - graph already in store");
+    let req = test::TestRequest::put()
+        .uri("http://localhost/store/person/1.ttl")
+        .header("Content-Type", "text/turtle; charset=utf-8")
+        .set_payload(
+            "
+@prefix foaf: <http://xmlns.com/foaf/0.1/> .
+@prefix v: <http://www.w3.org/2006/vcard/ns#> .
+
+<http://$HOST$/$GRAPHSTORE$/person/1> a foaf:Person;
+foaf:businessCard [
+    a v:VCard;
+    v:fn \"Jane Doe\"
+].
+",
+        )
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+
+    // GET of PUT - graph already in store
+    println!("GET of PUT - graph already in store");
+    let req = test::TestRequest::get()
+        .uri("http://localhost/store/person/1.ttl")
+        .header("Accept", "text/turtle")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    // PUT - default graph
+    println!("PUT - default graph");
+    let req = test::TestRequest::put()
+        .uri("http://localhost/store?default")
+        .header("Content-Type", "text/turtle; charset=utf-8")
+        .set_payload(
+            "
+@prefix foaf: <http://xmlns.com/foaf/0.1/> .
+@prefix v: <http://www.w3.org/2006/vcard/ns#> .
+
+[]  a foaf:Person;
+foaf:businessCard [
+    a v:VCard;
+    v:given-name \"Alice\"
+] .
+",
+        )
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::NO_CONTENT); // The default graph always exists in Oxigraph
+
+    // GET of PUT - default graph
+    println!("GET of PUT - default graph");
+    let req = test::TestRequest::get()
+        .uri("http://localhost/store?default")
+        .header("Accept", "text/turtle")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    // PUT - mismatched payload
+    println!("PUT - mismatched payload");
+    let req = test::TestRequest::put()
+        .uri("http://localhost/store/person/1.ttl")
+        .header("Content-Type", "text/turtle; charset=utf-8")
+        .set_payload("@prefix fo")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+    // PUT - empty graph
+    println!("PUT - empty graph");
+    let req = test::TestRequest::put()
+        .uri("http://localhost/store/person/2.ttl")
+        .header("Content-Type", "text/turtle; charset=utf-8")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::CREATED);
+
+    // GET of PUT - empty graph
+    println!("GET of PUT - empty graph");
+    let req = test::TestRequest::get()
+        .uri("http://localhost/store/person/2.ttl")
+        .header("Accept", "text/turtle")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    // PUT - replace empty graph
+    println!("PUT - replace empty graph");
+    let req = test::TestRequest::put()
+        .uri("http://localhost/store/person/2.ttl")
+        .header("Content-Type", "text/turtle; charset=utf-8")
+        .set_payload(
+            "
+@prefix foaf: <http://xmlns.com/foaf/0.1/> .
+@prefix v: <http://www.w3.org/2006/vcard/ns#> .
+
+[]  a foaf:Person;
+foaf:businessCard [
+    a v:VCard;
+    v:given-name \"Alice\"
+] .
+",
+        )
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+
+    // GET of replacement for empty graph
+    let req = test::TestRequest::get()
+        .uri("http://localhost/store/person/2.ttl")
+        .header("Accept", "text/turtle")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    // DELETE - existing graph
+    println!("DELETE - existing graph");
+    let req = test::TestRequest::delete()
+        .uri("http://localhost/store/person/2.ttl")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+
+    // GET of DELETE - existing graph
+    let req = test::TestRequest::get()
+        .uri("http://localhost/store/person/2.ttl")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+
+    // DELETE - non-existent graph
+    let req = test::TestRequest::delete()
+        .uri("http://localhost/store/person/2.ttl")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+    let body = test::read_body(resp).await;
+    let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(problem["code"], "graph_not_found");
+
+    // POST - existing graph
+    let req = test::TestRequest::post()
+        .uri("http://localhost/store/person/1.ttl")
+        .header("Content-Type", "text/turtle; charset=utf-8")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+
+    // TODO: POST - multipart/form-data
+    // TODO: GET of POST - multipart/form-data
+
+    // POST - create new graph
+    let req = test::TestRequest::post()
+        .uri("http://localhost/store")
+        .header("Content-Type", "text/turtle; charset=utf-8")
+        .set_payload(
+            "
+@prefix foaf: <http://xmlns.com/foaf/0.1/> .
+@prefix v: <http://www.w3.org/2006/vcard/ns#> .
+
+[]  a foaf:Person;
+foaf:businessCard [
+    a v:VCard;
+    v:given-name \"Alice\"
+] .
+",
+        )
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::CREATED);
+    let location = resp.headers().get("Location").unwrap().to_str().unwrap();
+
+    // GET of POST - create new graph
+    let req = test::TestRequest::get()
+        .uri(location)
+        .header("Accept", "text/turtle")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    // POST - empty graph to existing graph
+    let req = test::TestRequest::post()
+        .uri(location)
+        .header("Content-Type", "text/turtle; charset=utf-8")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+
+    // GET of POST - after noop
+    let req = test::TestRequest::get()
+        .uri(location)
+        .header("Accept", "text/turtle")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn mounted_under_a_prefix() {
+    let path = tempdir().unwrap();
+    let app_state = web::Data::new(AppState::new(SledStore::open(path.path()).unwrap()));
+    let mut app = test::init_service(
+        App::new().service(web::scope("/sparql").configure(config_app(app_state.clone()))),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("http://localhost/sparql/store")
+        .header("Content-Type", "application/n-triples")
+        .set_payload("<http://example.com/s> <http://example.com/p> <http://example.com/o> .\n")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::CREATED);
+    let location = resp
+        .headers()
+        .get(http::header::LOCATION)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+    assert!(location.starts_with("http://localhost/sparql/store/"));
+
+    let req = test::TestRequest::get()
+        .uri("http://localhost/sparql/query?query=ASK%20{%20GRAPH%20?g%20{%20?s%20?p%20?o%20}%20}")
+        .header("Accept", "application/sparql-results+json")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let body = test::read_body(resp).await;
+    assert!(String::from_utf8(body.to_vec()).unwrap().contains("true"));
+}